@@ -0,0 +1,59 @@
+use crate::events::GraphEvent;
+use crate::reducer::EventReducer;
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences, IntoNodeReferences, NodeIndexable};
+use std::collections::HashMap;
+
+/// Node ids of each weakly-connected component of the graph
+///
+/// Edge direction is ignored. Components are listed in order of their first
+/// node, and ids within a component keep declaration order.
+pub fn weak_components(events: &[GraphEvent]) -> Vec<Vec<String>> {
+    let mut reducer = EventReducer::new();
+    reducer.apply_all(events);
+    let graph = &reducer.data().graph;
+
+    let mut sets = UnionFind::new(graph.node_bound());
+    for edge in graph.edge_references() {
+        sets.union(edge.source().index(), edge.target().index());
+    }
+
+    let mut components: Vec<Vec<String>> = Vec::new();
+    let mut component_of_root = HashMap::new();
+    for (index, node) in graph.node_references() {
+        let root = sets.find(index.index());
+        let component = *component_of_root.entry(root).or_insert_with(|| {
+            components.push(Vec::new());
+            components.len() - 1
+        });
+        components[component].push(node.id.clone());
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dot;
+
+    #[test]
+    fn test_two_disconnected_triangles() {
+        let events = dot::parse(
+            r"
+            digraph {
+                A -> B;
+                B -> C;
+                C -> A;
+                X -> Y;
+                Z -> Y;
+                X -> Z;
+            }
+        ",
+        );
+
+        let components = weak_components(&events);
+
+        assert_eq!(components, vec![vec!["A", "B", "C"], vec!["X", "Y", "Z"]]);
+    }
+}
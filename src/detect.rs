@@ -0,0 +1,134 @@
+use crate::events::GraphEvent;
+use crate::{dot, plantuml};
+
+/// Which parser `parse_auto` chose, and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedKind {
+    /// Only a DOT graph header was found
+    Dot,
+    /// Only a `@startuml` block was found
+    PlantUml,
+    /// Both were found; the DOT graph header appeared first
+    DotOverPlantUml,
+    /// Both were found; `@startuml` appeared first
+    PlantUmlOverDot,
+}
+
+impl DetectedKind {
+    /// Whether the input contained markers for more than one format
+    pub fn is_ambiguous(self) -> bool {
+        matches!(self, Self::DotOverPlantUml | Self::PlantUmlOverDot)
+    }
+
+    /// Human-readable explanation of the choice, for debugging mixed inputs
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::Dot => "found a digraph/graph header",
+            Self::PlantUml => "found an @startuml block",
+            Self::DotOverPlantUml => {
+                "found both a digraph/graph header and @startuml; the graph header came first"
+            }
+            Self::PlantUmlOverDot => {
+                "found both @startuml and a digraph/graph header; @startuml came first"
+            }
+        }
+    }
+}
+
+/// Detect the diagram format of `content` and parse it
+///
+/// When markers for both formats are present, the one appearing first in
+/// the input wins and the returned kind reports the ambiguity.
+pub fn parse_auto(content: &str) -> Result<(DetectedKind, Vec<GraphEvent>), String> {
+    let kind = detect(content).ok_or_else(|| {
+        "Unrecognized diagram format: expected a DOT graph or @startuml block".to_string()
+    })?;
+
+    let events = match kind {
+        DetectedKind::Dot | DetectedKind::DotOverPlantUml => dot::parse(content),
+        DetectedKind::PlantUml | DetectedKind::PlantUmlOverDot => plantuml::parse(content)?,
+    };
+
+    Ok((kind, events))
+}
+
+/// Determine the diagram format without parsing
+pub fn detect(content: &str) -> Option<DetectedKind> {
+    let plantuml_pos = content.find("@startuml");
+    let dot_pos = find_dot_header(content);
+
+    match (dot_pos, plantuml_pos) {
+        (Some(_), None) => Some(DetectedKind::Dot),
+        (None, Some(_)) => Some(DetectedKind::PlantUml),
+        (Some(dot), Some(plantuml)) if dot < plantuml => Some(DetectedKind::DotOverPlantUml),
+        (Some(_), Some(_)) => Some(DetectedKind::PlantUmlOverDot),
+        (None, None) => None,
+    }
+}
+
+/// Byte offset of the first line opening a DOT graph
+fn find_dot_header(content: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let keyword = trimmed
+            .strip_prefix("strict")
+            .map_or(trimmed, str::trim_start);
+        let is_header = ["digraph", "graph"].iter().any(|header| {
+            keyword
+                .strip_prefix(header)
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '{'))
+        });
+        if is_header {
+            return Some(offset + line.len() - trimmed.len());
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_single_formats() {
+        let (kind, _) = parse_auto("digraph {\n    A -> B;\n}\n").unwrap();
+        assert_eq!(kind, DetectedKind::Dot);
+
+        let (kind, _) = parse_auto("@startuml\nA -> B: hi\n@enduml\n").unwrap();
+        assert_eq!(kind, DetectedKind::PlantUml);
+    }
+
+    #[test]
+    fn test_ambiguous_input_reports_choice() {
+        let content = r"Notes on the flow:
+
+@startuml
+A -> B: request
+@enduml
+
+The same as a graph:
+
+digraph {
+    A -> B;
+}
+";
+
+        let (kind, events) = parse_auto(content).unwrap();
+
+        assert_eq!(kind, DetectedKind::PlantUmlOverDot);
+        assert!(kind.is_ambiguous());
+        assert!(kind.reason().contains("@startuml came first"));
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, GraphEvent::SetLayout { .. }))
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_input() {
+        assert!(parse_auto("just some text").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Split a DOT attribute list like `a=1, b="two, 2"` into key/value pairs
+///
+/// Quoted values may contain commas, equals signs, and escapes; the
+/// surrounding quotes are removed and escapes translated as in [`unquote`]. HTML-like values such as
+/// `<<b>bold</b>>` are read with balanced angle brackets and keep their
+/// outer `<` `>` so callers can tell them apart. Attributes may be
+/// separated by commas, semicolons, or whitespace, and entries without a
+/// value are skipped.
+pub fn parse_attributes(attrs_str: &str) -> Vec<(String, String)> {
+    parse_attributes_with(attrs_str, false)
+}
+
+/// [`parse_attributes`], optionally also accepting `key:value` entries
+fn parse_attributes_with(attrs_str: &str, colon_separator: bool) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut chars = attrs_str.chars().peekable();
+
+    loop {
+        skip_separators(&mut chars);
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let key = read_token(&mut chars, colon_separator);
+        skip_whitespace(&mut chars);
+        let separator = chars.peek().copied();
+        if !(separator == Some('=') || colon_separator && separator == Some(':')) {
+            // Bare attribute or stray text without a value
+            if key.is_empty() {
+                chars.next();
+            }
+            continue;
+        }
+        chars.next();
+        skip_whitespace(&mut chars);
+
+        let value = read_token(&mut chars, false);
+        if !key.is_empty() {
+            attrs.push((key, value));
+        }
+    }
+
+    attrs
+}
+
+/// Read a possibly quoted DOT id or value
+///
+/// A quoted string ends at the first unescaped `"`; `\"`, `\\`, `\n`, and
+/// `\t` are translated and other escapes such as `\l` are kept as written.
+/// Unquoted input is returned trimmed.
+pub fn unquote(text: &str) -> String {
+    let text = text.trim();
+    text.strip_prefix('"').map_or_else(
+        || text.to_string(),
+        |rest| read_quoted(&mut rest.chars().peekable()),
+    )
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn skip_separators(chars: &mut Peekable<Chars>) {
+    while chars
+        .next_if(|c| c.is_whitespace() || *c == ',' || *c == ';')
+        .is_some()
+    {}
+}
+
+/// Rewrite `key:value` entries in every `[...]` attribute list as `key="value"`
+///
+/// Some non-standard exporters separate keys from values with a colon. The
+/// rest of the document, including `node:port` endpoints and quoted strings,
+/// is copied unchanged.
+pub fn normalize_colon_attributes(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                result.push(c);
+                copy_quoted(&mut chars, &mut result);
+            }
+            '[' => {
+                let mut list = String::new();
+                let closed = copy_attribute_list(&mut chars, &mut list);
+                let entries: Vec<String> = parse_attributes_with(&list, true)
+                    .into_iter()
+                    .map(|(key, value)| format!("{}={}", quote(&key), quote(&value)))
+                    .collect();
+                result.push('[');
+                result.push_str(&entries.join(", "));
+                if closed {
+                    result.push(']');
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Copy the rest of a quoted string, including its closing quote
+fn copy_quoted(chars: &mut Peekable<Chars>, out: &mut String) {
+    while let Some(c) = chars.next() {
+        out.push(c);
+        match c {
+            '\\' => out.extend(chars.next()),
+            '"' => break,
+            _ => {}
+        }
+    }
+}
+
+/// Copy an attribute list up to its closing `]`, returning whether one was found
+fn copy_attribute_list(chars: &mut Peekable<Chars>, out: &mut String) -> bool {
+    let mut html_depth = 0usize;
+    while let Some(c) = chars.next() {
+        match c {
+            ']' if html_depth == 0 => return true,
+            '"' if html_depth == 0 => {
+                out.push(c);
+                copy_quoted(chars, out);
+                continue;
+            }
+            '<' => html_depth += 1,
+            '>' => html_depth = html_depth.saturating_sub(1),
+            _ => {}
+        }
+        out.push(c);
+    }
+    false
+}
+
+/// Write a parsed key or value back as DOT, keeping HTML-like values bare
+fn quote(text: &str) -> String {
+    if text.starts_with('<') && text.ends_with('>') {
+        return text.to_string();
+    }
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+/// Read a quoted string, HTML-like value, or bare word, which ends at a `:`
+/// when that separates keys from values
+fn read_token(chars: &mut Peekable<Chars>, colon_separator: bool) -> String {
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            read_quoted(chars)
+        }
+        Some('<') => read_html(chars),
+        _ => {
+            let mut token = String::new();
+            let ends_token = |c: &char| {
+                c.is_whitespace()
+                    || matches!(c, ',' | ';' | '=' | '"')
+                    || colon_separator && *c == ':'
+            };
+            while let Some(c) = chars.next_if(|c| !ends_token(c)) {
+                token.push(c);
+            }
+            token
+        }
+    }
+}
+
+/// Read up to the unescaped closing quote, translating common escapes
+fn read_quoted(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                Some(escaped) => {
+                    value.push('\\');
+                    value.push(escaped);
+                }
+                None => value.push('\\'),
+            },
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// Read a `<...>` value with balanced nested angle brackets
+fn read_html(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    let mut depth = 0usize;
+    for c in chars.by_ref() {
+        value.push(c);
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(attrs: &[(&str, &str)]) -> Vec<(String, String)> {
+        attrs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_simple_pairs() {
+        assert_eq!(
+            parse_attributes("shape=box, color=red"),
+            pairs(&[("shape", "box"), ("color", "red")])
+        );
+    }
+
+    #[test]
+    fn test_quoted_values() {
+        assert_eq!(
+            parse_attributes(r#"label="Team Alpha", type="team""#),
+            pairs(&[("label", "Team Alpha"), ("type", "team")])
+        );
+    }
+
+    #[test]
+    fn test_commas_and_equals_inside_quotes() {
+        assert_eq!(
+            parse_attributes(r#"label="a, b = c", fontname="Helvetica, Arial""#),
+            pairs(&[("label", "a, b = c"), ("fontname", "Helvetica, Arial")])
+        );
+    }
+
+    #[test]
+    fn test_escaped_quote_inside_value() {
+        assert_eq!(
+            parse_attributes(r#"label="She said \"hi\"", color=blue"#),
+            pairs(&[("label", "She said \"hi\""), ("color", "blue")])
+        );
+    }
+
+    #[test]
+    fn test_html_value_with_nested_brackets() {
+        assert_eq!(
+            parse_attributes("label=<<b>Title</b><br/>body>, shape=plaintext"),
+            pairs(&[("label", "<<b>Title</b><br/>body>"), ("shape", "plaintext")])
+        );
+    }
+
+    #[test]
+    fn test_whitespace_and_semicolon_separators() {
+        assert_eq!(
+            parse_attributes("a=1 b = 2; c=3"),
+            pairs(&[("a", "1"), ("b", "2"), ("c", "3")])
+        );
+    }
+
+    #[test]
+    fn test_quoted_keys_and_empty_values() {
+        assert_eq!(
+            parse_attributes(r#""label"="", style=filled"#),
+            pairs(&[("label", ""), ("style", "filled")])
+        );
+    }
+
+    #[test]
+    fn test_bare_and_malformed_entries_are_skipped() {
+        assert_eq!(
+            parse_attributes("filled, =oops, color=red,,"),
+            pairs(&[("color", "red")])
+        );
+        assert!(parse_attributes("").is_empty());
+        assert!(parse_attributes("   ").is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_values_do_not_panic() {
+        assert_eq!(
+            parse_attributes(r#"label="open"#),
+            pairs(&[("label", "open")])
+        );
+        assert_eq!(parse_attributes("label=<<b>"), pairs(&[("label", "<<b>")]));
+    }
+
+    #[test]
+    fn test_normalize_colon_attributes() {
+        assert_eq!(
+            normalize_colon_attributes(
+                r#"a:p -> "x:y" [label:"a: \"b\"", color=red, html:<<b>x</b>>];"#
+            ),
+            r#"a:p -> "x:y" ["label"="a: \"b\"", "color"="red", "html"=<<b>x</b>>];"#
+        );
+        assert_eq!(
+            parse_attributes_with("shape : box; url:\"http://x\"", true),
+            pairs(&[("shape", "box"), ("url", "http://x")])
+        );
+        assert_eq!(parse_attributes("shape:box"), pairs(&[]));
+    }
+
+    #[test]
+    fn test_escape_translation() {
+        assert_eq!(
+            parse_attributes(r#"label="a\nb\tc\\d\le""#),
+            pairs(&[("label", "a\nb\tc\\d\\le")])
+        );
+        assert_eq!(unquote(r#" "She said \"hi\"" "#), "She said \"hi\"");
+        assert_eq!(unquote("plain"), "plain");
+    }
+}
@@ -0,0 +1,299 @@
+use super::attributes::{parse_attributes, unquote};
+use super::parser::{endpoint_nodes, split_edge_operators};
+use super::scope::{split_attribute_list, unquoted_chars};
+use crate::events::{GraphEvent, GroupType, Properties};
+use std::collections::{HashMap, HashSet};
+
+/// One `{ ... }` scope; only `subgraph cluster_*` scopes become groups
+struct Subgraph {
+    cluster: Option<String>,
+    label: Option<String>,
+    members: Vec<String>,
+    /// `labelloc` and `labeljust`, kept as custom properties
+    custom: HashMap<String, String>,
+}
+
+/// Emit an `AddGroup` for every `subgraph cluster_*` block
+///
+/// A node belongs to the innermost cluster it is first mentioned in, and a
+/// nested cluster is listed among its parent's members. Mentions outside
+/// every cluster, such as a top-level `A -> B`, do not place a node. Only ids for which
+/// `is_node` holds are collected, so keywords and attribute names are
+/// skipped. Groups are emitted as their blocks close, innermost first.
+pub fn cluster_groups(
+    lines: &[&str],
+    arrow: &str,
+    is_node: impl Fn(&str) -> bool,
+    events: &mut Vec<GraphEvent>,
+) {
+    let mut stack: Vec<Subgraph> = Vec::new();
+    let mut placed: HashSet<String> = HashSet::new();
+
+    for line in lines {
+        let trimmed = line.trim();
+        let (header, body) = trimmed
+            .strip_prefix("subgraph")
+            .map_or((None, trimmed), |rest| {
+                let (name, body) = rest.split_once('{').unwrap_or((rest, ""));
+                (Some(unquote(name)), body)
+            });
+
+        // Open every scope on the line before reading its nodes, so that
+        // `subgraph cluster_a { A -> B }` places A and B inside the cluster
+        let opens = unquoted_chars(trimmed).filter(|&c| c == '{').count();
+        for i in 0..opens {
+            let cluster = header
+                .as_ref()
+                .filter(|name| i == 0 && name.starts_with("cluster"))
+                .cloned();
+            stack.push(Subgraph {
+                cluster,
+                label: None,
+                members: Vec::new(),
+                custom: HashMap::new(),
+            });
+        }
+
+        let in_cluster_scope = stack.last().is_some_and(|last| last.cluster.is_some());
+        if let Some(frame) = stack.iter_mut().rev().find(|f| f.cluster.is_some()) {
+            let label = cluster_label(body);
+            let placement = label_placement(body);
+            if label.is_some() || !placement.is_empty() {
+                if in_cluster_scope {
+                    if label.is_some() {
+                        frame.label = label;
+                    }
+                    frame.custom.extend(placement);
+                }
+            } else {
+                for id in mentioned_nodes(body, arrow) {
+                    if is_node(&id) && placed.insert(id.clone()) {
+                        frame.members.push(id);
+                    }
+                }
+            }
+        }
+
+        let closes = unquoted_chars(trimmed).filter(|&c| c == '}').count();
+        for _ in 0..closes {
+            let Some(frame) = stack.pop() else {
+                break;
+            };
+            let Some(id) = frame.cluster else {
+                continue;
+            };
+            if let Some(parent) = stack.iter_mut().rev().find(|f| f.cluster.is_some()) {
+                parent.members.push(id.clone());
+            }
+            events.push(GraphEvent::AddGroup {
+                id,
+                label: frame.label,
+                members: frame.members,
+                group_type: GroupType::Cluster,
+                properties: Properties {
+                    custom: frame.custom,
+                    ..Properties::default()
+                },
+            });
+        }
+    }
+}
+
+/// The value of a `label=...` statement or a `graph [label=...]` default
+fn cluster_label(statement: &str) -> Option<String> {
+    let attrs = graph_attribute_list(statement);
+    if !attrs.trim_start().starts_with("label") {
+        return None;
+    }
+    parse_attributes(attrs)
+        .into_iter()
+        .find_map(|(key, value)| (key == "label").then_some(value))
+}
+
+/// `labelloc` and `labeljust` set by a `labelloc=...` statement or a
+/// `graph [...]` default
+fn label_placement(statement: &str) -> Vec<(String, String)> {
+    let attrs = graph_attribute_list(statement);
+    let is_graph_default = attrs.len() < statement.len();
+    let trimmed = attrs.trim_start();
+    if !is_graph_default && !trimmed.starts_with("labelloc") && !trimmed.starts_with("labeljust") {
+        return Vec::new();
+    }
+    parse_attributes(attrs)
+        .into_iter()
+        .filter(|(key, _)| key == "labelloc" || key == "labeljust")
+        .collect()
+}
+
+/// The list inside a `graph [...]` default, or the statement itself
+fn graph_attribute_list(statement: &str) -> &str {
+    statement
+        .strip_prefix("graph")
+        .and_then(|rest| rest.trim_start().strip_prefix('['))
+        .map_or(statement, |rest| {
+            rest.rsplit_once(']').map_or(rest, |(a, _)| a)
+        })
+}
+
+/// Node ids named by a node or edge statement
+pub(super) fn mentioned_nodes(statement: &str, arrow: &str) -> Vec<String> {
+    let mut statement = split_attribute_list(statement)
+        .map_or(statement, |(statement, _)| statement)
+        .trim();
+    // Drop braces closing an enclosing block, keeping `{A B}` sets intact
+    while unquoted_chars(statement).filter(|&c| c == '}').count()
+        > unquoted_chars(statement).filter(|&c| c == '{').count()
+    {
+        match statement.trim_end_matches(';').trim_end().strip_suffix('}') {
+            Some(rest) => statement = rest.trim_end(),
+            None => break,
+        }
+    }
+    let statement = statement.trim_end_matches(';');
+    let (parts, _) = split_edge_operators(statement, arrow);
+    if parts.len() > 1 {
+        parts
+            .into_iter()
+            .flat_map(endpoint_nodes)
+            .map(|endpoint| endpoint.node)
+            .collect()
+    } else if statement.is_empty() || statement.contains('=') {
+        Vec::new()
+    } else {
+        vec![unquote(statement)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(lines: &[&str], nodes: &[&str]) -> Vec<(String, Option<String>, Vec<String>)> {
+        let mut events = Vec::new();
+        cluster_groups(lines, "->", |id| nodes.contains(&id), &mut events);
+        events
+            .into_iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddGroup {
+                    id,
+                    label,
+                    members,
+                    group_type: GroupType::Cluster,
+                    ..
+                } => Some((id, label, members)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_clusters() {
+        let lines = [
+            "digraph {",
+            "subgraph cluster_outer {",
+            "label=\"Outer\";",
+            "A [shape=box];",
+            "subgraph cluster_inner {",
+            "graph [label=\"Inner\"];",
+            "B -> {C D};",
+            "}",
+            "A -> B;",
+            "}",
+            "D -> E;",
+            "}",
+        ];
+
+        assert_eq!(
+            groups(&lines, &["A", "B", "C", "D", "E"]),
+            vec![
+                (
+                    "cluster_inner".to_string(),
+                    Some("Inner".to_string()),
+                    vec!["B".to_string(), "C".to_string(), "D".to_string()],
+                ),
+                (
+                    "cluster_outer".to_string(),
+                    Some("Outer".to_string()),
+                    vec!["A".to_string(), "cluster_inner".to_string()],
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_placement_in_graph_with_edges() {
+        let lines = [
+            "digraph {",
+            "subgraph cluster_a {",
+            "label=\"A\";",
+            "labelloc=b;",
+            "graph [labeljust=r];",
+            "x -> y;",
+            "}",
+            "}",
+        ];
+        let mut events = Vec::new();
+        cluster_groups(&lines, "->", |id| ["x", "y"].contains(&id), &mut events);
+
+        let Some(GraphEvent::AddGroup {
+            label,
+            members,
+            properties,
+            ..
+        }) = events.pop()
+        else {
+            panic!("expected a cluster group");
+        };
+        assert_eq!(label.as_deref(), Some("A"));
+        assert_eq!(members, ["x", "y"]);
+        assert_eq!(
+            properties.custom.get("labelloc").map(String::as_str),
+            Some("b")
+        );
+        assert_eq!(
+            properties.custom.get("labeljust").map(String::as_str),
+            Some("r")
+        );
+    }
+
+    #[test]
+    fn test_top_level_mention_does_not_claim_nodes() {
+        let lines = [
+            "digraph {",
+            "A -> B;",
+            "subgraph cluster_x {",
+            "A;",
+            "B;",
+            "}",
+            "}",
+        ];
+
+        assert_eq!(
+            groups(&lines, &["A", "B"]),
+            vec![(
+                "cluster_x".to_string(),
+                None,
+                vec!["A".to_string(), "B".to_string()],
+            )]
+        );
+    }
+
+    #[test]
+    fn test_single_line_cluster_and_plain_subgraph() {
+        let lines = [
+            "digraph {",
+            "subgraph cluster_a { X -> Y }",
+            "subgraph plain { Z; }",
+            "}",
+        ];
+
+        assert_eq!(
+            groups(&lines, &["X", "Y", "Z"]),
+            vec![(
+                "cluster_a".to_string(),
+                None,
+                vec!["X".to_string(), "Y".to_string()],
+            )]
+        );
+    }
+}
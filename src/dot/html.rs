@@ -0,0 +1,138 @@
+use std::fmt::Write;
+
+/// The markup inside an HTML-like `<...>` attribute value
+///
+/// Attribute parsing keeps the outer brackets of such values, so a value
+/// counts as HTML-like when it is wrapped in `<` and `>` whose contents are
+/// themselves balanced. A quoted value such as `"<b>x</b>"` is not.
+pub fn html_label_body(value: &str) -> Option<&str> {
+    let body = value.strip_prefix('<')?.strip_suffix('>')?;
+    let mut depth = 0usize;
+    for c in body.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+    }
+    (depth == 0).then(|| body.trim())
+}
+
+/// Rows of cell text from the first `<table>` in an HTML-like label
+///
+/// Cell text has its inner markup removed and basic entities decoded.
+/// Returns `None` when the label has no table rows.
+pub fn table_rows(html: &str) -> Option<Vec<Vec<String>>> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<table")?;
+    let end = lower[start..]
+        .find("</table")
+        .map_or(html.len(), |pos| start + pos);
+
+    let rows: Vec<Vec<String>> = elements(html, &lower, start, end, "tr")
+        .into_iter()
+        .map(|(row_start, row_end)| {
+            elements(html, &lower, row_start, row_end, "td")
+                .into_iter()
+                .map(|(cell_start, cell_end)| strip_tags(&html[cell_start..cell_end]))
+                .collect()
+        })
+        .collect();
+
+    if rows.is_empty() { None } else { Some(rows) }
+}
+
+/// Content ranges of each `<tag ...>...</tag>` between `start` and `end`
+fn elements(html: &str, lower: &str, start: usize, end: usize, tag: &str) -> Vec<(usize, usize)> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}");
+    let mut ranges = Vec::new();
+    let mut offset = start;
+
+    while let Some(found) = lower[offset..end].find(&open) {
+        let tag_start = offset + found;
+        let Some(content_start) = html[tag_start..end]
+            .find('>')
+            .map(|pos| tag_start + pos + 1)
+        else {
+            break;
+        };
+        let content_end = lower[content_start..end]
+            .find(&close)
+            .map_or(end, |pos| content_start + pos);
+        ranges.push((content_start, content_end));
+        offset = content_end;
+    }
+
+    ranges
+}
+
+/// Plain text of an HTML fragment: tags removed, entities decoded, trimmed
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Encode table rows as a JSON array of arrays of strings
+pub fn rows_to_json(rows: &[Vec<String>]) -> String {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().map(|cell| json_string(cell)).collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_rows() {
+        let html = r#"<<TABLE BORDER="0"><TR><TD><B>Name</B></TD><TD port="t">Type</TD></TR><tr><td>id</td><td>u32 &amp; key</td></tr></TABLE>>"#;
+
+        let rows = table_rows(html).unwrap();
+
+        assert_eq!(rows, vec![vec!["Name", "Type"], vec!["id", "u32 & key"]]);
+        assert_eq!(
+            rows_to_json(&rows),
+            r#"[["Name","Type"],["id","u32 & key"]]"#
+        );
+        assert!(table_rows("<<b>no table</b>>").is_none());
+    }
+}
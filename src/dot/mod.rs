@@ -1,3 +1,21 @@
+mod attributes;
+mod clusters;
+pub(crate) mod html;
 pub mod parser;
+mod ranks;
+mod scope;
+mod statements;
+mod validate;
 
+pub use parser::ParseOptions;
+pub use parser::parse_all_dot_to_events as parse_all;
+pub use parser::parse_dot_checked as parse_checked;
+pub use parser::parse_dot_checked_with_warnings as parse_checked_with_warnings;
+pub use parser::parse_dot_iter as parse_iter;
+pub use parser::parse_dot_iter_with_options as parse_iter_with_options;
 pub use parser::parse_dot_to_events as parse;
+pub use parser::parse_dot_to_events_with_options as parse_with_options;
+pub use validate::{DotParseError, DotParseWarning};
+
+/// Serialize events back to DOT; see [`crate::export::events_to_dot`]
+pub use crate::export::events_to_dot as to_dot;
@@ -1,7 +1,17 @@
 #![allow(clippy::cast_possible_truncation)] // Stack depth won't exceed u32::MAX
 
-use crate::events::{Direction, EdgeType, GraphEvent, LayoutType, NodeType, Position, Properties};
-use std::collections::HashMap;
+use super::attributes::{normalize_colon_attributes, parse_attributes, unquote};
+use super::clusters::cluster_groups;
+use super::html::{html_label_body, rows_to_json, table_rows};
+use super::ranks::rank_groups;
+use super::scope::{DefaultScopes, split_attribute_list, unquoted_chars};
+use super::statements::{located_statements, split_graphs, split_statements, strip_comments};
+use super::validate::{DotParseError, DotParseWarning, charset_warning, validate};
+use crate::events::{
+    Direction, EdgeType, GraphEvent, GroupType, LayoutType, MessageType, NodeType, Overlap,
+    Position, Properties, Splines, StateType, Style, stable_hash,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 // Type alias for node attributes to reduce complexity
 type NodeAttributes = HashMap<
@@ -14,455 +24,3091 @@ type NodeAttributes = HashMap<
     ),
 >;
 
+/// Options controlling how DOT input is turned into events
+#[allow(clippy::struct_excessive_bools)] // Independent opt-in switches
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Maps `type=` attribute values to node types, e.g. `"svc"` to
+    /// `NodeType::Process`. Values not in the table become `NodeType::Custom`.
+    pub node_types: HashMap<String, NodeType>,
+    /// Emit `UpdateEdge` instead of a second `AddEdge` when an edge between
+    /// the same pair of nodes is declared again, e.g. `A -> B` followed by
+    /// `A -> B [color=red]`
+    pub update_redeclared_edges: bool,
+    /// Emit `LayoutType::Sequential` instead of `Hierarchical` for a
+    /// `rankdir=LR` graph whose nodes form a single chain, as in a pipeline
+    pub infer_pipeline_layout: bool,
+    /// Read state-machine conventions from untyped node shapes:
+    /// `doublecircle` becomes a final state, and `point` or a small filled
+    /// circle becomes an initial state
+    pub infer_state_shapes: bool,
+    /// Derive edge ids from a hash of the endpoints and label instead of
+    /// `from->to`, so an edge keeps its id across edits to its other
+    /// attributes while a relabeled edge gets a new one
+    pub hashed_edge_ids: bool,
+    /// Number directed edges in emission order starting at this value,
+    /// emitting them as synchronous `EdgeType::Message`s instead of
+    /// `EdgeType::Directed`; numbering stops rising at `u32::MAX`
+    pub message_sequence_base: Option<u32>,
+    /// Emit node and edge events in the order their statements appear,
+    /// instead of every node before every edge. A node declared after an
+    /// edge has already created it is emitted as an `UpdateNode`.
+    pub source_order: bool,
+    /// Emit `{ rank=same; ... }` blocks as `GroupType::Parallel` groups
+    /// instead of the layout-only `GroupType::Custom("rank_same")`
+    pub rank_same_as_parallel: bool,
+    /// Also accept `key:value` entries in attribute lists, as written by
+    /// some non-standard exporters. `key=value` is accepted either way.
+    pub colon_attributes: bool,
+}
+
 /// Parse a DOT file and return events
 pub fn parse_dot_to_events(content: &str) -> Vec<GraphEvent> {
-    let mut events = Vec::new();
-    let mut node_attributes = HashMap::new();
+    parse_dot_to_events_with_options(content, &ParseOptions::default())
+}
+
+/// Parse a DOT file, rejecting structurally malformed input
+///
+/// Unbalanced braces or brackets, unterminated strings, and edge operators
+/// that don't match the graph kind are reported with their line and column.
+/// Input that passes is parsed exactly as by [`parse_dot_to_events`].
+pub fn parse_dot_checked(content: &str) -> Result<Vec<GraphEvent>, DotParseError> {
+    let stripped = strip_comments(content);
+    let statements = split_statements(&stripped);
+    let lines: Vec<&str> = statements.iter().map(String::as_str).collect();
+    let directed = graph_header(&lines).map(|header| header.directed);
+    validate(&stripped, directed)?;
+    Ok(parse_dot_to_events(content))
+}
+
+/// Parse a DOT file like [`parse_dot_checked`], also returning non-fatal
+/// diagnostics
+///
+/// A `charset` other than UTF-8 is reported, since the `&str` input has
+/// already been decoded and the declared encoding no longer applies. The
+/// warning points at the graph attribute that set it, and the charset itself
+/// is kept on the `SetLayout` event either way.
+pub fn parse_dot_checked_with_warnings(
+    content: &str,
+) -> Result<(Vec<GraphEvent>, Vec<DotParseWarning>), DotParseError> {
+    let events = parse_dot_checked(content)?;
+    let stripped = strip_comments(content);
+    let statements = located_statements(&stripped);
+    let lines: Vec<&str> = statements.iter().map(|(_, s)| s.as_str()).collect();
+    let warnings = located_graph_attributes(&lines)
+        .into_iter()
+        .rev()
+        .find(|(_, key, _)| key == "charset")
+        .and_then(|(index, _, charset)| charset_warning(&stripped, statements[index].0, &charset))
+        .into_iter()
+        .collect();
+    Ok((events, warnings))
+}
+
+/// Parse every top-level graph in a DOT file into its own event stream
+pub fn parse_all_dot_to_events(content: &str) -> Vec<Vec<GraphEvent>> {
+    split_graphs(&strip_comments(content))
+        .into_iter()
+        .map(parse_dot_to_events)
+        .collect()
+}
+
+/// Parse a DOT file with custom options and return events
+pub fn parse_dot_to_events_with_options(content: &str, options: &ParseOptions) -> Vec<GraphEvent> {
+    let mut events: Vec<GraphEvent> = DotEvents::new(content, options.clone()).collect();
+    if options.infer_pipeline_layout {
+        infer_pipeline_layout(&mut events);
+    }
+    events
+}
+
+/// Parse a DOT file lazily, producing events as statements are scanned
+///
+/// The stream matches [`parse_dot_to_events`], bracketed by `BatchStart` and
+/// `BatchEnd`, without holding every event in memory at once. Only the
+/// output is streamed: the source is still stripped of comments and split
+/// into statements up front.
+pub fn parse_dot_iter(content: &str) -> impl Iterator<Item = GraphEvent> + '_ {
+    parse_dot_iter_with_options(content, &ParseOptions::default())
+}
+
+/// Parse a DOT file lazily with custom options, like [`parse_dot_iter`]
+///
+/// [`ParseOptions::infer_pipeline_layout`] needs the whole stream and is
+/// ignored here; every other option applies as in
+/// [`parse_dot_to_events_with_options`].
+pub fn parse_dot_iter_with_options(
+    content: &str,
+    options: &ParseOptions,
+) -> impl Iterator<Item = GraphEvent> + use<> {
+    DotEvents::new(content, options.clone())
+}
+
+/// Which pass over the statements the iterator is in
+enum Stage {
+    /// Nested org-chart format without edges
+    Nested,
+    Nodes,
+    Edges,
+    /// Nodes and edges together, in source order
+    Interleaved,
+    Clusters,
+    Done,
+}
+
+/// Event iterator behind [`parse_dot_iter`]
+///
+/// Node declarations are read in one pass over the statements and edges in
+/// a second, so nodes declared after their edges keep their attributes.
+/// With [`ParseOptions::source_order`] both are read in a single pass.
+struct DotEvents {
+    statements: Vec<String>,
+    options: ParseOptions,
+    stage: Stage,
+    index: usize,
+    pending: VecDeque<GraphEvent>,
+    node_attributes: NodeAttributes,
+    node_scopes: DefaultScopes,
+    edge_pass: EdgePass,
+    clusters: Vec<ClusterFrame>,
+    /// Nodes already emitted in the single-pass stage
+    added_nodes: HashSet<String>,
+}
+
+impl DotEvents {
+    fn new(content: &str, options: ParseOptions) -> Self {
+        let mut content = strip_comments(content);
+        if options.colon_attributes {
+            content = normalize_colon_attributes(&content);
+        }
+        let content = &content;
+        let statements = split_statements(content);
+        let lines: Vec<&str> = statements.iter().map(String::as_str).collect();
+
+        // Start batch for efficiency
+        let mut pending = VecDeque::from([GraphEvent::BatchStart]);
+
+        // Announce the graph's name and kind from its header
+        let header = graph_header(&lines);
+        if let Some(header) = &header {
+            pending.push_back(GraphEvent::SetGraphMeta {
+                name: header.name.clone(),
+                directed: header.directed,
+                strict: header.strict,
+            });
+        }
+
+        let is_digraph = header
+            .as_ref()
+            .map_or_else(|| content.contains("digraph"), |header| header.directed);
+        let strict = header.is_some_and(|header| header.strict);
+        let nested = is_org_chart(&lines);
+
+        let attributes = graph_attributes(&lines);
+        if !nested {
+            pending.extend(layout_event(&attributes));
+        }
+        let compound = graph_attribute(&attributes, "compound") == Some("true");
+        let edge_pass = EdgePass::new(is_digraph, compound, strict, &options);
+
+        let stage = if nested {
+            Stage::Nested
+        } else if options.source_order {
+            Stage::Interleaved
+        } else {
+            Stage::Nodes
+        };
+
+        Self {
+            statements,
+            options,
+            stage,
+            index: 0,
+            pending,
+            node_attributes: HashMap::new(),
+            node_scopes: DefaultScopes::default(),
+            edge_pass,
+            clusters: Vec::new(),
+            added_nodes: HashSet::new(),
+        }
+    }
+
+    /// Run the current pass over the next statement, or move to the next pass
+    fn advance(&mut self) {
+        let mut events = Vec::new();
+        let line = self.statements.get(self.index).map(String::as_str);
+        self.index += 1;
+        match (&self.stage, line) {
+            (Stage::Nested, Some(line)) => {
+                parse_nested_statement(line, &mut self.clusters, &mut events);
+            }
+            (Stage::Nodes, Some(line)) => parse_node_statement(
+                line,
+                &mut self.node_scopes,
+                &mut events,
+                &mut self.node_attributes,
+                &self.options,
+            ),
+            (Stage::Nodes, None) => {
+                self.stage = Stage::Edges;
+                self.index = 0;
+            }
+            (Stage::Edges, Some(line)) => self.edge_pass.statement(
+                line,
+                &mut events,
+                &mut self.node_attributes,
+                &self.options,
+            ),
+            (Stage::Edges | Stage::Interleaved, None) => self.stage = Stage::Clusters,
+            (Stage::Interleaved, Some(line)) => {
+                parse_node_statement(
+                    line,
+                    &mut self.node_scopes,
+                    &mut events,
+                    &mut self.node_attributes,
+                    &self.options,
+                );
+                // A declaration following an edge that created the node
+                // updates it rather than adding it twice
+                for event in &mut events {
+                    if let GraphEvent::AddNode {
+                        id,
+                        label,
+                        properties,
+                        ..
+                    } = event
+                        && !self.added_nodes.insert(id.clone())
+                    {
+                        *event = GraphEvent::UpdateNode {
+                            id: id.clone(),
+                            label: label.take(),
+                            properties: std::mem::take(properties),
+                        };
+                    }
+                }
+                self.edge_pass.statement(
+                    line,
+                    &mut events,
+                    &mut self.node_attributes,
+                    &self.options,
+                );
+                self.added_nodes
+                    .extend(events.iter().filter_map(|event| match event {
+                        GraphEvent::AddNode { id, .. } => Some(id.clone()),
+                        _ => None,
+                    }));
+            }
+            (Stage::Clusters, _) => {
+                // Group nodes by the `subgraph cluster_*` blocks they are declared in
+                let lines: Vec<&str> = self.statements.iter().map(String::as_str).collect();
+                let arrow = if self.edge_pass.is_digraph {
+                    "->"
+                } else {
+                    "--"
+                };
+                let node_attributes = &self.node_attributes;
+                let is_node = |id: &str| node_attributes.contains_key(id);
+                cluster_groups(&lines, arrow, is_node, &mut events);
+
+                // Nodes sharing a rank form a layout constraint or, when
+                // asked for, a parallel group
+                let rank_group_type = if self.options.rank_same_as_parallel {
+                    GroupType::Parallel
+                } else {
+                    GroupType::Custom("rank_same".to_string())
+                };
+                rank_groups(&lines, arrow, is_node, &rank_group_type, &mut events);
+                self.finish(&mut events);
+            }
+            (Stage::Nested, None) => self.finish(&mut events),
+            (Stage::Done, _) => {}
+        }
+        self.pending.extend(events);
+    }
+
+    fn finish(&mut self, events: &mut Vec<GraphEvent>) {
+        // End batch
+        events.push(GraphEvent::BatchEnd);
+        self.stage = Stage::Done;
+    }
+}
+
+impl Iterator for DotEvents {
+    type Item = GraphEvent;
+
+    fn next(&mut self) -> Option<GraphEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            if matches!(self.stage, Stage::Done) {
+                return None;
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Layout hint from the graph attributes
+fn layout_event(attributes: &[(String, String)]) -> Option<GraphEvent> {
+    let rankdir = graph_attribute(attributes, "rankdir");
+    let properties = layout_properties(attributes);
+    if rankdir.is_none() && properties == Properties::default() {
+        return None;
+    }
+    let direction = match rankdir {
+        Some("BT") => Direction::BottomToTop,
+        Some("LR") => Direction::LeftToRight,
+        Some("RL") => Direction::RightToLeft,
+        _ => Direction::TopToBottom, // Default: TB
+    };
+    Some(GraphEvent::SetLayout {
+        layout_type: LayoutType::Hierarchical { direction },
+        properties,
+    })
+}
+
+/// Switch a `rankdir=LR` layout to `Sequential` when the graph is one chain
+///
+/// Needs the whole stream, so it runs after parsing rather than while
+/// iterating.
+fn infer_pipeline_layout(events: &mut [GraphEvent]) {
+    let Some(layout_index) = events
+        .iter()
+        .position(|e| matches!(e, GraphEvent::SetLayout { .. }))
+    else {
+        return;
+    };
+    if is_simple_path(&events[layout_index..])
+        && let GraphEvent::SetLayout { layout_type, .. } = &mut events[layout_index]
+        && *layout_type
+            == (LayoutType::Hierarchical {
+                direction: Direction::LeftToRight,
+            })
+    {
+        *layout_type = LayoutType::Sequential {
+            direction: Direction::LeftToRight,
+        };
+    }
+}
+
+/// The `[strict] (di)graph [name] {` header
+struct GraphHeader {
+    strict: bool,
+    directed: bool,
+    name: Option<String>,
+}
+
+/// Read the graph header from the first statement; keywords are
+/// case-insensitive, as in DOT
+fn graph_header(lines: &[&str]) -> Option<GraphHeader> {
+    let header = lines
+        .iter()
+        .map(|line| line.trim())
+        .find(|l| !l.is_empty())?;
+    let stripped = strip_keyword(header, "strict")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(str::trim_start);
+    let strict = stripped.is_some();
+    let header = stripped.unwrap_or(header);
+    let (directed, rest) = if let Some(rest) = strip_keyword(header, "digraph") {
+        (true, rest)
+    } else {
+        (false, strip_keyword(header, "graph")?)
+    };
+    if !rest.starts_with(|c: char| c.is_whitespace() || c == '{') {
+        return None;
+    }
+    let name = unquote(rest.split('{').next().unwrap_or_default());
+    Some(GraphHeader {
+        strict,
+        directed,
+        name: (!name.is_empty()).then_some(name),
+    })
+}
+
+/// `text` after a leading `keyword`, matched case-insensitively
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    text.get(..keyword.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(keyword))
+        .map(|_| &text[keyword.len()..])
+}
+
+/// Whether the nodes and edges form one unbranched chain `A -> B -> ... -> Z`
+fn is_simple_path(events: &[GraphEvent]) -> bool {
+    let mut nodes = HashSet::new();
+    let mut edges = Vec::new();
+    for event in events {
+        match event {
+            GraphEvent::AddNode { id, .. } => {
+                nodes.insert(id.as_str());
+            }
+            GraphEvent::AddEdge { from, to, .. } => edges.push((from.as_str(), to.as_str())),
+            _ => {}
+        }
+    }
+
+    let mut next = HashMap::new();
+    let mut has_incoming = HashSet::new();
+    for (from, to) in edges {
+        if next.insert(from, to).is_some() || !has_incoming.insert(to) {
+            return false;
+        }
+    }
+
+    let mut starts = nodes.iter().filter(|node| !has_incoming.contains(*node));
+    let (Some(&start), None) = (starts.next(), starts.next()) else {
+        return false;
+    };
+
+    let mut current = start;
+    let mut visited = 1;
+    while let Some(&to) = next.get(current) {
+        current = to;
+        visited += 1;
+    }
+    nodes.len() > 1 && visited == nodes.len()
+}
+
+/// Handle one statement of the node pass: scoped defaults and node
+/// declarations, with or without attributes
+fn parse_node_statement(
+    line: &str,
+    scopes: &mut DefaultScopes,
+    events: &mut Vec<GraphEvent>,
+    node_attributes: &mut NodeAttributes,
+    options: &ParseOptions,
+) {
+    let trimmed = line.trim();
+
+    // Skip comments and empty lines
+    if trimmed.starts_with("//") || trimmed.is_empty() || scopes.observe(trimmed) {
+        return;
+    }
+
+    // Parse node definitions with attributes; edge statements, with either
+    // operator, are left to the edge pass
+    if trimmed.contains(']')
+        && let Some((node_part, attrs_part)) = split_attribute_list(trimmed)
+        && split_edge_operators(node_part, "->").0.len() == 1
+    {
+        let node_id = unquote(node_part);
+
+        // Graph-level attributes are read by `graph_attributes`
+        if node_id == "graph" {
+            return;
+        }
+
+        // Extract attributes
+        let attrs_str = attrs_part
+            .rsplit_once(']')
+            .map_or(attrs_part, |(attrs, _)| attrs);
+
+        // Explicit attributes override scoped defaults
+        let defaults = scopes.node_defaults().iter().cloned();
+        add_node(
+            &node_id,
+            defaults.chain(parse_attributes(attrs_str)),
+            events,
+            node_attributes,
+            options,
+        );
+    } else if let Some(node_id) = bare_node_id(trimmed)
+        && !node_attributes.contains_key(&node_id)
+    {
+        let defaults = scopes.node_defaults().iter().cloned();
+        add_node(&node_id, defaults, events, node_attributes, options);
+    }
+}
+
+/// The id declared by a bare `A;` or `"Web Server";` statement
+fn bare_node_id(statement: &str) -> Option<String> {
+    let statement = statement.trim_end_matches(';').trim_end();
+    let quoted = statement.starts_with('"') && statement.ends_with('"') && statement.len() > 1;
+    let plain = !statement.is_empty()
+        && statement
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.'));
+    let keyword = matches!(
+        statement.to_ascii_lowercase().as_str(),
+        "graph" | "digraph" | "subgraph" | "node" | "edge" | "strict"
+    );
+    ((quoted || plain) && !keyword).then(|| unquote(statement))
+}
+
+/// Emit an `AddNode` built from a node's attributes and record them
+fn add_node(
+    node_id: &str,
+    attrs: impl Iterator<Item = (String, String)>,
+    events: &mut Vec<GraphEvent>,
+    node_attributes: &mut NodeAttributes,
+    options: &ParseOptions,
+) {
+    let mut node_type = None;
+    let mut level = None;
+    let mut label = None;
+    let mut absolute = None;
+    let mut properties = Properties::default();
+    let mut style = Style::default();
+    let mut custom_props = HashMap::new();
+
+    for (key, value) in attrs {
+        match key.as_str() {
+            "type" => node_type = Some(value),
+            "level" => level = value.parse::<u32>().ok(),
+            "label" => label = Some(value),
+            "pos" => match parse_pos(&value) {
+                Some((position, pinned)) => {
+                    absolute = Some(position);
+                    if pinned {
+                        custom_props.insert("pinned".to_string(), "true".to_string());
+                    }
+                }
+                None => {
+                    custom_props.insert(key, value);
+                }
+            },
+            _ => {
+                if !apply_style_attribute(&mut style, &key, &value) {
+                    custom_props.insert(key, value);
+                }
+            }
+        }
+    }
+
+    // Plaintext tables keep their cells for renderers, their markup, and a
+    // text fallback label; other HTML-like labels keep their markup, flagged
+    // as such
+    let html = label
+        .as_deref()
+        .and_then(html_label_body)
+        .map(str::to_string);
+    let is_plaintext = matches!(style.shape.as_deref(), Some("plaintext" | "plain" | "none"));
+    if is_plaintext && let Some(rows) = html.as_deref().and_then(table_rows) {
+        custom_props.insert("table".to_string(), rows_to_json(&rows));
+        custom_props.insert("markup".to_string(), html.clone().unwrap_or_default());
+        let lines: Vec<String> = rows.iter().map(|row| row.join(" | ")).collect();
+        label = Some(lines.join("\n"));
+    } else if let Some(body) = html {
+        custom_props.insert("label_format".to_string(), "html".to_string());
+        label = Some(body);
+    }
+
+    // Store attributes for later use
+    node_attributes.insert(
+        node_id.to_string(),
+        (
+            node_type.clone(),
+            level,
+            label.clone(),
+            custom_props.clone(),
+        ),
+    );
+
+    // Set position if level is specified; laid-out coordinates take precedence
+    if let Some(lvl) = level {
+        properties.position = Some(Position::Layer { level: lvl });
+    }
+    if absolute.is_some() {
+        properties.position = absolute;
+    }
+
+    properties.custom = custom_props;
+    if style != Style::default() {
+        properties.style = Some(style);
+    }
+
+    let node_type = match node_type {
+        Some(t) => options
+            .node_types
+            .get(&t)
+            .cloned()
+            .unwrap_or(NodeType::Custom(t)),
+        None if options.infer_state_shapes => properties
+            .style
+            .as_ref()
+            .and_then(shape_state_type)
+            .map_or(NodeType::Node, |state_type| NodeType::State { state_type }),
+        None => NodeType::Node,
+    };
+
+    // Emit node event
+    events.push(GraphEvent::AddNode {
+        id: node_id.to_string(),
+        label: label.or_else(|| Some(node_id.to_string())),
+        node_type,
+        properties,
+    });
+}
+
+/// Set the `Style` field for a Graphviz visual attribute
+///
+/// Returns `false` for attributes with no matching field, or numeric
+/// attributes whose value doesn't parse, so callers can keep them as custom.
+fn apply_style_attribute(style: &mut Style, key: &str, value: &str) -> bool {
+    let number = || value.parse::<f32>().ok();
+    match key {
+        "color" => style.color = Some(value.to_string()),
+        "fillcolor" | "bgcolor" => style.background_color = Some(value.to_string()),
+        "style" => style.border_style = Some(value.to_string()),
+        "shape" => style.shape = Some(value.to_string()),
+        "fontname" => style.font_family = Some(value.to_string()),
+        "penwidth" => match number() {
+            Some(width) => style.border_width = Some(width),
+            None => return false,
+        },
+        "fontsize" => match number() {
+            Some(size) => style.font_size = Some(size),
+            None => return false,
+        },
+        // `size` is the width; `height` stays custom so the two axes are
+        // never confused
+        "width" => match number() {
+            Some(size) => style.size = Some(size),
+            None => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+/// Read a laid-out `pos="x,y"` (optionally `x,y,z`) value, with a trailing
+/// `!` marking the node as pinned; anything else, including `nan` or `inf`
+/// coordinates, is rejected
+fn parse_pos(value: &str) -> Option<(Position, bool)> {
+    let value = value.trim();
+    let (coordinates, pinned) = value
+        .strip_suffix('!')
+        .map_or((value, false), |coordinates| (coordinates, true));
+    let numbers: Vec<f32> = coordinates
+        .split(',')
+        .map(|part| part.trim().parse::<f32>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<_>>()?;
+    let position = match numbers[..] {
+        [x, y] => Position::Absolute { x, y, z: None },
+        [x, y, z] => Position::Absolute { x, y, z: Some(z) },
+        _ => return None,
+    };
+    Some((position, pinned))
+}
+
+/// Read a `"x,y"` or `"x"` dimension pair, where a single number applies
+/// to both and a trailing `!` asks for the drawing to be scaled up to fill
+fn parse_dimensions(value: &str) -> Option<(f32, f32, bool)> {
+    let value = value.trim();
+    let (dimensions, fill) = value
+        .strip_suffix('!')
+        .map_or_else(|| (value, false), |dimensions| (dimensions, true));
+    let numbers: Vec<f32> = dimensions
+        .split(',')
+        .map(|part| part.trim().parse::<f32>().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [both] => Some((both, both, fill)),
+        [x, y] => Some((x, y, fill)),
+        _ => None,
+    }
+}
+
+/// State-machine meaning of a node's `shape`, if it has one
+fn shape_state_type(style: &Style) -> Option<StateType> {
+    match style.shape.as_deref() {
+        Some("doublecircle") => Some(StateType::Final),
+        Some("point") => Some(StateType::Initial),
+        Some("circle")
+            if style
+                .border_style
+                .as_ref()
+                .is_some_and(|border| border.contains("filled"))
+                && style.size.is_some_and(|size| size <= 0.3) =>
+        {
+            Some(StateType::Initial)
+        }
+        _ => None,
+    }
+}
+
+/// State of the edge pass carried from one statement to the next
+struct EdgePass {
+    scopes: DefaultScopes,
+    /// `from->to` key to the id of the edge first emitted for it
+    emitted_edges: HashMap<String, String>,
+    /// Node pairs already connected, for strict graphs only
+    connected_pairs: HashSet<(String, String)>,
+    next_sequence: Option<u32>,
+    is_digraph: bool,
+    compound: bool,
+    strict: bool,
+}
+
+impl EdgePass {
+    fn new(is_digraph: bool, compound: bool, strict: bool, options: &ParseOptions) -> Self {
+        Self {
+            scopes: DefaultScopes::default(),
+            emitted_edges: HashMap::new(),
+            connected_pairs: HashSet::new(),
+            next_sequence: options.message_sequence_base,
+            is_digraph,
+            compound,
+            strict,
+        }
+    }
+
+    fn next_edge_type(&mut self) -> EdgeType {
+        match (self.is_digraph, self.next_sequence.as_mut()) {
+            (true, Some(sequence)) => {
+                let edge_type = EdgeType::Message {
+                    message_type: MessageType::Synchronous,
+                    sequence: Some(*sequence),
+                };
+                *sequence = sequence.saturating_add(1);
+                edge_type
+            }
+            (true, None) => EdgeType::Directed,
+            (false, _) => EdgeType::Undirected,
+        }
+    }
+
+    /// Handle one statement: scoped defaults and edge declarations
+    fn statement(
+        &mut self,
+        line: &str,
+        events: &mut Vec<GraphEvent>,
+        node_attributes: &mut NodeAttributes,
+        options: &ParseOptions,
+    ) {
+        let trimmed = line.trim();
+        if self.scopes.observe(trimmed) {
+            return;
+        }
+        let arrow = if self.is_digraph { "->" } else { "--" };
+
+        // `A -> B -> C [attrs]` applies the attributes to every hop
+        let (statement, attrs_str) =
+            split_attribute_list(trimmed).map_or((trimmed, ""), |(statement, rest)| {
+                (
+                    statement,
+                    rest.rsplit_once(']').map_or("", |(attrs, _)| attrs),
+                )
+            });
+        let (parts, mismatched) = split_edge_operators(statement, arrow);
+        if parts.len() < 2 {
+            return;
+        }
+        let chain: Vec<Vec<Endpoint>> = parts.into_iter().map(endpoint_nodes).collect();
+
+        // Ensure nodes exist, picking up the node defaults in scope here
+        for node_id in chain.iter().flatten().map(|endpoint| &endpoint.node) {
+            if !node_attributes.contains_key(node_id) {
+                let defaults = self.scopes.node_defaults().iter().cloned();
+                add_node(node_id, defaults, events, node_attributes, options);
+            }
+        }
+
+        // Edge attributes
+        let defaults = self.scopes.edge_defaults().iter().cloned();
+        let (label, mut properties) =
+            edge_properties(defaults.chain(parse_attributes(attrs_str)), self.compound);
+
+        // A stray operator of the other kind still connects the nodes, as
+        // the graph's kind of edge, but is flagged for the consumer
+        if mismatched {
+            let declared = if self.is_digraph { "--" } else { "->" };
+            properties
+                .custom
+                .insert("declared_arrow".to_string(), declared.to_string());
+        }
+
+        // `{A B} -> {C D}` connects every member on the left to every one on the right
+        let hops = chain.windows(2).flat_map(|hop| {
+            hop[0]
+                .iter()
+                .flat_map(|from| hop[1].iter().map(move |to| (from, to)))
+        });
+        for (from, to) in hops {
+            // Ports stay part of the key so `a:f0 -> b` and `a:f1 -> b` are distinct
+            let key = format!("{}{arrow}{}", from.qualified(), to.qualified());
+            if options.update_redeclared_edges
+                && let Some(id) = self.emitted_edges.get(&key)
+            {
+                events.push(GraphEvent::UpdateEdge {
+                    id: id.clone(),
+                    label: label.clone(),
+                    properties: port_properties(&properties, from, to),
+                });
+                continue;
+            }
+
+            // Strict graphs keep a single edge per pair; undirected pairs are unordered
+            if self.strict {
+                let (a, b) = (from.node.clone(), to.node.clone());
+                let pair = if !self.is_digraph && b < a {
+                    (b, a)
+                } else {
+                    (a, b)
+                };
+                if !self.connected_pairs.insert(pair) {
+                    continue;
+                }
+            }
+
+            let id = if options.hashed_edge_ids {
+                let hash = stable_hash([
+                    from.qualified().as_str(),
+                    to.qualified().as_str(),
+                    label.as_deref().unwrap_or_default(),
+                ]);
+                format!("e{hash:016x}")
+            } else {
+                key.clone()
+            };
+            self.emitted_edges.entry(key).or_insert_with(|| id.clone());
+
+            events.push(GraphEvent::AddEdge {
+                id,
+                from: from.node.clone(),
+                to: to.node.clone(),
+                edge_type: self.next_edge_type(),
+                label: label.clone(),
+                properties: port_properties(&properties, from, to),
+            });
+        }
+    }
+}
+
+/// One edge endpoint: a node id plus an optional `port[:compass]` suffix
+pub(super) struct Endpoint {
+    pub(super) node: String,
+    port: Option<String>,
+}
+
+impl Endpoint {
+    fn parse(text: &str) -> Self {
+        let mut parts = split_unquoted(text, ':').into_iter().map(unquote);
+        let node = parts.next().unwrap_or_default();
+        let port = parts.collect::<Vec<_>>().join(":");
+        Self {
+            node,
+            port: (!port.is_empty()).then_some(port),
+        }
+    }
+
+    /// The endpoint as written, including any port
+    fn qualified(&self) -> String {
+        self.port
+            .as_ref()
+            .map_or_else(|| self.node.clone(), |port| format!("{}:{port}", self.node))
+    }
+}
+
+/// Split an edge statement at its `->` and `--` operators outside quotes,
+/// also reporting whether any operator is not the graph's `arrow`
+pub(super) fn split_edge_operators<'a>(statement: &'a str, arrow: &str) -> (Vec<&'a str>, bool) {
+    let mut parts = Vec::new();
+    let mut mismatched = false;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    let mut chars = statement.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '-' if !in_quotes => {
+                if let Some((_, next @ ('>' | '-'))) = chars.peek().copied() {
+                    mismatched |= !arrow.ends_with(next);
+                    parts.push(&statement[start..i]);
+                    chars.next();
+                    start = i + 2;
+                }
+            }
+            _ => {}
+        }
+    }
+    parts.push(&statement[start..]);
+    (parts, mismatched)
+}
+
+/// Split `text` at every `separator` outside double quotes
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Node ids of one side of an edge: a single id or a `{A B C}` set
+pub(super) fn endpoint_nodes(endpoint: &str) -> Vec<Endpoint> {
+    let endpoint = endpoint.trim().trim_end_matches(';');
+    endpoint
+        .trim_end()
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .map_or_else(
+            || vec![Endpoint::parse(endpoint)],
+            |members| {
+                members
+                    .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                    .filter(|member| !member.is_empty())
+                    .map(Endpoint::parse)
+                    .collect()
+            },
+        )
+}
+
+/// Edge properties with the endpoints' ports recorded as `from_port`/`to_port`
+fn port_properties(properties: &Properties, from: &Endpoint, to: &Endpoint) -> Properties {
+    let mut properties = properties.clone();
+    for (key, port) in [("from_port", &from.port), ("to_port", &to.port)] {
+        if let Some(port) = port {
+            properties.custom.insert(key.to_string(), port.clone());
+        }
+    }
+    properties
+}
+
+/// Split edge attributes into the label and the remaining properties
+fn edge_properties(
+    attrs: impl Iterator<Item = (String, String)>,
+    compound: bool,
+) -> (Option<String>, Properties) {
+    let mut properties = Properties::default();
+    let mut label = None;
+    for (key, value) in attrs {
+        match key.as_str() {
+            "label" => label = Some(value),
+            // Endpoint labels are placed near the head/tail by renderers
+            "headlabel" => {
+                properties.custom.insert("head_label".to_string(), value);
+            }
+            "taillabel" => {
+                properties.custom.insert("tail_label".to_string(), value);
+            }
+            _ => {
+                properties.custom.insert(key, value);
+            }
+        }
+    }
+
+    // Clip at cluster boundaries only when the graph opts into compound edges
+    if compound
+        && (properties.custom.contains_key("lhead") || properties.custom.contains_key("ltail"))
+    {
+        properties
+            .custom
+            .insert("clip_to_cluster".to_string(), "true".to_string());
+    }
+
+    (label, properties)
+}
+
+/// Graph attributes copied onto the `SetLayout` event's properties
+const LAYOUT_ATTRIBUTES: &[&str] = &[
+    "bgcolor", "label", "fontsize", "overlap", "splines", "charset", "ratio", "size", "margin",
+];
+
+/// Graph-level attributes in declaration order, set either bare
+/// (`key=value;`) or in a `graph [key=value];` default statement
+///
+/// Only statements directly inside the graph's braces count, so a cluster's
+/// `label` is not mistaken for the graph's.
+fn graph_attributes(lines: &[&str]) -> Vec<(String, String)> {
+    located_graph_attributes(lines)
+        .into_iter()
+        .map(|(_, key, value)| (key, value))
+        .collect()
+}
+
+/// [`graph_attributes`] as `(statement index, key, value)`
+fn located_graph_attributes(lines: &[&str]) -> Vec<(usize, String, String)> {
+    let mut attributes = Vec::new();
+    let mut depth = 0usize;
+    for (index, line) in lines.iter().enumerate() {
+        let located = |(key, value)| (index, key, value);
+        let trimmed = line.trim();
+        if depth == 1 {
+            if let Some(attrs_str) = trimmed
+                .strip_prefix("graph")
+                .map(str::trim_start)
+                .and_then(|rest| rest.strip_prefix('['))
+                .and_then(|rest| rest.rsplit_once(']'))
+                .map(|(attrs, _)| attrs)
+            {
+                attributes.extend(parse_attributes(attrs_str).into_iter().map(located));
+            } else if trimmed.contains('=')
+                && !trimmed.contains(['[', '{'])
+                && !trimmed.contains("->")
+                && !trimmed.contains("--")
+            {
+                attributes.extend(parse_attributes(trimmed).into_iter().map(located));
+            }
+        }
+        for c in unquoted_chars(trimmed) {
+            match c {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    attributes
+}
+
+/// The last value given for a graph attribute; later statements override
+fn graph_attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .rev()
+        .find_map(|(k, value)| (k == key).then_some(value.as_str()))
+}
+
+/// Layout properties from the graph's `LAYOUT_ATTRIBUTES`, mapped onto the
+/// style where a field fits and kept as custom entries otherwise
+fn layout_properties(attributes: &[(String, String)]) -> Properties {
+    let mut properties = Properties::default();
+    let mut style = Style::default();
+    for key in LAYOUT_ATTRIBUTES {
+        let Some(value) = graph_attribute(attributes, key) else {
+            continue;
+        };
+        // Typed hints are stored in their canonical spelling
+        let value = match *key {
+            "overlap" => Overlap::parse(value).as_str().to_string(),
+            "splines" => Splines::parse(value).as_str().to_string(),
+            _ if apply_style_attribute(&mut style, key, value) => continue,
+            _ => value.to_string(),
+        };
+        // Page dimensions in inches are also split into their components
+        let components = match *key {
+            "size" => Some(("size_width", "size_height")),
+            "margin" => Some(("margin_x", "margin_y")),
+            _ => None,
+        };
+        if let Some((first, second)) = components
+            && let Some((x, y, fill)) = parse_dimensions(&value)
+        {
+            properties.custom.insert(first.to_string(), x.to_string());
+            properties.custom.insert(second.to_string(), y.to_string());
+            if fill {
+                properties
+                    .custom
+                    .insert(format!("{key}_fill"), "true".to_string());
+            }
+        }
+        properties.custom.insert((*key).to_string(), value);
+    }
+    if style != Style::default() {
+        properties.style = Some(style);
+    }
+    properties
+}
+
+/// An open `subgraph cluster_*` block in the nested format
+struct ClusterFrame {
+    name: String,
+    node_id: Option<String>,
+    members: Vec<String>,
+    custom: HashMap<String, String>,
+}
+
+/// Whether the statements follow the nested org-chart format: labelled
+/// `subgraph cluster_*` blocks holding only labelled nodes, with no edges and
+/// nothing declared outside a cluster. Anything else takes the regular path,
+/// which keeps every node and edge.
+fn is_org_chart(lines: &[&str]) -> bool {
+    let mut depth = 0usize;
+    let mut has_cluster = false;
+    let mut has_header = false;
+    for line in lines {
+        let trimmed = line.trim();
+        let is_cluster_start = trimmed.starts_with("subgraph") && trimmed.contains("cluster_");
+        let is_node = trimmed.contains('[')
+            && trimmed.contains("label=")
+            && !trimmed.contains("->")
+            && !trimmed.contains("--");
+        let is_cluster_attribute = ["label=", "Label=", "labelloc", "labeljust"]
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix));
+        if trimmed.is_empty() {
+            continue;
+        }
+        if is_cluster_start {
+            depth += 1;
+            has_cluster = true;
+        } else if trimmed == "}" {
+            depth = depth.saturating_sub(1);
+        } else if !has_header && trimmed.ends_with('{') {
+            has_header = true;
+        } else if depth == 0 || !(is_node || is_cluster_attribute) {
+            return false;
+        }
+    }
+    has_cluster
+}
+
+/// Handle one statement of the nested org-chart format
+fn parse_nested_statement(line: &str, stack: &mut Vec<ClusterFrame>, events: &mut Vec<GraphEvent>) {
+    let trimmed = line.trim();
+
+    // Parse subgraph start
+    if trimmed.starts_with("subgraph") {
+        if let Some(cluster_start) = trimmed.find("cluster_") {
+            let cluster_name = trimmed[cluster_start..]
+                .split_whitespace()
+                .next()
+                .unwrap_or("");
+
+            // Find label in subsequent lines
+            stack.push(ClusterFrame {
+                name: cluster_name.to_string(),
+                node_id: None,
+                members: Vec::new(),
+                custom: HashMap::new(),
+            });
+        }
+    }
+    // Parse cluster label placement
+    else if (trimmed.starts_with("labelloc") || trimmed.starts_with("labeljust"))
+        && let Some(frame) = stack.last_mut()
+    {
+        for (key, value) in parse_attributes(trimmed) {
+            if key == "labelloc" || key == "labeljust" {
+                frame.custom.insert(key, value);
+            }
+        }
+    }
+    // Parse labels
+    else if (trimmed.starts_with("label=") || trimmed.starts_with("Label=")) && !stack.is_empty()
+    {
+        let label = extract_label_value(trimmed);
+
+        let node_type = cluster_node_type(&label);
+
+        let level = stack.len() as u32 - 1;
+        let properties = Properties {
+            position: Some(Position::Layer { level }),
+            ..Default::default()
+        };
+
+        // Create node for this cluster
+        let node_id = label.clone();
+        events.push(GraphEvent::AddNode {
+            id: node_id.clone(),
+            label: Some(label),
+            node_type,
+            properties,
+        });
+
+        // Connect to parent if exists
+        if stack.len() > 1
+            && let Some(ClusterFrame {
+                node_id: Some(parent_id),
+                ..
+            }) = stack.iter().rev().nth(1)
+        {
+            events.push(GraphEvent::AddEdge {
+                id: format!("{parent_id}->{node_id}"),
+                from: parent_id.clone(),
+                to: node_id.clone(),
+                edge_type: EdgeType::Directed,
+                label: None,
+                properties: Properties::default(),
+            });
+        }
+
+        // Update stack with node ID
+        if let Some(frame) = stack.last_mut() {
+            frame.members.push(node_id.clone());
+            frame.node_id = Some(node_id);
+        }
+    }
+    // Parse standalone nodes
+    else if trimmed.contains('[') && trimmed.contains("label=") && !trimmed.contains("->") {
+        add_cluster_member(trimmed, stack, events);
+    }
+    // Handle closing braces
+    else if trimmed == "}"
+        && let Some(frame) = stack.pop()
+    {
+        close_cluster(frame, stack, events);
+    }
+}
+
+/// Emit a standalone node declared inside the nested cluster format
+fn add_cluster_member(line: &str, stack: &mut [ClusterFrame], events: &mut Vec<GraphEvent>) {
+    if let Some((node_part, _)) = line.split_once('[') {
+        let label = extract_node_label(line).unwrap_or_else(|| unquote(node_part));
+
+        let level = stack.len() as u32;
+        let node_type = if label.to_lowercase().contains("supervisor") {
+            NodeType::Custom("team".to_string())
+        } else {
+            NodeType::Custom("user".to_string())
+        };
+
+        let properties = Properties {
+            position: Some(Position::Layer { level }),
+            ..Default::default()
+        };
+
+        events.push(GraphEvent::AddNode {
+            id: label.clone(),
+            label: Some(label.clone()),
+            node_type,
+            properties,
+        });
+
+        // Connect to parent if exists
+        if let Some(frame) = stack.last_mut() {
+            if let Some(parent_id) = &frame.node_id {
+                events.push(GraphEvent::AddEdge {
+                    id: format!("{parent_id}->{label}"),
+                    from: parent_id.clone(),
+                    to: label.clone(),
+                    edge_type: EdgeType::Directed,
+                    label: None,
+                    properties: Properties::default(),
+                });
+            }
+            frame.members.push(label);
+        }
+    }
+}
+
+/// Determine a cluster's node type based on label content
+fn cluster_node_type(label: &str) -> NodeType {
+    let lower = label.to_lowercase();
+    if lower.contains("tenant") || lower.contains("organization") {
+        NodeType::Custom("organization".to_string())
+    } else if lower.contains("contact center") {
+        NodeType::Custom("line_of_business".to_string())
+    } else if lower.contains("site") {
+        NodeType::Custom("site".to_string())
+    } else {
+        NodeType::Node
+    }
+}
+
+/// Emit a finished cluster as a container group nested inside its parent
+fn close_cluster(frame: ClusterFrame, stack: &mut [ClusterFrame], events: &mut Vec<GraphEvent>) {
+    if let Some(parent) = stack.last_mut() {
+        parent.members.push(frame.name.clone());
+    }
+    events.push(GraphEvent::AddGroup {
+        id: frame.name,
+        label: frame.node_id,
+        members: frame.members,
+        group_type: GroupType::Container,
+        properties: Properties {
+            custom: frame.custom,
+            ..Default::default()
+        },
+    });
+}
+
+fn extract_label_value(line: &str) -> String {
+    let mut label = unquote(
+        line.split_once('=')
+            .map_or(line, |(_, value)| value)
+            .trim()
+            .trim_end_matches(';'),
+    );
+
+    // Extract meaningful name from label (after the colon if present)
+    if let Some((_, name)) = label.split_once(':') {
+        label = name.trim().to_string();
+    }
+
+    label
+}
+
+fn extract_node_label(line: &str) -> Option<String> {
+    let (_, attrs_part) = line.split_once('[')?;
+    let attrs_str = attrs_part
+        .rsplit_once(']')
+        .map_or(attrs_part, |(attrs, _)| attrs);
+    let (_, label) = parse_attributes(attrs_str)
+        .into_iter()
+        .find(|(key, _)| key == "label")?;
+    Some(label.replace('\n', " ").trim().to_string())
+}
+
+// ============================================================================
+// Legacy API - Deprecated
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_graph_to_events() {
+        let dot = r"
+            digraph {
+                A -> B;
+                B -> C;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        // Should have: BatchStart, 3 nodes, 2 edges, BatchEnd
+        assert!(events.len() >= 6);
+        assert!(matches!(events.first(), Some(GraphEvent::BatchStart)));
+        assert!(matches!(events.last(), Some(GraphEvent::BatchEnd)));
+
+        // Count node and edge events
+        let node_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
+            .count();
+        let edge_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+            .count();
+
+        assert_eq!(node_count, 3);
+        assert_eq!(edge_count, 2);
+    }
+
+    #[test]
+    fn test_parse_node_with_attributes_to_events() {
+        let dot = r#"
+            digraph {
+                "Node1" [type="team", level="2", label="Team Alpha"];
+                "Node2" [type="user", level="1"];
+                "Node1" -> "Node2";
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        // Find the Node1 event
+        let node1_event = events
+            .iter()
+            .find(|e| matches!(e, GraphEvent::AddNode { id, .. } if id == "Node1"));
+
+        assert!(node1_event.is_some());
+
+        if let Some(GraphEvent::AddNode {
+            label,
+            node_type,
+            properties,
+            ..
+        }) = node1_event
+        {
+            assert_eq!(label.as_deref(), Some("Team Alpha"));
+            assert!(matches!(node_type, NodeType::Custom(t) if t == "team"));
+            assert!(matches!(
+                properties.position,
+                Some(Position::Layer { level: 2 })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_layout_detection() {
+        let dot = r"
+            digraph {
+                rankdir=LR;
+                A -> B;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        // Should have a SetLayout event
+        let layout_event = events
+            .iter()
+            .find(|e| matches!(e, GraphEvent::SetLayout { .. }));
+        assert!(layout_event.is_some());
+
+        if let Some(GraphEvent::SetLayout { layout_type, .. }) = layout_event {
+            assert!(matches!(
+                layout_type,
+                LayoutType::Hierarchical {
+                    direction: Direction::LeftToRight
+                }
+            ));
+        }
+    }
+
+    fn find_edge<'a>(events: &'a [GraphEvent], edge_id: &str) -> Option<&'a Properties> {
+        events.iter().find_map(|e| match e {
+            GraphEvent::AddEdge { id, properties, .. } if id == edge_id => Some(properties),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_compound_edges_clip_to_cluster() {
+        let dot = r"
+            digraph {
+                compound=true;
+                subgraph cluster_a {
+                    A;
+                }
+                subgraph cluster_b {
+                    B;
+                }
+                A -> B [lhead=cluster_b];
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+        let properties = find_edge(&events, "A->B").unwrap();
+
+        assert_eq!(
+            properties.custom.get("lhead").map(String::as_str),
+            Some("cluster_b")
+        );
+        assert_eq!(
+            properties.custom.get("clip_to_cluster").map(String::as_str),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn test_clip_to_cluster_requires_compound() {
+        let dot = r"
+            digraph {
+                A -> B [ltail=cluster_a];
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+        let properties = find_edge(&events, "A->B").unwrap();
+
+        assert_eq!(
+            properties.custom.get("ltail").map(String::as_str),
+            Some("cluster_a")
+        );
+        assert!(!properties.custom.contains_key("clip_to_cluster"));
+    }
+
+    #[test]
+    fn test_samehead_groups_edges() {
+        let dot = r"
+            digraph {
+                A -> C [samehead=g1];
+                B -> C [samehead=g1];
+                C -> D [sametail=t1];
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        for edge_id in ["A->C", "B->C"] {
+            let properties = find_edge(&events, edge_id).unwrap();
+            assert_eq!(
+                properties.custom.get("samehead").map(String::as_str),
+                Some("g1")
+            );
+        }
+        let properties = find_edge(&events, "C->D").unwrap();
+        assert_eq!(
+            properties.custom.get("sametail").map(String::as_str),
+            Some("t1")
+        );
+    }
+
+    #[test]
+    fn test_custom_node_type_table() {
+        let dot = r#"
+            digraph {
+                api [type="svc"];
+                cache [type="store"];
+                other [type="widget"];
+                api -> cache;
+            }
+        "#;
+
+        let options = ParseOptions {
+            node_types: HashMap::from([
+                ("svc".to_string(), NodeType::Process),
+                ("store".to_string(), NodeType::DataStore),
+            ]),
+            ..Default::default()
+        };
+        let events = parse_dot_to_events_with_options(dot, &options);
+
+        let node_type = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, node_type, .. } if id == node_id => {
+                    Some(node_type.clone())
+                }
+                _ => None,
+            })
+        };
+
+        assert_eq!(node_type("api"), Some(NodeType::Process));
+        assert_eq!(node_type("cache"), Some(NodeType::DataStore));
+        assert_eq!(
+            node_type("other"),
+            Some(NodeType::Custom("widget".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_graph_attributes_on_layout() {
+        let dot = r#"
+            digraph {
+                graph [rankdir=LR, bgcolor=white];
+                label="Overview";
+                subgraph cluster_a {
+                    label="Cluster";
+                    A -> B;
+                }
+                fontsize=20;
+                rankdir=RL;
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let (layout_type, properties) = events
+            .iter()
+            .find_map(|e| match e {
+                GraphEvent::SetLayout {
+                    layout_type,
+                    properties,
+                } => Some((layout_type.clone(), properties.clone())),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            layout_type,
+            LayoutType::Hierarchical {
+                direction: Direction::RightToLeft
+            }
+        );
+        let style = properties.style.unwrap();
+        assert_eq!(style.background_color.as_deref(), Some("white"));
+        assert_eq!(style.font_size, Some(20.0));
+        assert_eq!(
+            properties.custom.get("label").map(String::as_str),
+            Some("Overview")
+        );
+    }
+
+    #[test]
+    fn test_typed_layout_hints() {
+        let layout_properties = |dot: &str| {
+            parse_dot_to_events(dot).into_iter().find_map(|e| match e {
+                GraphEvent::SetLayout { properties, .. } => Some(properties),
+                _ => None,
+            })
+        };
+
+        let properties =
+            layout_properties("graph {\n    overlap=scale;\n    splines=false;\n    A -- B;\n}\n")
+                .unwrap();
+        assert_eq!(properties.overlap(), Some(Overlap::Scale));
+        assert_eq!(properties.splines(), Some(Splines::Line));
+        assert_eq!(
+            properties.custom.get("splines").map(String::as_str),
+            Some("line")
+        );
+
+        let properties = layout_properties("graph {\n    graph [overlap=no];\n}\n").unwrap();
+        assert_eq!(properties.overlap(), Some(Overlap::Remove));
+        assert_eq!(properties.splines(), None);
+    }
+
+    #[test]
+    fn test_page_size_attributes() {
+        let dot =
+            "digraph {\n    size=\"8,11\";\n    ratio=fill;\n    margin=0.5;\n    A -> B;\n}\n";
+        let properties = parse_dot_to_events(dot)
+            .into_iter()
+            .find_map(|e| match e {
+                GraphEvent::SetLayout { properties, .. } => Some(properties),
+                _ => None,
+            })
+            .unwrap();
+        let custom = |key: &str| properties.custom.get(key).map(String::as_str);
+
+        assert_eq!(custom("size"), Some("8,11"));
+        assert_eq!(custom("size_width"), Some("8"));
+        assert_eq!(custom("size_height"), Some("11"));
+        assert_eq!(custom("size_fill"), None);
+        assert_eq!(custom("ratio"), Some("fill"));
+        assert_eq!(custom("margin_x"), Some("0.5"));
+        assert_eq!(custom("margin_y"), Some("0.5"));
+
+        assert_eq!(parse_dimensions("7.5,10!"), Some((7.5, 10.0, true)));
+        assert_eq!(parse_dimensions("wide"), None);
+    }
+
+    #[test]
+    fn test_layout_detection_graph_attribute_statement() {
+        let dot = r"
+            digraph {
+                graph [rankdir=BT];
+                A -> B;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        let layout_type = events.iter().find_map(|e| match e {
+            GraphEvent::SetLayout { layout_type, .. } => Some(layout_type.clone()),
+            _ => None,
+        });
+        assert_eq!(
+            layout_type,
+            Some(LayoutType::Hierarchical {
+                direction: Direction::BottomToTop
+            })
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, GraphEvent::AddNode { id, .. } if id == "graph"))
+        );
+    }
+
+    #[test]
+    fn test_unicode_identifiers_and_labels() {
+        let dot = r#"
+            digraph {
+                café [label="Café ☕", type="shop"];
+                "東京" [label="東京駅"];
+                café -> naïve;
+                naïve -> "東京";
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let labels: Vec<(&str, Option<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, label, .. } => Some((id.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("café", Some("Café ☕")),
+                ("東京", Some("東京駅")),
+                ("naïve", Some("naïve")),
+            ]
+        );
+        assert!(find_edge(&events, "café->naïve").is_some());
+        assert!(find_edge(&events, "naïve->東京").is_some());
+    }
+
+    #[test]
+    fn test_misplaced_brackets_do_not_panic() {
+        let dot = "digraph {\n    é ] x [ ;\n    label ü\n}\n";
+
+        let events = parse_dot_to_events(dot);
+
+        assert!(matches!(events.last(), Some(GraphEvent::BatchEnd)));
+    }
+
+    #[test]
+    fn test_redeclared_edge_emits_update() {
+        let dot = r"
+            digraph {
+                A -> B;
+                A -> B [color=red];
+            }
+        ";
+
+        let options = ParseOptions {
+            update_redeclared_edges: true,
+            ..Default::default()
+        };
+        let events = parse_dot_to_events_with_options(dot, &options);
+
+        let add_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+            .count();
+        assert_eq!(add_count, 1);
+
+        let update = events.iter().find_map(|e| match e {
+            GraphEvent::UpdateEdge { id, properties, .. } => Some((id, properties)),
+            _ => None,
+        });
+        let (id, properties) = update.unwrap();
+        assert_eq!(id, "A->B");
+        assert_eq!(
+            properties.custom.get("color").map(String::as_str),
+            Some("red")
+        );
+
+        // Without the option the redeclaration is a second AddEdge
+        let events = parse_dot_to_events(dot);
+        let add_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+            .count();
+        assert_eq!(add_count, 2);
+    }
+
+    #[test]
+    fn test_edge_label_head_and_tail_labels() {
+        let dot = r#"
+            digraph {
+                A -> B [label="calls", headlabel="1", taillabel="*"];
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let edge = events.iter().find_map(|e| match e {
+            GraphEvent::AddEdge {
+                label, properties, ..
+            } => Some((label, properties)),
+            _ => None,
+        });
+        let (label, properties) = edge.unwrap();
+        assert_eq!(label.as_deref(), Some("calls"));
+        assert_eq!(
+            properties.custom.get("head_label").map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(
+            properties.custom.get("tail_label").map(String::as_str),
+            Some("*")
+        );
+        assert!(!properties.custom.contains_key("label"));
+    }
+
+    #[test]
+    fn test_cluster_label_placement() {
+        let dot = r#"
+            digraph {
+                subgraph cluster_tenant {
+                    label="Acme Tenant"
+                    labelloc=b
+                    labeljust=r
+                    alice [label="Agent: Alice"];
+                }
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let properties = events
+            .iter()
+            .find_map(|e| match e {
+                GraphEvent::AddGroup { id, properties, .. } if id == "cluster_tenant" => {
+                    Some(properties)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            properties.custom.get("labelloc").map(String::as_str),
+            Some("b")
+        );
+        assert_eq!(
+            properties.custom.get("labeljust").map(String::as_str),
+            Some("r")
+        );
+    }
+
+    #[test]
+    fn test_pipeline_layout_inference() {
+        let dot = r"
+            digraph {
+                rankdir=LR;
+                extract -> transform;
+                transform -> load;
+            }
+        ";
+        let options = ParseOptions {
+            infer_pipeline_layout: true,
+            ..Default::default()
+        };
+
+        let layout = |events: &[GraphEvent]| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::SetLayout { layout_type, .. } => Some(layout_type.clone()),
+                _ => None,
+            })
+        };
+
+        let events = parse_dot_to_events_with_options(dot, &options);
+        assert_eq!(
+            layout(&events),
+            Some(LayoutType::Sequential {
+                direction: Direction::LeftToRight
+            })
+        );
+
+        // Opt-in only
+        let events = parse_dot_to_events(dot);
+        assert!(matches!(
+            layout(&events),
+            Some(LayoutType::Hierarchical { .. })
+        ));
+
+        // A branch is not a pipeline
+        let branched = dot.replace(
+            "transform -> load;",
+            "transform -> load;\n extract -> audit;",
+        );
+        let events = parse_dot_to_events_with_options(&branched, &options);
+        assert!(matches!(
+            layout(&events),
+            Some(LayoutType::Hierarchical { .. })
+        ));
+    }
+
+    #[test]
+    fn test_state_shapes() {
+        let dot = r"
+            digraph {
+                start [shape=point];
+                begin [shape=circle, style=filled, width=0.2];
+                idle [shape=circle];
+                done [shape=doublecircle];
+                start -> idle;
+                idle -> done;
+            }
+        ";
+        let options = ParseOptions {
+            infer_state_shapes: true,
+            ..Default::default()
+        };
+
+        let events = parse_dot_to_events_with_options(dot, &options);
+        let node_type = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, node_type, .. } if id == node_id => {
+                    Some(node_type.clone())
+                }
+                _ => None,
+            })
+        };
+
+        let state = |state_type| Some(NodeType::State { state_type });
+        assert_eq!(node_type("start"), state(StateType::Initial));
+        assert_eq!(node_type("begin"), state(StateType::Initial));
+        assert_eq!(node_type("idle"), Some(NodeType::Node));
+        assert_eq!(node_type("done"), state(StateType::Final));
+    }
+
+    #[test]
+    fn test_anonymous_block_scopes_defaults() {
+        let dot = r#"
+            digraph {
+                {
+                    node [shape=box];
+                    edge [style=dashed];
+                    A [label="Inside"];
+                    A -> C;
+                }
+                B [label="Outside"];
+                A -> B;
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let node_shape = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => {
+                    Some(properties.style.as_ref().and_then(|s| s.shape.clone()))
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(node_shape("A"), Some(Some("box".to_string())));
+        assert_eq!(node_shape("B"), Some(None));
+        assert_eq!(node_shape("node"), None);
+
+        let style = |edge_id| find_edge(&events, edge_id).map(|p| p.custom.get("style").cloned());
+        assert_eq!(style("A->C"), Some(Some("dashed".to_string())));
+        assert_eq!(style("A->B"), Some(None));
+    }
+
+    #[test]
+    fn test_edge_label_forms_and_visual_attributes() {
+        let dot = r#"
+            digraph {
+                Decision -> End [label="Yes"];
+                Decision -> Retry [label=No, style=dashed, color=red, weight=2];
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let edges: Vec<(&str, &str, Option<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    from, to, label, ..
+                } => Some((from.as_str(), to.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("Decision", "End", Some("Yes")),
+                ("Decision", "Retry", Some("No")),
+            ]
+        );
+
+        let custom = &find_edge(&events, "Decision->Retry").unwrap().custom;
+        assert_eq!(custom.get("style").map(String::as_str), Some("dashed"));
+        assert_eq!(custom.get("color").map(String::as_str), Some("red"));
+        assert_eq!(custom.get("weight").map(String::as_str), Some("2"));
+    }
+
+    #[test]
+    fn test_hashed_edge_ids_are_stable() {
+        let options = ParseOptions {
+            hashed_edge_ids: true,
+            ..Default::default()
+        };
+        let edge_ids = |dot: &str| -> Vec<String> {
+            parse_dot_to_events_with_options(dot, &options)
+                .into_iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge { id, .. } => Some(id),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let original = edge_ids("digraph {\n    A -> B [label=\"calls\", color=red];\n}\n");
+        let recolored = edge_ids("digraph {\n    A -> B [label=\"calls\", color=blue];\n}\n");
+        let relabeled = edge_ids("digraph {\n    A -> B [label=\"invokes\", color=red];\n}\n");
+
+        assert_eq!(original.len(), 1);
+        assert_ne!(original[0], "A->B");
+        assert_eq!(original, recolored);
+        assert_ne!(original, relabeled);
+    }
+
+    #[test]
+    fn test_chained_edges() {
+        let dot = r"
+            digraph {
+                A -> B -> C -> D [color=blue];
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        let node_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
+            .count();
+        assert_eq!(node_count, 4);
+
+        for edge_id in ["A->B", "B->C", "C->D"] {
+            let properties = find_edge(&events, edge_id).unwrap();
+            assert_eq!(
+                properties.custom.get("color").map(String::as_str),
+                Some("blue")
+            );
+        }
+        assert!(find_edge(&events, "A->B -> C -> D").is_none());
+    }
+
+    #[test]
+    fn test_statements_on_one_line() {
+        let events = parse_dot_to_events("digraph { a -> b; c -> d; { rank=same; X; Y; } }\n");
+
+        let mut nodes: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        nodes.sort_unstable();
+        assert_eq!(nodes, vec!["X", "Y", "a", "b", "c", "d"]);
+
+        let edges: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edges, vec!["a->b", "c->d"]);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddGroup { members, .. } if members == &["X", "Y"]
+        )));
+    }
+
+    #[test]
+    fn test_undirected_edges_with_attributes() {
+        for dot in [
+            "graph {\n    A -- C [color=red];\n}\n",
+            "digraph {\n    A -- C [color=red];\n}\n",
+        ] {
+            let events = parse_dot_to_events(dot);
+
+            let mut nodes: Vec<&str> = events
+                .iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                    _ => None,
+                })
+                .collect();
+            nodes.sort_unstable();
+            assert_eq!(nodes, vec!["A", "C"], "{dot}");
+            let colors: Vec<Option<&str>> = events
+                .iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge { properties, .. } => {
+                        Some(properties.custom.get("color").map(String::as_str))
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(colors, vec![Some("red")], "{dot}");
+        }
+    }
+
+    #[test]
+    fn test_multi_line_node_declaration() {
+        let dot =
+            "digraph {\n    A [\n        label=\"x\",\n        color=red\n    ];\n    A -> B;\n}\n";
+
+        let events = parse_dot_to_events(dot);
+
+        let node = events.iter().find_map(|e| match e {
+            GraphEvent::AddNode {
+                id,
+                label,
+                properties,
+                ..
+            } if id == "A" => Some((
+                label.as_deref(),
+                properties.style.as_ref().and_then(|s| s.color.as_deref()),
+            )),
+            _ => None,
+        });
+        assert_eq!(node, Some((Some("x"), Some("red"))));
+    }
+
+    #[test]
+    fn test_block_comment_hides_edges() {
+        let dot = r"
+            digraph {
+                A -> B;
+                /* disabled:
+                   B -> C;
+                   C -> A; */
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        assert!(find_edge(&events, "A->B").is_some());
+        assert!(find_edge(&events, "B->C").is_none());
+        assert!(find_edge(&events, "C->A").is_none());
+        assert!(
+            !events.iter().any(
+                |e| matches!(e, GraphEvent::AddNode { id, .. } if id == "C" || id.contains('*'))
+            )
+        );
+    }
+
+    #[test]
+    fn test_default_statements_apply_to_later_elements() {
+        let dot = r"
+            digraph {
+                A [label=First];
+                node [shape=box, color=blue];
+                edge [style=dashed];
+                B [color=red];
+                A -> B;
+                B -> C;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        let node_style = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => Some(
+                    properties
+                        .style
+                        .as_ref()
+                        .map(|style| (style.color.as_deref(), style.shape.as_deref())),
+                ),
+                _ => None,
+            })
+        };
+        assert_eq!(node_style("A"), Some(None));
+        assert_eq!(node_style("B"), Some(Some((Some("red"), Some("box")))));
+        // Auto-created from an edge after the defaults
+        assert_eq!(node_style("C"), Some(Some((Some("blue"), Some("box")))));
+        assert!(node_style("node").is_none());
+        assert!(node_style("edge").is_none());
+
+        let style =
+            |edge_id| find_edge(&events, edge_id).and_then(|p| p.custom.get("style").cloned());
+        assert_eq!(style("A->B").as_deref(), Some("dashed"));
+        assert_eq!(style("B->C").as_deref(), Some("dashed"));
+    }
+
+    #[test]
+    fn test_visual_attributes_populate_style() {
+        let dot = r##"
+            digraph {
+                A [
+                    color=red, fillcolor="#eee", style=dashed, penwidth=2, shape=box,
+                    fontsize=12, fontname=Helvetica, width=1.5, height=0.5,
+                    tooltip=hi, fontcolor=blue
+                ];
+                B [penwidth=thick];
+            }
+        "##;
+
+        let events = parse_dot_to_events(dot);
+        let properties = |node_id: &str| {
+            events
+                .iter()
+                .find_map(|e| match e {
+                    GraphEvent::AddNode { id, properties, .. } if id == node_id => {
+                        Some(properties.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        let a = properties("A");
+        assert_eq!(
+            a.style,
+            Some(Style {
+                color: Some("red".to_string()),
+                background_color: Some("#eee".to_string()),
+                border_style: Some("dashed".to_string()),
+                border_width: Some(2.0),
+                shape: Some("box".to_string()),
+                size: Some(1.5),
+                font_size: Some(12.0),
+                font_family: Some("Helvetica".to_string()),
+                ..Default::default()
+            })
+        );
+        let mut custom: Vec<&str> = a.custom.keys().map(String::as_str).collect();
+        custom.sort_unstable();
+        assert_eq!(custom, vec!["fontcolor", "height", "tooltip"]);
+
+        // Unparseable numbers stay custom
+        let b = properties("B");
+        assert_eq!(b.style, None);
+        assert_eq!(b.custom.get("penwidth").map(String::as_str), Some("thick"));
+    }
+
+    #[test]
+    fn test_fontname_keeps_fallback_stack() {
+        let dot = r#"
+            digraph {
+                node [fontname="Helvetica, Arial, sans-serif", shape=box];
+                A;
+                B [fontname="Fira Code,monospace", label="b, c"];
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+        let font = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => properties
+                    .style
+                    .as_ref()
+                    .and_then(|style| style.font_family.clone()),
+                _ => None,
+            })
+        };
+
+        assert_eq!(font("A").as_deref(), Some("Helvetica, Arial, sans-serif"));
+        assert_eq!(font("B").as_deref(), Some("Fira Code,monospace"));
+        let label = events.iter().find_map(|e| match e {
+            GraphEvent::AddNode { id, label, .. } if id == "B" => label.as_deref(),
+            _ => None,
+        });
+        assert_eq!(label, Some("b, c"));
+    }
+
+    #[test]
+    fn test_empty_default_statement_resets() {
+        let dot = r"
+            digraph {
+                edge [style=dashed, color=gray];
+                A -> B;
+                edge [];
+                B -> C;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        let custom = |edge_id| find_edge(&events, edge_id).unwrap().custom.clone();
+        assert_eq!(
+            custom("A->B").get("style").map(String::as_str),
+            Some("dashed")
+        );
+        assert!(custom("B->C").is_empty());
+    }
+
+    #[test]
+    fn test_brace_group_endpoints() {
+        let dot = r"
+            digraph {
+                {A B} -> {C D};
+                E -> {F G} -> H;
+            }
+        ";
+
+        let events = parse_dot_to_events(dot);
+
+        let edges: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                "A->C", "A->D", "B->C", "B->D", "E->F", "E->G", "F->H", "G->H"
+            ]
+        );
+
+        let nodes: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nodes, vec!["A", "B", "C", "D", "E", "F", "G", "H"]);
+    }
+
+    #[test]
+    fn test_clusters_in_graph_with_edges() {
+        let dot = r#"
+            digraph {
+                subgraph cluster_api {
+                    label="API";
+                    gateway -> auth;
+                }
+                auth -> db;
+            }
+        "#;
+
+        let events = parse_dot_to_events(dot);
+
+        let group = events.iter().find_map(|e| match e {
+            GraphEvent::AddGroup {
+                id,
+                label,
+                members,
+                group_type: GroupType::Cluster,
+                ..
+            } => Some((id.as_str(), label.as_deref(), members.clone())),
+            _ => None,
+        });
+        assert_eq!(
+            group,
+            Some((
+                "cluster_api",
+                Some("API"),
+                vec!["gateway".to_string(), "auth".to_string()]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_brackets_inside_quoted_ids() {
+        let dot = "digraph {\n    \"a[1]\" -> b;\n    subgraph cluster_x {\n        \"c[2]\" [color=red];\n    }\n}\n";
+
+        let events = parse_dot_to_events(dot);
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddEdge { from, to, .. } if from == "a[1]" && to == "b"
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddNode { id, properties, .. }
+                if id == "c[2]"
+                    && properties.style.as_ref().and_then(|s| s.color.as_deref()) == Some("red")
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddGroup { members, .. } if members == &["c[2]"]
+        )));
+        assert!(!events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddNode { id, .. } if id == "a" || id == "c"
+        )));
+    }
+
+    #[test]
+    fn test_only_org_charts_take_the_nested_path() {
+        let summary = |dot: &str| {
+            let mut nodes = Vec::new();
+            let mut edges = Vec::new();
+            let mut groups = Vec::new();
+            for event in parse_dot_to_events(dot) {
+                match event {
+                    GraphEvent::AddNode { id, .. } => nodes.push(id),
+                    GraphEvent::AddEdge { id, .. } => edges.push(id),
+                    GraphEvent::AddGroup {
+                        id,
+                        members,
+                        group_type,
+                        ..
+                    } => groups.push((id, members, group_type)),
+                    _ => {}
+                }
+            }
+            (nodes, edges, groups)
+        };
 
-    // Start batch for efficiency
-    events.push(GraphEvent::BatchStart);
+        let (nodes, edges, groups) =
+            summary(r#"graph G { a -- b; subgraph cluster_x { label="X"; c; d; } c -- d }"#);
+        assert_eq!(nodes, ["c", "d", "a", "b"]);
+        assert_eq!(edges, ["a--b", "c--d"]);
+        assert_eq!(
+            groups,
+            [(
+                "cluster_x".to_string(),
+                vec!["c".to_string(), "d".to_string()],
+                GroupType::Cluster
+            )]
+        );
 
-    // Check if this is a nested subgraph format
-    let has_edges = content.contains("->");
-    let is_digraph = content.contains("digraph");
+        let (nodes, edges, groups) = summary("digraph { subgraph { node [color=red]; a; } b; }");
+        assert_eq!(nodes, ["a", "b"]);
+        assert!(edges.is_empty() && groups.is_empty());
 
-    if !has_edges && content.contains("subgraph") {
-        parse_nested_subgraphs_to_events(content, &mut events);
-    } else {
-        parse_regular_dot(content, &mut events, &mut node_attributes, is_digraph);
+        let (nodes, _, groups) = summary(r#"digraph { subgraph cluster_a { x [label="X"]; y; } }"#);
+        assert_eq!(nodes, ["x", "y"]);
+        assert_eq!(groups[0].1, ["x", "y"]);
     }
 
-    // End batch
-    events.push(GraphEvent::BatchEnd);
-
-    events
-}
+    #[test]
+    fn test_mismatched_edge_operator_is_normalized() {
+        let dot = "digraph {\n    A -> B;\n    A -- C;\n    \"x--y\" -> D;\n}\n";
 
-fn parse_regular_dot(
-    content: &str,
-    events: &mut Vec<GraphEvent>,
-    node_attributes: &mut NodeAttributes,
-    is_digraph: bool,
-) {
-    // Detect layout direction
-    if let Some(rankdir) = extract_rankdir(content) {
-        let direction = match rankdir.as_str() {
-            "BT" => Direction::BottomToTop,
-            "LR" => Direction::LeftToRight,
-            "RL" => Direction::RightToLeft,
-            _ => Direction::TopToBottom, // Default: TB
-        };
-        events.push(GraphEvent::SetLayout {
-            layout_type: LayoutType::Hierarchical { direction },
-            properties: Properties::default(),
-        });
+        let edges: Vec<_> = parse_dot_to_events(dot)
+            .into_iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    id,
+                    edge_type,
+                    properties,
+                    ..
+                } => Some((
+                    id,
+                    edge_type,
+                    properties.custom.get("declared_arrow").cloned(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("A->B".to_string(), EdgeType::Directed, None),
+                (
+                    "A->C".to_string(),
+                    EdgeType::Directed,
+                    Some("--".to_string())
+                ),
+                ("x--y->D".to_string(), EdgeType::Directed, None),
+            ]
+        );
     }
 
-    let lines: Vec<&str> = content.lines().collect();
+    #[test]
+    fn test_absolute_positions() {
+        let dot = r#"
+            digraph {
+                A [pos="10,20!"];
+                B [pos="30.5,40"];
+            }
+        "#;
 
-    // Parse nodes
-    parse_nodes(&lines, events, node_attributes);
+        let nodes: Vec<_> = parse_dot_to_events(dot)
+            .into_iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } => Some((
+                    id,
+                    properties.position,
+                    properties.custom.get("pinned").cloned(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (
+                    "A".to_string(),
+                    Some(Position::Absolute {
+                        x: 10.0,
+                        y: 20.0,
+                        z: None
+                    }),
+                    Some("true".to_string())
+                ),
+                (
+                    "B".to_string(),
+                    Some(Position::Absolute {
+                        x: 30.5,
+                        y: 40.0,
+                        z: None
+                    }),
+                    None
+                ),
+            ]
+        );
+    }
 
-    // Parse edges
-    parse_edges(&lines, events, node_attributes, is_digraph);
-}
+    #[test]
+    fn test_negative_and_malformed_positions() {
+        let dot = r#"
+            digraph {
+                A [pos="-12.5,340.0"];
+                B [pos=" +3e1 , -0.25 ,7 !"];
+                C [pos="12.5"];
+                D [pos="1,,2"];
+                E [pos="nan,inf"];
+                F [pos="!"];
+            }
+        "#;
 
-fn parse_nodes(lines: &[&str], events: &mut Vec<GraphEvent>, node_attributes: &mut NodeAttributes) {
-    for line in lines {
-        let trimmed = line.trim();
+        let events = parse_dot_to_events(dot);
+        let node = |node_id: &str| {
+            events
+                .iter()
+                .find_map(|e| match e {
+                    GraphEvent::AddNode { id, properties, .. } if id == node_id => {
+                        Some(properties.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
 
-        // Skip comments and empty lines
-        if trimmed.starts_with("//") || trimmed.is_empty() {
-            continue;
+        assert_eq!(
+            node("A").position,
+            Some(Position::Absolute {
+                x: -12.5,
+                y: 340.0,
+                z: None
+            })
+        );
+        let b = node("B");
+        assert_eq!(
+            b.position,
+            Some(Position::Absolute {
+                x: 30.0,
+                y: -0.25,
+                z: Some(7.0)
+            })
+        );
+        assert_eq!(b.custom.get("pinned").map(String::as_str), Some("true"));
+
+        // Malformed values are kept verbatim instead of becoming a position
+        for (node_id, pos) in [("C", "12.5"), ("D", "1,,2"), ("E", "nan,inf"), ("F", "!")] {
+            let properties = node(node_id);
+            assert_eq!(properties.position, None, "{node_id}");
+            assert_eq!(properties.custom.get("pos").map(String::as_str), Some(pos));
         }
+    }
 
-        // Parse node definitions with attributes
-        if trimmed.contains('[') && trimmed.contains(']') && !trimmed.contains("->") {
-            if let Some(node_end) = trimmed.find('[') {
-                let node_id = trimmed[..node_end].trim().trim_matches('"');
-
-                // Extract attributes
-                let attrs_str = &trimmed[node_end + 1..trimmed.rfind(']').unwrap_or(trimmed.len())];
-                let mut node_type = None;
-                let mut level = None;
-                let mut label = None;
-                let mut properties = Properties::default();
-                let mut custom_props = HashMap::new();
-
-                // Parse attributes
-                for attr in attrs_str.split(',') {
-                    let parts: Vec<&str> = attr.split('=').collect();
-                    if parts.len() == 2 {
-                        let key = parts[0].trim();
-                        let value = parts[1].trim().trim_matches('"');
-
-                        match key {
-                            "type" => node_type = Some(value.to_string()),
-                            "level" => level = value.parse::<u32>().ok(),
-                            "label" => label = Some(value.to_string()),
-                            _ => {
-                                custom_props.insert(key.to_string(), value.to_string());
-                            }
-                        }
-                    }
-                }
+    #[test]
+    fn test_checked_parse() {
+        let dot = "digraph {\n    // a -- comment\n    A -> B;\n}\n";
+        assert_eq!(parse_dot_checked(dot), Ok(parse_dot_to_events(dot)));
 
-                // Store attributes for later use
-                node_attributes.insert(
-                    node_id.to_string(),
-                    (
-                        node_type.clone(),
-                        level,
-                        label.clone(),
-                        custom_props.clone(),
-                    ),
-                );
+        let error = parse_dot_checked("digraph {\n    A -> B;\n    B -- C;\n}\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 7));
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 7: undirected edge `--` in a digraph"
+        );
 
-                // Set position if level is specified
-                if let Some(lvl) = level {
-                    properties.position = Some(Position::Layer { level: lvl });
-                }
+        // Columns count the characters of comments before the error
+        let error = parse_dot_checked("digraph {\n/* c */ A -- B;\n}\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+    }
 
-                properties.custom = custom_props;
+    #[test]
+    fn test_charset_is_recorded() {
+        let latin1 = "digraph {\n    charset=\"latin1\";\n    A -> B;\n}\n";
+        let (events, warnings) = parse_dot_checked_with_warnings(latin1).unwrap();
 
-                // Emit node event
-                events.push(GraphEvent::AddNode {
-                    id: node_id.to_string(),
-                    label: label.or_else(|| Some(node_id.to_string())),
-                    node_type: node_type.map_or(NodeType::Node, NodeType::Custom),
-                    properties,
-                });
-            }
-        }
-    }
-}
+        let charset = events.iter().find_map(|e| match e {
+            GraphEvent::SetLayout { properties, .. } => properties.custom.get("charset"),
+            _ => None,
+        });
+        assert_eq!(charset.map(String::as_str), Some("latin1"));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].column), (2, 5));
+        assert!(warnings[0].message.contains("latin1"));
 
-fn parse_edges(
-    lines: &[&str],
-    events: &mut Vec<GraphEvent>,
-    node_attributes: &mut NodeAttributes,
-    is_digraph: bool,
-) {
-    for line in lines {
-        let trimmed = line.trim();
+        let commented = "digraph {\n    /* legacy */ charset=\"latin1\";\n}\n";
+        let (_, warnings) = parse_dot_checked_with_warnings(commented).unwrap();
+        assert_eq!((warnings[0].line, warnings[0].column), (2, 18));
 
-        if trimmed.contains("->") || trimmed.contains("--") {
-            let arrow = if is_digraph { "->" } else { "--" };
-            if let Some(arrow_pos) = trimmed.find(arrow) {
-                let from = trimmed[..arrow_pos]
-                    .trim()
-                    .trim_matches('"')
-                    .trim_end_matches(';');
-
-                let to_part = &trimmed[arrow_pos + arrow.len()..];
-                let to = to_part
-                    .split('[')
-                    .next()
-                    .unwrap_or(to_part)
-                    .trim()
-                    .trim_matches('"')
-                    .trim_end_matches(';');
-
-                // Ensure nodes exist
-                if !node_attributes.contains_key(from) {
-                    events.push(GraphEvent::AddNode {
-                        id: from.to_string(),
-                        label: Some(from.to_string()),
-                        node_type: NodeType::Node,
-                        properties: Properties::default(),
-                    });
-                    node_attributes.insert(from.to_string(), (None, None, None, HashMap::new()));
-                }
+        // The warning points at the attribute, not at earlier mentions
+        let decoy = "digraph {\n    charset_node [label=\"charset\"];\n    graph [label=\"charset\", charset=latin1];\n}\n";
+        let (_, warnings) = parse_dot_checked_with_warnings(decoy).unwrap();
+        assert_eq!((warnings[0].line, warnings[0].column), (3, 29));
+        assert_eq!(
+            warnings[0].to_string(),
+            "line 3, column 29: charset \"latin1\" ignored: the input was already decoded as UTF-8"
+        );
 
-                if !node_attributes.contains_key(to) {
-                    events.push(GraphEvent::AddNode {
-                        id: to.to_string(),
-                        label: Some(to.to_string()),
-                        node_type: NodeType::Node,
-                        properties: Properties::default(),
-                    });
-                    node_attributes.insert(to.to_string(), (None, None, None, HashMap::new()));
+        let utf8 = "digraph {\n    graph [charset=\"UTF-8\"];\n    A -> B;\n}\n";
+        let (_, warnings) = parse_dot_checked_with_warnings(utf8).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_iterator_matches_collected_events() {
+        let regular = r#"
+            digraph G {
+                rankdir=LR;
+                node [shape=box];
+                subgraph cluster_a {
+                    label="A";
+                    A -> B;
                 }
+                B [color=red];
+                B -> C [label="next"];
+            }
+        "#;
+        let nested = r#"
+            digraph {
+                subgraph cluster_org {
+                    label="Org: Acme";
+                    alice [label="Alice"];
+                }
+            }
+        "#;
 
-                // Create edge
-                let edge_type = if is_digraph {
-                    EdgeType::Directed
-                } else {
-                    EdgeType::Undirected
-                };
+        for dot in [regular, nested] {
+            let streamed: Vec<GraphEvent> = parse_dot_iter(dot).collect();
+            assert_eq!(streamed, parse_dot_to_events(dot));
+            assert!(matches!(streamed.first(), Some(GraphEvent::BatchStart)));
+            assert!(matches!(streamed.last(), Some(GraphEvent::BatchEnd)));
+        }
 
-                events.push(GraphEvent::AddEdge {
-                    id: format!("{from}{arrow}{to}"),
-                    from: from.to_string(),
-                    to: to.to_string(),
-                    edge_type,
-                    label: None,
+        let small = "digraph G {\n    A [label=\"Start\"];\n    A -> B [label=\"next\"];\n}\n";
+        let node = |id: &str, label: &str| GraphEvent::AddNode {
+            id: id.to_string(),
+            label: Some(label.to_string()),
+            node_type: NodeType::Node,
+            properties: Properties::default(),
+        };
+        assert_eq!(
+            parse_dot_iter(small).collect::<Vec<_>>(),
+            vec![
+                GraphEvent::BatchStart,
+                GraphEvent::SetGraphMeta {
+                    name: Some("G".to_string()),
+                    directed: true,
+                    strict: false,
+                },
+                node("A", "Start"),
+                node("B", "B"),
+                GraphEvent::AddEdge {
+                    id: "A->B".to_string(),
+                    from: "A".to_string(),
+                    to: "B".to_string(),
+                    edge_type: EdgeType::Directed,
+                    label: Some("next".to_string()),
                     properties: Properties::default(),
-                });
-            }
-        }
+                },
+                GraphEvent::BatchEnd,
+            ]
+        );
+
+        let options = ParseOptions {
+            source_order: true,
+            message_sequence_base: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_dot_iter_with_options(regular, &options).collect::<Vec<_>>(),
+            parse_dot_to_events_with_options(regular, &options)
+        );
     }
-}
 
-fn extract_rankdir(content: &str) -> Option<String> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("rankdir") {
-            if let Some(eq_pos) = trimmed.find('=') {
-                let value = trimmed[eq_pos + 1..]
-                    .trim()
-                    .trim_end_matches(';')
-                    .trim_matches('"');
-                return Some(value.to_string());
+    #[test]
+    fn test_trailing_block_comments() {
+        let dot = r#"
+            digraph {
+                A -> B; /* trailing */
+                C [color=red]; /* spans
+                   lines */ D -> E [label="x"]; /* again */
+                F -> /* mid */ G;
+                H /* before attributes */ [shape=box];
             }
-        }
-    }
-    None
-}
+        "#;
+        let events = parse_dot_to_events(dot);
 
-fn parse_nested_subgraphs_to_events(content: &str, events: &mut Vec<GraphEvent>) {
-    let mut stack: Vec<(String, Option<String>)> = Vec::new();
+        let edges: Vec<(&str, Option<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { id, label, .. } => Some((id.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![("A->B", None), ("D->E", Some("x")), ("F->G", None)]
+        );
 
-    for line in content.lines() {
-        let trimmed = line.trim();
+        let node = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => {
+                    properties.style.clone()
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(node("C").and_then(|s| s.color).as_deref(), Some("red"));
+        assert_eq!(node("H").and_then(|s| s.shape).as_deref(), Some("box"));
+    }
 
-        // Parse subgraph start
-        if trimmed.starts_with("subgraph") {
-            if let Some(cluster_start) = trimmed.find("cluster_") {
-                let cluster_name = trimmed[cluster_start..]
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or("");
+    #[test]
+    fn test_rank_same_group_type() {
+        let dot = "digraph {\n    A -> B;\n    A -> C;\n    { rank=same; B; C; }\n}\n";
+        let rank_group = |options: &ParseOptions| {
+            parse_dot_to_events_with_options(dot, options)
+                .into_iter()
+                .find_map(|e| match e {
+                    GraphEvent::AddGroup {
+                        members,
+                        group_type,
+                        ..
+                    } => Some((members, group_type)),
+                    _ => None,
+                })
+        };
+
+        let members = vec!["B".to_string(), "C".to_string()];
+        assert_eq!(
+            rank_group(&ParseOptions::default()),
+            Some((members.clone(), GroupType::Custom("rank_same".to_string())))
+        );
+        let options = ParseOptions {
+            rank_same_as_parallel: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(rank_group(&options), Some((members, GroupType::Parallel)));
+    }
 
-                // Find label in subsequent lines
-                stack.push((cluster_name.to_string(), None));
+    #[test]
+    fn test_colon_separated_attributes() {
+        let dot = r#"
+            digraph {
+                A [label:"Start: here", shape:box];
+                B [color=red; fontname:Helvetica];
+                A:out -> B [label:"go", style:dashed];
             }
-        }
-        // Parse labels
-        else if (trimmed.starts_with("label=") || trimmed.starts_with("Label="))
-            && !stack.is_empty()
-        {
-            let label = extract_label_value(trimmed);
+        "#;
 
-            // Determine node type based on label content
-            let node_type = if label.to_lowercase().contains("tenant")
-                || label.to_lowercase().contains("organization")
-            {
-                NodeType::Custom("organization".to_string())
-            } else if label.to_lowercase().contains("contact center") {
-                NodeType::Custom("line_of_business".to_string())
-            } else if label.to_lowercase().contains("site") {
-                NodeType::Custom("site".to_string())
-            } else {
-                NodeType::Node
-            };
+        let options = ParseOptions {
+            colon_attributes: true,
+            ..Default::default()
+        };
+        let events = parse_dot_to_events_with_options(dot, &options);
 
-            let level = stack.len() as u32 - 1;
-            let properties = Properties {
-                position: Some(Position::Layer { level }),
-                ..Default::default()
-            };
+        let node = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    properties,
+                    ..
+                } if id == node_id => Some((label.clone(), properties.style.clone())),
+                _ => None,
+            })
+        };
+        let (label, style) = node("A").unwrap();
+        assert_eq!(label.as_deref(), Some("Start: here"));
+        assert_eq!(style.and_then(|s| s.shape).as_deref(), Some("box"));
+        let (_, style) = node("B").unwrap();
+        let style = style.unwrap();
+        assert_eq!(style.color.as_deref(), Some("red"));
+        assert_eq!(style.font_family.as_deref(), Some("Helvetica"));
 
-            // Create node for this cluster
-            let node_id = label.clone();
-            events.push(GraphEvent::AddNode {
-                id: node_id.clone(),
-                label: Some(label),
-                node_type,
+        let edge = events.iter().find_map(|e| match e {
+            GraphEvent::AddEdge {
+                from,
+                to,
+                label,
                 properties,
-            });
+                ..
+            } => Some((
+                from.as_str(),
+                to.as_str(),
+                label.as_deref(),
+                properties.custom.get("from_port").map(String::as_str),
+                properties.custom.get("style").map(String::as_str),
+            )),
+            _ => None,
+        });
+        assert_eq!(
+            edge,
+            Some(("A", "B", Some("go"), Some("out"), Some("dashed")))
+        );
 
-            // Connect to parent if exists
-            if stack.len() > 1 {
-                if let Some((_, Some(parent_id))) = stack.iter().rev().nth(1) {
-                    events.push(GraphEvent::AddEdge {
-                        id: format!("{parent_id}->{node_id}"),
-                        from: parent_id.clone(),
-                        to: node_id.clone(),
-                        edge_type: EdgeType::Directed,
-                        label: None,
-                        properties: Properties::default(),
-                    });
-                }
-            }
+        // Without the option colon entries are not attributes
+        let events = parse_dot_to_events(dot);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddNode { id, label: Some(label), .. } if id == "A" && label == "A"
+        )));
+    }
 
-            // Update stack with node ID
-            if let Some((cluster, _)) = stack.last_mut() {
-                *stack.last_mut().unwrap() = (cluster.clone(), Some(node_id));
+    #[test]
+    fn test_source_order() {
+        let dot = r#"
+            digraph {
+                A [label="Start"];
+                A -> B;
+                C [shape=box];
+                B [color=red];
+                C -> A;
             }
-        }
-        // Parse standalone nodes
-        else if trimmed.contains('[') && trimmed.contains("label=") && !trimmed.contains("->") {
-            if let Some(node_end) = trimmed.find('[') {
-                let node_id = trimmed[..node_end].trim().trim_matches('"');
-                let label = extract_node_label(trimmed).unwrap_or_else(|| node_id.to_string());
-
-                let level = stack.len() as u32;
-                let node_type = if label.to_lowercase().contains("supervisor") {
-                    NodeType::Custom("team".to_string())
-                } else {
-                    NodeType::Custom("user".to_string())
-                };
-
-                let properties = Properties {
-                    position: Some(Position::Layer { level }),
-                    ..Default::default()
-                };
+        "#;
+        let options = ParseOptions {
+            source_order: true,
+            ..ParseOptions::default()
+        };
+        let events = parse_dot_to_events_with_options(dot, &options);
 
-                events.push(GraphEvent::AddNode {
-                    id: label.clone(),
-                    label: Some(label.clone()),
-                    node_type,
-                    properties,
-                });
+        let order: Vec<String> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(format!("add {id}")),
+                GraphEvent::UpdateNode { id, .. } => Some(format!("update {id}")),
+                GraphEvent::AddEdge { id, .. } => Some(format!("edge {id}")),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "add A",
+                "add B",
+                "edge A->B",
+                "add C",
+                "update B",
+                "edge C->A"
+            ]
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::UpdateNode { id, properties, .. }
+                if id == "B"
+                    && properties.style.as_ref().and_then(|s| s.color.as_deref()) == Some("red")
+        )));
+    }
 
-                // Connect to parent if exists
-                if let Some((_, Some(parent_id))) = stack.last() {
-                    events.push(GraphEvent::AddEdge {
-                        id: format!("{parent_id}->{label}"),
-                        from: parent_id.clone(),
-                        to: label,
-                        edge_type: EdgeType::Directed,
-                        label: None,
-                        properties: Properties::default(),
-                    });
+    #[test]
+    fn test_parse_all_graphs() {
+        let dot = r#"
+            digraph first {
+                A -> B;
+            }
+            // the second graph
+            digraph second {
+                subgraph cluster_x {
+                    label="}";
+                    C -> D;
                 }
             }
-        }
-        // Handle closing braces
-        else if trimmed == "}" && !stack.is_empty() {
-            stack.pop();
-        }
+        "#;
+
+        let graphs = parse_all_dot_to_events(dot);
+        assert_eq!(graphs.len(), 2);
+
+        let summary = |events: &[GraphEvent]| -> (Option<String>, Vec<String>) {
+            let name = events.iter().find_map(|e| match e {
+                GraphEvent::SetGraphMeta { name, .. } => name.clone(),
+                _ => None,
+            });
+            let edges = events
+                .iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge { id, .. } => Some(id.clone()),
+                    _ => None,
+                })
+                .collect();
+            (name, edges)
+        };
+        assert_eq!(
+            summary(&graphs[0]),
+            (Some("first".to_string()), vec!["A->B".to_string()])
+        );
+        assert_eq!(
+            summary(&graphs[1]),
+            (Some("second".to_string()), vec!["C->D".to_string()])
+        );
     }
-}
 
-fn extract_label_value(line: &str) -> String {
-    let label_start = line.find('=').unwrap_or(0) + 1;
-    let mut label = line[label_start..]
-        .trim()
-        .trim_matches('"')
-        .trim_matches(';')
-        .to_string();
+    #[test]
+    fn test_graph_meta() {
+        let meta = |dot: &str| {
+            parse_dot_to_events(dot).into_iter().find_map(|e| match e {
+                GraphEvent::SetGraphMeta {
+                    name,
+                    directed,
+                    strict,
+                } => Some((name, directed, strict)),
+                _ => None,
+            })
+        };
 
-    // Extract meaningful name from label (after the colon if present)
-    if let Some(colon_pos) = label.find(':') {
-        label = label[colon_pos + 1..].trim().to_string();
+        assert_eq!(
+            meta("digraph OrgChart {\n    A -> B;\n}\n"),
+            Some((Some("OrgChart".to_string()), true, false))
+        );
+        assert_eq!(
+            meta("strict graph \"my net\" {\n    A -- B;\n}\n"),
+            Some((Some("my net".to_string()), false, true))
+        );
+        assert_eq!(meta("digraph {\n    A;\n}\n"), Some((None, true, false)));
+        assert_eq!(
+            meta("STRICT DiGraph G {\n    A -> B;\n}\n"),
+            Some((Some("G".to_string()), true, true))
+        );
     }
 
-    label
-}
+    #[test]
+    fn test_strict_graphs_drop_parallel_edges() {
+        let edge_pairs = |dot: &str| -> Vec<(String, String)> {
+            parse_dot_to_events(dot)
+                .into_iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge { from, to, .. } => Some((from, to)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let pair = |from: &str, to: &str| (from.to_string(), to.to_string());
 
-fn extract_node_label(line: &str) -> Option<String> {
-    line.find("label=").and_then(|label_start| {
-        let label_part = &line[label_start + 6..];
-        label_part.find('"').and_then(|first_quote| {
-            label_part[first_quote + 1..].find('"').map(|second_quote| {
-                label_part[first_quote + 1..first_quote + 1 + second_quote]
-                    .replace("\\n", " ")
-                    .trim()
-                    .to_string()
-            })
-        })
-    })
-}
+        let directed = "strict digraph {\n    A -> B;\n    A -> B;\n    B -> A;\n}\n";
+        assert_eq!(edge_pairs(directed), vec![pair("A", "B"), pair("B", "A")]);
 
-// ============================================================================
-// Legacy API - Deprecated
-// ============================================================================
+        let undirected = "strict graph {\n    A -- B;\n    B -- A;\n}\n";
+        assert_eq!(edge_pairs(undirected), vec![pair("A", "B")]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let loose = "digraph {\n    A -> B;\n    A -> B;\n}\n";
+        assert_eq!(edge_pairs(loose).len(), 2);
+
+        // Strictness is graph metadata, not a layout hint
+        let events = parse_dot_to_events(directed);
+        assert!(matches!(
+            &events[1],
+            GraphEvent::SetGraphMeta { strict: true, .. }
+        ));
+        assert!(
+            !events
+                .iter()
+                .any(|e| matches!(e, GraphEvent::SetLayout { .. }))
+        );
+    }
 
     #[test]
-    fn test_parse_simple_graph_to_events() {
-        let dot = r"
+    fn test_message_sequence_numbering() {
+        let dot = "digraph {\n    A -> B -> C;\n    C -> A;\n}\n";
+        let sequences = |base: u32| -> Vec<u32> {
+            let options = ParseOptions {
+                message_sequence_base: Some(base),
+                ..Default::default()
+            };
+            parse_dot_to_events_with_options(dot, &options)
+                .into_iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge {
+                        edge_type:
+                            EdgeType::Message {
+                                message_type: MessageType::Synchronous,
+                                sequence,
+                            },
+                        ..
+                    } => sequence,
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(sequences(1), vec![1, 2, 3]);
+        assert_eq!(
+            sequences(u32::MAX - 1),
+            vec![u32::MAX - 1, u32::MAX, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn test_edge_ports() {
+        let dot = r#"
             digraph {
-                A -> B;
-                B -> C;
+                struct1:f0 -> struct2:f1:w;
+                struct1:f1 -> "a:b";
             }
-        ";
+        "#;
 
         let events = parse_dot_to_events(dot);
 
-        // Should have: BatchStart, 3 nodes, 2 edges, BatchEnd
-        assert!(events.len() >= 6);
-        assert!(matches!(events.first(), Some(GraphEvent::BatchStart)));
-        assert!(matches!(events.last(), Some(GraphEvent::BatchEnd)));
-
-        // Count node and edge events
-        let node_count = events
-            .iter()
-            .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
-            .count();
-        let edge_count = events
+        let edges: Vec<_> = events
             .iter()
-            .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
-            .count();
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    from,
+                    to,
+                    properties,
+                    ..
+                } => Some((
+                    from.as_str(),
+                    to.as_str(),
+                    properties.custom.get("from_port").map(String::as_str),
+                    properties.custom.get("to_port").map(String::as_str),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("struct1", "struct2", Some("f0"), Some("f1:w")),
+                ("struct1", "a:b", Some("f1"), None),
+            ]
+        );
 
-        assert_eq!(node_count, 3);
-        assert_eq!(edge_count, 2);
+        let nodes: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nodes, vec!["struct1", "struct2", "a:b"]);
     }
 
     #[test]
-    fn test_parse_node_with_attributes_to_events() {
+    fn test_escaped_quotes_in_ids_and_labels() {
         let dot = r#"
             digraph {
-                "Node1" [type="team", level="2", label="Team Alpha"];
-                "Node2" [type="user", level="1"];
-                "Node1" -> "Node2";
+                "say \"hi\"" [label="She said \"hi\"\nthen left"];
+                "say \"hi\"" -> B;
             }
         "#;
 
         let events = parse_dot_to_events(dot);
 
-        // Find the Node1 event
-        let node1_event = events
+        let nodes: Vec<(&str, Option<&str>)> = events
             .iter()
-            .find(|e| matches!(e, GraphEvent::AddNode { id, .. } if id == "Node1"));
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, label, .. } => Some((id.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("say \"hi\"", Some("She said \"hi\"\nthen left")),
+                ("B", Some("B")),
+            ]
+        );
+        assert!(find_edge(&events, "say \"hi\"->B").is_some());
+    }
 
-        assert!(node1_event.is_some());
+    #[test]
+    fn test_html_labels() {
+        let dot = r#"
+            digraph {
+                title [label=<<b>Title</b> <i>a &lt; b</i>>];
+                plain [label="<b>not html</b>"];
+            }
+        "#;
 
-        if let Some(GraphEvent::AddNode {
-            label,
-            node_type,
-            properties,
-            ..
-        }) = node1_event
-        {
-            assert_eq!(label.as_deref(), Some("Team Alpha"));
-            assert!(matches!(node_type, NodeType::Custom(t) if t == "team"));
-            assert!(matches!(
-                properties.position,
-                Some(Position::Layer { level: 2 })
-            ));
-        }
+        let nodes: Vec<_> = parse_dot_to_events(dot)
+            .into_iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    label, properties, ..
+                } => Some((label, properties.custom.get("label_format").cloned())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (
+                    Some("<b>Title</b> <i>a &lt; b</i>".to_string()),
+                    Some("html".to_string())
+                ),
+                (Some("<b>not html</b>".to_string()), None),
+            ]
+        );
     }
 
     #[test]
-    fn test_layout_detection() {
+    fn test_plaintext_table_label() {
         let dot = r"
             digraph {
-                rankdir=LR;
-                A -> B;
+                matrix [shape=plaintext, label=<
+                    <table>
+                        <tr><td>a</td><td>b</td></tr>
+                        <tr><td>c</td><td><b>d</b></td></tr>
+                    </table>
+                >];
             }
         ";
 
         let events = parse_dot_to_events(dot);
 
-        // Should have a SetLayout event
-        let layout_event = events
+        let (label, properties) = events
             .iter()
-            .find(|e| matches!(e, GraphEvent::SetLayout { .. }));
-        assert!(layout_event.is_some());
-
-        if let Some(GraphEvent::SetLayout { layout_type, .. }) = layout_event {
-            assert!(matches!(
-                layout_type,
-                LayoutType::Hierarchical {
-                    direction: Direction::LeftToRight
-                }
-            ));
-        }
+            .find_map(|e| match e {
+                GraphEvent::AddNode {
+                    label, properties, ..
+                } => Some((label.as_deref(), properties)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(label, Some("a | b\nc | d"));
+        assert_eq!(
+            properties.custom.get("table").map(String::as_str),
+            Some(r#"[["a","b"],["c","d"]]"#)
+        );
+        let markup = &properties.custom["markup"];
+        assert!(markup.trim().starts_with("<table>"), "{markup}");
+        assert!(markup.contains("<td><b>d</b></td>"), "{markup}");
     }
 }
@@ -0,0 +1,180 @@
+use super::clusters::mentioned_nodes;
+use crate::events::{GraphEvent, GroupType, Properties};
+
+/// One `{ ... }` scope, collecting the nodes mentioned inside it
+#[derive(Default)]
+struct Scope {
+    rank_same: bool,
+    members: Vec<String>,
+}
+
+/// Emit an `AddGroup` for every `{ rank=same; ... }` block
+///
+/// Members are the nodes mentioned anywhere in the block, including nested
+/// braces, for which `is_node` holds. Groups are numbered `rank-N` in the
+/// order their blocks close.
+pub fn rank_groups(
+    lines: &[&str],
+    arrow: &str,
+    is_node: impl Fn(&str) -> bool,
+    group_type: &GroupType,
+    events: &mut Vec<GraphEvent>,
+) {
+    let mut stack: Vec<Scope> = Vec::new();
+    let mut count = 0;
+
+    for line in lines {
+        for token in tokens(line) {
+            match token {
+                Token::Open => stack.push(Scope::default()),
+                Token::Close => {
+                    let Some(scope) = stack.pop() else {
+                        continue;
+                    };
+                    if scope.rank_same && !scope.members.is_empty() {
+                        let mut properties = Properties::default();
+                        properties
+                            .custom
+                            .insert("rank".to_string(), "same".to_string());
+                        events.push(GraphEvent::AddGroup {
+                            id: format!("rank-{count}"),
+                            label: None,
+                            members: scope.members,
+                            group_type: group_type.clone(),
+                            properties,
+                        });
+                        count += 1;
+                    } else if let Some(parent) = stack.last_mut() {
+                        extend_unique(&mut parent.members, scope.members);
+                    }
+                }
+                Token::Statement(statement) => {
+                    let Some(scope) = stack.last_mut() else {
+                        continue;
+                    };
+                    if is_rank_same(statement) {
+                        scope.rank_same = true;
+                    } else if !statement.starts_with("subgraph") {
+                        let nodes = mentioned_nodes(statement, arrow)
+                            .into_iter()
+                            .filter(|id| is_node(id))
+                            .collect();
+                        extend_unique(&mut scope.members, nodes);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn extend_unique(members: &mut Vec<String>, nodes: Vec<String>) {
+    for node in nodes {
+        if !members.contains(&node) {
+            members.push(node);
+        }
+    }
+}
+
+/// Whether a statement is `rank=same`, bare or as `graph [rank=same]`
+fn is_rank_same(statement: &str) -> bool {
+    let compact: String = statement
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '"')
+        .collect();
+    compact == "rank=same" || compact == "graph[rank=same]"
+}
+
+enum Token<'a> {
+    Open,
+    Close,
+    Statement(&'a str),
+}
+
+/// Split a line at unquoted braces and semicolons outside attribute lists
+fn tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut bracket_depth = 0usize;
+
+    for (i, c) in line.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            '[' => bracket_depth += 1,
+            ']' => bracket_depth = bracket_depth.saturating_sub(1),
+            '{' | '}' | ';' if bracket_depth == 0 => {
+                push_statement(&mut tokens, &line[start..i]);
+                match c {
+                    '{' => tokens.push(Token::Open),
+                    '}' => tokens.push(Token::Close),
+                    _ => {}
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    push_statement(&mut tokens, &line[start..]);
+
+    tokens
+}
+
+fn push_statement<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        tokens.push(Token::Statement(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_same_blocks() {
+        let lines = [
+            "digraph {",
+            "A -> B -> C;",
+            "{ rank=same; B; C }",
+            "subgraph bottom {",
+            "rank = \"same\";",
+            "D -> E [label=\"x; y\"];",
+            "}",
+            "{ A; }",
+            "}",
+        ];
+        let mut events = Vec::new();
+        let nodes = ["A", "B", "C", "D", "E"];
+        rank_groups(
+            &lines,
+            "->",
+            |id| nodes.contains(&id),
+            &GroupType::Parallel,
+            &mut events,
+        );
+
+        let groups: Vec<(&str, Vec<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddGroup { id, members, .. } => {
+                    Some((id.as_str(), members.iter().map(String::as_str).collect()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("rank-0", vec!["B", "C"]), ("rank-1", vec!["D", "E"])]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use super::attributes::parse_attributes;
+
+/// Attribute defaults from `node [...]` and `edge [...]` statements
+#[derive(Debug, Clone, Default)]
+struct Defaults {
+    node: Vec<(String, String)>,
+    edge: Vec<(String, String)>,
+}
+
+/// Tracks default attributes through nested `{ ... }` scopes
+///
+/// Every brace opens a scope, whether it belongs to the graph, a
+/// `subgraph`, or an anonymous block; defaults set inside a scope are
+/// dropped when it closes. An empty statement such as `edge []` clears the
+/// defaults for that element kind in the current scope.
+#[derive(Debug)]
+pub struct DefaultScopes {
+    stack: Vec<Defaults>,
+}
+
+impl Default for DefaultScopes {
+    fn default() -> Self {
+        Self {
+            stack: vec![Defaults::default()],
+        }
+    }
+}
+
+impl DefaultScopes {
+    /// Update the scopes for one line, returning whether it was a default
+    /// statement rather than a node or edge declaration
+    pub fn observe(&mut self, line: &str) -> bool {
+        let trimmed = line.trim();
+
+        let mut is_default = false;
+        for (keyword, kind) in [("node", Kind::Node), ("edge", Kind::Edge)] {
+            if let Some(attrs_str) = default_attrs(trimmed, keyword) {
+                self.set(kind, parse_attributes(attrs_str));
+                is_default = true;
+            }
+        }
+
+        for c in unquoted_chars(trimmed) {
+            match c {
+                '{' => {
+                    let inherited = self.current().clone();
+                    self.stack.push(inherited);
+                }
+                '}' if self.stack.len() > 1 => {
+                    self.stack.pop();
+                }
+                _ => {}
+            }
+        }
+
+        is_default
+    }
+
+    /// Defaults for nodes declared at the current position
+    pub fn node_defaults(&self) -> &[(String, String)] {
+        &self.current().node
+    }
+
+    /// Defaults for edges declared at the current position
+    pub fn edge_defaults(&self) -> &[(String, String)] {
+        &self.current().edge
+    }
+
+    fn current(&self) -> &Defaults {
+        self.stack.last().expect("root scope is never popped")
+    }
+
+    fn set(&mut self, kind: Kind, attrs: Vec<(String, String)>) {
+        let Some(scope) = self.stack.last_mut() else {
+            return;
+        };
+        let defaults = match kind {
+            Kind::Node => &mut scope.node,
+            Kind::Edge => &mut scope.edge,
+        };
+        if attrs.is_empty() {
+            defaults.clear();
+        }
+        for (key, value) in attrs {
+            defaults.retain(|(existing, _)| *existing != key);
+            defaults.push((key, value));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Node,
+    Edge,
+}
+
+/// The attribute list of a `keyword [...]` default statement
+fn default_attrs<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    line.strip_prefix(keyword)
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix('['))
+        .and_then(|rest| rest.rsplit_once(']'))
+        .map(|(attrs, _)| attrs)
+}
+
+/// Characters of a line that are outside double-quoted strings
+pub(super) fn unquoted_chars(line: &str) -> impl Iterator<Item = char> + '_ {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    line.chars().filter(move |&c| {
+        if escaped {
+            escaped = false;
+            return false;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            _ => return !in_quotes,
+        }
+        false
+    })
+}
+
+/// Byte offset of the first `needle` outside quoted strings
+pub(super) fn unquoted_find(text: &str, needle: &str) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (offset, c) in text.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_quotes = true;
+        } else if text[offset..].starts_with(needle) {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// A statement split at the `[` opening its attribute list, skipping any `[`
+/// inside a quoted id such as `"a[1]"`
+pub(super) fn split_attribute_list(statement: &str) -> Option<(&str, &str)> {
+    unquoted_find(statement, "[").map(|offset| (&statement[..offset], &statement[offset + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_scoped_to_braces() {
+        let mut scopes = DefaultScopes::default();
+        scopes.observe("digraph {");
+        scopes.observe("node [shape=box];");
+        scopes.observe("{");
+        assert!(scopes.observe("node [color=red, shape=circle];"));
+        assert_eq!(
+            scopes.node_defaults(),
+            [
+                ("color".to_string(), "red".to_string()),
+                ("shape".to_string(), "circle".to_string()),
+            ]
+        );
+        scopes.observe("}");
+        assert_eq!(
+            scopes.node_defaults(),
+            [("shape".to_string(), "box".to_string())]
+        );
+        assert!(!scopes.observe("A [label=\"{not a scope\"];"));
+        assert!(scopes.edge_defaults().is_empty());
+    }
+}
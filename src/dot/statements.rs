@@ -0,0 +1,329 @@
+/// Join the physical lines of a DOT file into logical statements
+///
+/// A line break inside `[...]` or after a dangling edge operator continues
+/// the statement, so `A [\n  label="x",\n  color=red\n];` becomes one line.
+/// A `;` outside quotes, `[...]`, and `<...>` ends a statement, keeping the
+/// `;`, so `a -> b; c -> d;` on one line is two statements. Block braces get
+/// statements of their own, so `digraph { a -> b }` is `digraph {`, `a -> b`,
+/// and `}`, while node sets such as `a -> {b c}` stay whole. Newlines inside
+/// quoted strings are kept as part of the statement. Comments should already
+/// be removed with [`strip_comments`].
+pub fn split_statements(content: &str) -> Vec<String> {
+    located_statements(content)
+        .into_iter()
+        .map(|(_, statement)| statement)
+        .collect()
+}
+
+/// [`split_statements`], pairing each statement with the byte offset in
+/// `content` of its first non-blank character
+pub fn located_statements(content: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    // Offset of the first non-blank character of `current`
+    let mut start: Option<usize> = None;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut bracket_depth = 0usize;
+    let mut angle_depth = 0usize;
+    // Whether a `;` ended a statement earlier on the current line
+    let mut terminated = false;
+    // Open braces, `true` for a node set like `{b c}` in `a -> {b c}`
+    let mut braces: Vec<bool> = Vec::new();
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        if start.is_none() && !c.is_whitespace() {
+            start = Some(offset);
+        }
+        if in_quotes {
+            current.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '[' | ']' | '<' | '>' => {
+                current.push(c);
+                match c {
+                    '"' => in_quotes = true,
+                    '[' => bracket_depth += 1,
+                    ']' => bracket_depth = bracket_depth.saturating_sub(1),
+                    '<' => angle_depth += 1,
+                    _ => angle_depth = angle_depth.saturating_sub(1),
+                }
+            }
+            '{' if bracket_depth == 0 && angle_depth == 0 => {
+                let trimmed = current.trim_end();
+                let set = trimmed.ends_with("->")
+                    || trimmed.ends_with("--")
+                    || (trimmed.is_empty() && precedes_edge(chars.clone().map(|(_, c)| c)));
+                braces.push(set);
+                current.push(c);
+                if !set {
+                    statements.push((start.take().unwrap_or(offset), std::mem::take(&mut current)));
+                    terminated = true;
+                }
+            }
+            '}' if bracket_depth == 0 && angle_depth == 0 => {
+                if braces.pop().unwrap_or(false) {
+                    current.push(c);
+                    continue;
+                }
+                if !current.trim().is_empty() {
+                    statements.push((start.take().unwrap_or(offset), std::mem::take(&mut current)));
+                    start = Some(offset);
+                }
+                current.push(c);
+                while chars
+                    .next_if(|&(_, next)| next == ' ' || next == '\t')
+                    .is_some()
+                {}
+                if chars.next_if(|&(_, next)| next == ';').is_some() {
+                    current.push(';');
+                }
+                statements.push((start.take().unwrap_or(offset), std::mem::take(&mut current)));
+                terminated = true;
+            }
+            ';' if bracket_depth == 0 && angle_depth == 0 => {
+                current.push(c);
+                statements.push((start.take().unwrap_or(offset), std::mem::take(&mut current)));
+                terminated = true;
+            }
+            '\n' => {
+                let trimmed = current.trim_end();
+                if bracket_depth > 0 || trimmed.ends_with("->") || trimmed.ends_with("--") {
+                    current.truncate(trimmed.len());
+                    current.push(' ');
+                    while chars
+                        .next_if(|&(_, next)| next == ' ' || next == '\t')
+                        .is_some()
+                    {}
+                } else if terminated && current.trim().is_empty() {
+                    current.clear();
+                    start = None;
+                } else {
+                    statements.push((start.take().unwrap_or(offset), std::mem::take(&mut current)));
+                }
+                terminated = false;
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        statements.push((start.unwrap_or(content.len()), current));
+    }
+
+    statements
+}
+
+/// Whether the text after a `{` closes it and then continues with an edge
+/// operator, as in `{a b} -> c`
+fn precedes_edge(mut chars: impl Iterator<Item = char>) -> bool {
+    let mut depth = 1usize;
+    let mut in_quotes = false;
+    for c in chars.by_ref() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '{' if !in_quotes => depth += 1,
+            '}' if !in_quotes => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let rest: String = chars.skip_while(|c| c.is_whitespace()).take(2).collect();
+    rest == "->" || rest == "--"
+}
+
+/// Split a file holding several graphs into one slice per top-level
+/// `graph`/`digraph` block
+///
+/// A block ends at the `}` that balances its first `{`; braces inside quoted
+/// strings are ignored. Text after the last block is dropped unless it holds
+/// more than whitespace. Comments should already be removed with
+/// [`strip_comments`].
+pub fn split_graphs(content: &str) -> Vec<&str> {
+    let mut graphs = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in content.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            '{' => depth += 1,
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    graphs.push(content[start..=i].trim());
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if !content[start..].trim().is_empty() {
+        graphs.push(content[start..].trim());
+    }
+
+    graphs
+}
+
+/// Remove `//` and `/* ... */` comments and `#` preprocessor lines
+///
+/// Comment markers inside quoted strings are left alone. Each commented-out
+/// character becomes a space and line breaks are kept, so lines and columns
+/// in the result match the original source.
+pub fn strip_comments(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut at_line_start = true;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            result.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '#' if at_line_start => {
+                result.push(' ');
+                while chars.next_if(|&next| next != '\n').is_some() {
+                    result.push(' ');
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                result.push(' ');
+                while chars.next_if(|&next| next != '\n').is_some() {
+                    result.push(' ');
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                result.push_str("  ");
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    result.push(if next == '\n' { '\n' } else { ' ' });
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            '"' => {
+                in_quotes = true;
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+
+        if c == '\n' {
+            at_line_start = true;
+        } else if !c.is_whitespace() {
+            at_line_start = false;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_attribute_list_is_one_statement() {
+        let statements =
+            split_statements("digraph {\n  A [\n    label=\"x\",\n    color=red\n  ];\n}\n");
+
+        let trimmed: Vec<&str> = statements.iter().map(|s| s.trim()).collect();
+        assert_eq!(
+            trimmed,
+            vec!["digraph {", "A [ label=\"x\", color=red ];", "}"]
+        );
+    }
+
+    #[test]
+    fn test_several_statements_on_one_line() {
+        let statements = split_statements(
+            "digraph { a -> b; c -> d [label=\"x; y\"]; e [label=<<b>;</b>>]; }\n",
+        );
+
+        let trimmed: Vec<&str> = statements.iter().map(|s| s.trim()).collect();
+        assert_eq!(
+            trimmed,
+            vec![
+                "digraph {",
+                "a -> b;",
+                "c -> d [label=\"x; y\"];",
+                "e [label=<<b>;</b>>];",
+                "}"
+            ]
+        );
+
+        let statements = split_statements("subgraph s { {x y} -> z; a -> {b c} }\n");
+        let trimmed: Vec<&str> = statements.iter().map(|s| s.trim()).collect();
+        assert_eq!(
+            trimmed,
+            vec!["subgraph s {", "{x y} -> z;", "a -> {b c}", "}"]
+        );
+    }
+
+    #[test]
+    fn test_quoted_newlines_and_comments() {
+        let content =
+            strip_comments("A [label=\"two\nlines // kept\"]; // dropped\nB -> // wrap\n  C;\n");
+        let statements = split_statements(&content);
+
+        assert_eq!(statements[0].trim(), "A [label=\"two\nlines // kept\"];");
+        assert_eq!(statements[1].trim(), "B -> C;");
+    }
+
+    #[test]
+    fn test_block_comments_and_preprocessor_lines() {
+        let content = strip_comments(
+            "#include \"common.gv\"\ndigraph {\n    /* disabled:\n       A -> B; */\n    C [label=\"/* kept */\", color=\"#fff\"];\n}\n",
+        );
+
+        let statements: Vec<String> = split_statements(&content)
+            .into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        assert_eq!(
+            statements,
+            vec![
+                "digraph {",
+                "C [label=\"/* kept */\", color=\"#fff\"];",
+                "}"
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::scope::unquoted_find;
+use std::fmt;
+
+/// A structural problem found by [`parse_dot_checked`](super::parser::parse_dot_checked)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotParseError {
+    /// 1-based line of the offending character
+    pub line: usize,
+    /// 1-based column of the offending character
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DotParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for DotParseError {}
+
+/// A non-fatal issue reported by
+/// [`parse_dot_checked_with_warnings`](super::parser::parse_dot_checked_with_warnings)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotParseWarning {
+    /// 1-based line of the attribute the warning is about
+    pub line: usize,
+    /// 1-based column of the attribute the warning is about
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DotParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// Line and column of a character
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    fn error(self, message: impl Into<String>) -> DotParseError {
+        DotParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+/// Check comment-free DOT source for unbalanced braces and brackets,
+/// unterminated strings, and edge operators that don't match `directed`
+///
+/// `directed` is `None` when the graph kind is unknown, which skips the
+/// edge operator check.
+pub fn validate(content: &str, directed: Option<bool>) -> Result<(), DotParseError> {
+    let mut braces: Vec<Location> = Vec::new();
+    let mut bracket: Option<Location> = None;
+    let mut quote: Option<Location> = None;
+    let mut escaped = false;
+    let mut html_depth = 0usize;
+    let mut location = Location { line: 1, column: 0 };
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            location.line += 1;
+            location.column = 0;
+        } else {
+            location.column += 1;
+        }
+
+        if quote.is_some() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+        if html_depth > 0 {
+            match c {
+                '<' => html_depth += 1,
+                '>' => html_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => quote = Some(location),
+            '<' if bracket.is_some() => html_depth = 1,
+            '[' if bracket.is_none() => bracket = Some(location),
+            ']' if bracket.is_some() => bracket = None,
+            ']' => return Err(location.error("unexpected ] without a matching [")),
+            '{' | '}' if bracket.is_some() => {
+                let open = bracket.unwrap_or(location);
+                return Err(open.error("missing ] in attribute list"));
+            }
+            '{' => braces.push(location),
+            '}' if braces.pop().is_none() => {
+                return Err(location.error("unexpected } without a matching {"));
+            }
+            '-' if bracket.is_none() => {
+                let operator = match chars.peek() {
+                    Some('>') => "->",
+                    Some('-') => "--",
+                    _ => continue,
+                };
+                match (directed, operator) {
+                    (Some(false), "->") => {
+                        return Err(location.error("directed edge `->` in an undirected graph"));
+                    }
+                    (Some(true), "--") => {
+                        return Err(location.error("undirected edge `--` in a digraph"));
+                    }
+                    _ => {}
+                }
+                chars.next();
+                location.column += 1;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(open) = quote {
+        return Err(open.error("unterminated quoted string"));
+    }
+    if let Some(open) = bracket {
+        return Err(open.error("missing ] in attribute list"));
+    }
+    if let Some(open) = braces.pop() {
+        return Err(open.error("unclosed { without a matching }"));
+    }
+    Ok(())
+}
+
+/// A warning for a declared `charset` other than UTF-8
+///
+/// `statement` is the byte offset in `content` of the statement that set
+/// the charset; the warning points at the `charset` key within it.
+pub fn charset_warning(content: &str, statement: usize, charset: &str) -> Option<DotParseWarning> {
+    let normalized = charset.to_ascii_lowercase().replace(['-', '_'], "");
+    if normalized == "utf8" {
+        return None;
+    }
+    let offset = statement + unquoted_find(&content[statement..], "charset").unwrap_or(0);
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    Some(DotParseWarning {
+        line,
+        column,
+        message: format!("charset \"{charset}\" ignored: the input was already decoded as UTF-8"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(content: &str, directed: Option<bool>) -> (usize, usize, String) {
+        let error = validate(content, directed).unwrap_err();
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn test_valid_source() {
+        let content = "digraph {\n    A [label=\"{x}\", html=<<b>}</b>>];\n    A -> B;\n}\n";
+        assert_eq!(validate(content, Some(true)), Ok(()));
+    }
+
+    #[test]
+    fn test_structural_errors() {
+        assert_eq!(
+            error_at("digraph {\n    A -> B;\n", Some(true)),
+            (1, 9, "unclosed { without a matching }".to_string())
+        );
+        assert_eq!(
+            error_at("digraph {\n}\n}\n", Some(true)),
+            (3, 1, "unexpected } without a matching {".to_string())
+        );
+        assert_eq!(
+            error_at("digraph {\n    A [label=\"hi];\n}\n", Some(true)),
+            (2, 14, "unterminated quoted string".to_string())
+        );
+        assert_eq!(
+            error_at("digraph {\n    A [label=hi;\n}\n", Some(true)),
+            (2, 7, "missing ] in attribute list".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mismatched_edge_operators() {
+        assert_eq!(
+            error_at("digraph {\n    A -- B;\n}\n", Some(true)),
+            (2, 7, "undirected edge `--` in a digraph".to_string())
+        );
+        assert_eq!(
+            error_at("graph {\n    A -> B;\n}\n", Some(false)),
+            (
+                2,
+                7,
+                "directed edge `->` in an undirected graph".to_string()
+            )
+        );
+        assert_eq!(validate("graph {\n    A -- B;\n}\n", Some(false)), Ok(()));
+    }
+}
@@ -1,9 +1,18 @@
 #![allow(clippy::derive_partial_eq_without_eq)] // Can't derive Eq due to f32 fields
 
-use std::collections::HashMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
 
 /// Rich graph events that can represent any type of diagram
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event"))]
 pub enum GraphEvent {
     /// Add a node to the graph
     AddNode {
@@ -64,6 +73,14 @@ pub enum GraphEvent {
         properties: Properties,
     },
 
+    /// Describe the graph as a whole: its declared name, whether it is
+    /// directed, and whether it is `strict` (no parallel edges)
+    SetGraphMeta {
+        name: Option<String>,
+        directed: bool,
+        strict: bool,
+    },
+
     /// Clear the entire graph
     Clear,
 
@@ -76,6 +93,7 @@ pub enum GraphEvent {
 
 /// Types of nodes - generic enough for any diagram
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeType {
     /// Standard node (default)
     Node,
@@ -95,6 +113,7 @@ pub enum NodeType {
 
 /// State types for state machines
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StateType {
     Initial,
     Final,
@@ -105,6 +124,7 @@ pub enum StateType {
 
 /// Types of edges/connections
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgeType {
     /// Directed edge (default)
     Directed,
@@ -131,6 +151,7 @@ pub enum EdgeType {
 
 /// Message types for sequence diagrams
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageType {
     Synchronous,
     Asynchronous,
@@ -141,6 +162,7 @@ pub enum MessageType {
 
 /// Group types
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GroupType {
     /// Logical grouping
     Cluster,
@@ -156,6 +178,7 @@ pub enum GroupType {
 
 /// Layout hints for visualization
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LayoutType {
     /// Hierarchical layout (trees, org charts)
     Hierarchical { direction: Direction },
@@ -175,6 +198,7 @@ pub enum LayoutType {
 
 /// Direction for layouts
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     TopToBottom,
     BottomToTop,
@@ -182,8 +206,115 @@ pub enum Direction {
     RightToLeft,
 }
 
+/// How a layout resolves overlapping nodes, after Graphviz `overlap`
+///
+/// A hint passed through for renderers; no layout in this crate reads it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overlap {
+    /// Overlaps are left alone (`true`)
+    Retain,
+    /// Overlaps are removed (`false`)
+    Remove,
+    /// The layout is scaled up uniformly (`scale`)
+    Scale,
+    /// The layout is scaled separately along each axis (`scalexy`)
+    ScaleXy,
+    /// Any other removal technique, e.g. `prism` or `compress`
+    Other(String),
+}
+
+impl Overlap {
+    /// Read a Graphviz `overlap` value; booleans accept `yes`/`no` and numbers
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "scale" => Self::Scale,
+            "scalexy" => Self::ScaleXy,
+            other => match parse_bool(other) {
+                Some(true) => Self::Retain,
+                Some(false) => Self::Remove,
+                None => Self::Other(other.to_string()),
+            },
+        }
+    }
+
+    /// Canonical Graphviz spelling
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Retain => "true",
+            Self::Remove => "false",
+            Self::Scale => "scale",
+            Self::ScaleXy => "scalexy",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// How edges are routed, after Graphviz `splines`
+///
+/// A hint passed through for renderers; no layout in this crate reads it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Splines {
+    /// Edges are not drawn (`none` or empty)
+    Hidden,
+    /// Straight segments (`line` or `false`)
+    Line,
+    /// Polylines (`polyline`)
+    Polyline,
+    /// Curved arcs (`curved`)
+    Curved,
+    /// Axis-aligned segments (`ortho`)
+    Ortho,
+    /// Smooth splines (`spline` or `true`)
+    Spline,
+    /// Any other routing style
+    Other(String),
+}
+
+impl Splines {
+    /// Read a Graphviz `splines` value; booleans accept `yes`/`no` and numbers
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Self::Hidden,
+            "line" => Self::Line,
+            "polyline" => Self::Polyline,
+            "curved" => Self::Curved,
+            "ortho" => Self::Ortho,
+            "spline" => Self::Spline,
+            other => match parse_bool(other) {
+                Some(true) => Self::Spline,
+                Some(false) => Self::Line,
+                None => Self::Other(other.to_string()),
+            },
+        }
+    }
+
+    /// Canonical Graphviz spelling
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Hidden => "none",
+            Self::Line => "line",
+            Self::Polyline => "polyline",
+            Self::Curved => "curved",
+            Self::Ortho => "ortho",
+            Self::Spline => "spline",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// Graphviz boolean: `true`/`yes`/`false`/`no`, or an integer that is true
+/// when non-zero
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        number => number.parse::<i64>().ok().map(|n| n != 0),
+    }
+}
+
 /// Generic properties that can be attached to any element
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Properties {
     /// Visual properties
     pub style: Option<Style>,
@@ -194,7 +325,8 @@ pub struct Properties {
 }
 
 /// Visual style properties
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Style {
     pub color: Option<String>,
     pub background_color: Option<String>,
@@ -210,6 +342,7 @@ pub struct Style {
 
 /// Position hints
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Position {
     /// Absolute coordinates
     Absolute { x: f32, y: f32, z: Option<f32> },
@@ -223,6 +356,10 @@ pub enum Position {
     /// Grid position
     Grid { row: u32, column: u32 },
     /// Sequential order
+    ///
+    /// In sequence diagrams a participant's order is its lifeline column,
+    /// while a note, divider, or delay's order is the index of the message
+    /// it precedes; tell them apart by `NodeType`.
     Sequential { order: u32 },
     /// Layer/level in hierarchy
     Layer { level: u32 },
@@ -244,6 +381,117 @@ pub enum EventResult {
     Invalid(String),
 }
 
+impl Properties {
+    /// Typed `overlap` layout hint stored under `custom["overlap"]`
+    pub fn overlap(&self) -> Option<Overlap> {
+        self.custom
+            .get("overlap")
+            .map(|value| Overlap::parse(value))
+    }
+
+    /// Typed `splines` layout hint stored under `custom["splines"]`
+    pub fn splines(&self) -> Option<Splines> {
+        self.custom
+            .get("splines")
+            .map(|value| Splines::parse(value))
+    }
+
+    /// Flatten into a single string map for key-value consumers
+    ///
+    /// Style fields become `style.<field>` and position hints
+    /// `position.<field>` (e.g. `position.layer`, `position.order`); custom
+    /// entries keep their own keys.
+    pub fn to_flat_map(&self) -> HashMap<String, String> {
+        let mut flat = self.custom.clone();
+        if let Some(style) = &self.style {
+            let fields = [
+                ("color", style.color.clone()),
+                ("background_color", style.background_color.clone()),
+                ("border_style", style.border_style.clone()),
+                ("border_color", style.border_color.clone()),
+                ("border_width", style.border_width.map(|v| v.to_string())),
+                ("shape", style.shape.clone()),
+                ("size", style.size.map(|v| v.to_string())),
+                ("font_size", style.font_size.map(|v| v.to_string())),
+                ("font_family", style.font_family.clone()),
+                ("opacity", style.opacity.map(|v| v.to_string())),
+            ];
+            for (field, value) in fields {
+                if let Some(value) = value {
+                    flat.insert(format!("style.{field}"), value);
+                }
+            }
+        }
+        let position: Vec<(&str, String)> = match &self.position {
+            Some(Position::Absolute { x, y, z }) => {
+                let mut fields = vec![("x", x.to_string()), ("y", y.to_string())];
+                fields.extend(z.map(|z| ("z", z.to_string())));
+                fields
+            }
+            Some(Position::Relative {
+                anchor,
+                offset_x,
+                offset_y,
+                offset_z,
+            }) => {
+                let mut fields = vec![
+                    ("anchor", anchor.clone()),
+                    ("offset_x", offset_x.to_string()),
+                    ("offset_y", offset_y.to_string()),
+                ];
+                fields.extend(offset_z.map(|z| ("offset_z", z.to_string())));
+                fields
+            }
+            Some(Position::Grid { row, column }) => {
+                vec![("row", row.to_string()), ("column", column.to_string())]
+            }
+            Some(Position::Sequential { order }) => vec![("order", order.to_string())],
+            Some(Position::Layer { level }) => vec![("layer", level.to_string())],
+            None => Vec::new(),
+        };
+        for (field, value) in position {
+            flat.insert(format!("position.{field}"), value);
+        }
+        flat
+    }
+}
+
+impl Style {
+    /// Render the populated fields as an inline CSS declaration list for
+    /// SVG/HTML output, e.g. `fill: #fff; stroke: black; stroke-width: 2;`.
+    /// Fields that are `None` are skipped, so an empty style yields `""`.
+    pub fn to_css(&self) -> String {
+        let mut declarations: Vec<String> = Vec::new();
+        if let Some(fill) = &self.background_color {
+            declarations.push(format!("fill: {fill};"));
+        }
+        if let Some(stroke) = &self.border_color {
+            declarations.push(format!("stroke: {stroke};"));
+        }
+        if let Some(width) = self.border_width {
+            declarations.push(format!("stroke-width: {width};"));
+        }
+        match self.border_style.as_deref() {
+            Some("dashed") => declarations.push("stroke-dasharray: 5,5;".to_string()),
+            Some("dotted") => declarations.push("stroke-dasharray: 1,3;".to_string()),
+            _ => {}
+        }
+        if let Some(color) = &self.color {
+            declarations.push(format!("color: {color};"));
+        }
+        if let Some(family) = &self.font_family {
+            declarations.push(format!("font-family: {family};"));
+        }
+        if let Some(size) = self.font_size {
+            declarations.push(format!("font-size: {size}px;"));
+        }
+        if let Some(opacity) = self.opacity {
+            declarations.push(format!("opacity: {opacity};"));
+        }
+        declarations.join(" ")
+    }
+}
+
 impl GraphEvent {
     /// Create a simple node event
     pub fn simple_node(id: impl Into<String>, label: impl Into<String>) -> Self {
@@ -268,4 +516,951 @@ impl GraphEvent {
             properties: Properties::default(),
         }
     }
+
+    /// Create a simple cluster group event
+    pub fn simple_group(
+        id: impl Into<String>,
+        members: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self::AddGroup {
+            id: id.into(),
+            label: None,
+            members: members.into_iter().map(Into::into).collect(),
+            group_type: GroupType::Cluster,
+            properties: Properties::default(),
+        }
+    }
+
+    /// Create a layout event with default properties
+    pub fn layout(layout_type: LayoutType) -> Self {
+        Self::SetLayout {
+            layout_type,
+            properties: Properties::default(),
+        }
+    }
+}
+
+/// Parent/child relationships between groups in an event stream
+///
+/// Only `Container` groups nest: a group listed among a container's members
+/// is treated as its child. Other members are node ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupTree {
+    members: HashMap<String, Vec<String>>,
+    containers: HashSet<String>,
+}
+
+impl GroupTree {
+    /// Ids of all groups in the tree
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.members.keys().map(String::as_str)
+    }
+
+    /// The container group directly holding `group_id`, if any
+    pub fn parent(&self, group_id: &str) -> Option<&str> {
+        self.containers
+            .iter()
+            .find(|container| {
+                self.members
+                    .get(*container)
+                    .is_some_and(|members| members.iter().any(|m| m == group_id))
+            })
+            .map(String::as_str)
+    }
+
+    /// Groups directly nested inside `group_id`
+    pub fn children(&self, group_id: &str) -> Vec<&str> {
+        if !self.containers.contains(group_id) {
+            return Vec::new();
+        }
+        self.members
+            .get(group_id)
+            .into_iter()
+            .flatten()
+            .filter(|member| self.members.contains_key(*member))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Groups that have no parent container
+    pub fn roots(&self) -> Vec<&str> {
+        self.groups()
+            .filter(|group| self.parent(group).is_none())
+            .collect()
+    }
+
+    /// All node ids contained in `group_id` or any group nested inside it
+    pub fn descendants(&self, group_id: &str) -> Vec<String> {
+        let mut nodes = Vec::new();
+        let mut visited = HashSet::new();
+        self.collect_descendants(group_id, &mut nodes, &mut visited);
+        nodes
+    }
+
+    fn collect_descendants(
+        &self,
+        group_id: &str,
+        nodes: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+    ) {
+        if !visited.insert(group_id.to_string()) {
+            return;
+        }
+        let is_container = self.containers.contains(group_id);
+        for member in self.members.get(group_id).into_iter().flatten() {
+            if is_container && self.members.contains_key(member) {
+                self.collect_descendants(member, nodes, visited);
+            } else if !nodes.contains(member) {
+                nodes.push(member.clone());
+            }
+        }
+    }
+}
+
+/// Build the group hierarchy described by an event stream
+pub fn group_tree(events: &[GraphEvent]) -> GroupTree {
+    let mut tree = GroupTree::default();
+
+    for event in events {
+        match event {
+            GraphEvent::AddGroup {
+                id,
+                members,
+                group_type,
+                ..
+            } => {
+                tree.members.insert(id.clone(), members.clone());
+                if *group_type == GroupType::Container {
+                    tree.containers.insert(id.clone());
+                } else {
+                    tree.containers.remove(id);
+                }
+            }
+            GraphEvent::UpdateGroup { id, members } => {
+                if let Some(existing) = tree.members.get_mut(id) {
+                    existing.clone_from(members);
+                }
+            }
+            GraphEvent::RemoveGroup { id } => {
+                tree.members.remove(id);
+                tree.containers.remove(id);
+            }
+            GraphEvent::Clear => tree = GroupTree::default(),
+            _ => {}
+        }
+    }
+
+    tree
+}
+
+/// Node weight of the graph built by `to_typed_digraph`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichNode {
+    pub id: String,
+    pub label: Option<String>,
+    pub node_type: NodeType,
+}
+
+/// Edge weight of the graph built by `to_typed_digraph`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichEdge {
+    pub id: String,
+    pub label: Option<String>,
+    pub edge_type: EdgeType,
+}
+
+/// Fold an event stream into a `DiGraph` whose weights keep labels and types
+///
+/// Unlike the legacy `GraphData`, edges carry their label and `EdgeType`, so
+/// graph algorithms can see edge data. Removed elements are compacted away.
+#[cfg(feature = "std")]
+pub fn to_typed_digraph(events: &[GraphEvent]) -> petgraph::graph::DiGraph<RichNode, RichEdge> {
+    build_graph(events).map(
+        |_, node| RichNode {
+            id: node.id.clone(),
+            label: node.label.clone(),
+            node_type: node.node_type.clone(),
+        },
+        |_, edge| RichEdge {
+            id: edge.id.clone(),
+            label: edge.label.clone(),
+            edge_type: edge.edge_type.clone(),
+        },
+    )
+}
+
+/// Fold an event stream into a `DiGraph` carrying the full node and edge data
+///
+/// Events are applied as by `EventReducer`: removing a node drops its
+/// incident edges, `UpdateNode` merges into the node's weight, and `Clear`
+/// starts over. Removed elements are compacted away, so indices are dense.
+#[cfg(feature = "std")]
+pub fn build_graph(
+    events: &[GraphEvent],
+) -> petgraph::graph::DiGraph<crate::reducer::NodeData, crate::reducer::EdgeData> {
+    let mut reducer = crate::reducer::EventReducer::new();
+    reducer.apply_all(events);
+    petgraph::graph::DiGraph::from(reducer.snapshot().graph)
+}
+
+/// Stable content hash of an event stream, for caching rendered output
+///
+/// The [`canonicalize`]d stream is hashed, so declaration order within a run
+/// of additions does not change the fingerprint. Events are hashed field by
+/// field, with custom properties sorted by key.
+pub fn fingerprint(events: &[GraphEvent]) -> u64 {
+    let mut hasher = Fnv(FNV_OFFSET_BASIS);
+    for event in canonicalize(events) {
+        event.feed(&mut hasher);
+    }
+    hasher.0
+}
+
+/// Reorder an event stream into a deterministic form, for snapshot tests
+///
+/// Only runs of consecutive declarations (`SetGraphMeta`, `SetLayout`, and
+/// the `Add*` events) are reordered: within each run, graph metadata and
+/// layout come first, then nodes sorted by id, edges by `(from, to, id)`,
+/// and groups by id. Updates, removals, `Clear`, and batch markers stay
+/// where they were and end a run, so the canonical stream reduces to the
+/// same graph. Canonicalizing twice changes nothing.
+pub fn canonicalize(events: &[GraphEvent]) -> Vec<GraphEvent> {
+    let mut result = Vec::with_capacity(events.len());
+    let mut run: Vec<&GraphEvent> = Vec::new();
+
+    for event in events {
+        if canonical_key(event).is_some() {
+            run.push(event);
+        } else {
+            sort_run(&mut result, &mut run);
+            result.push(event.clone());
+        }
+    }
+    sort_run(&mut result, &mut run);
+
+    result
+}
+
+/// Whether two event streams are equal once canonicalized
+pub fn semantically_equal(a: &[GraphEvent], b: &[GraphEvent]) -> bool {
+    canonicalize(a) == canonicalize(b)
+}
+
+fn sort_run(result: &mut Vec<GraphEvent>, run: &mut Vec<&GraphEvent>) {
+    // Stable, so events sharing a key keep their relative order
+    run.sort_by(|a, b| canonical_key(a).cmp(&canonical_key(b)));
+    result.extend(run.drain(..).cloned());
+}
+
+/// Sort key of a declaration that may be reordered, `None` for other events
+fn canonical_key(event: &GraphEvent) -> Option<(u8, &str, &str, &str)> {
+    match event {
+        GraphEvent::SetGraphMeta { .. } | GraphEvent::SetLayout { .. } => Some((0, "", "", "")),
+        GraphEvent::AddNode { id, .. } => Some((1, id, "", "")),
+        GraphEvent::AddEdge { id, from, to, .. } => Some((2, from, to, id)),
+        GraphEvent::AddGroup { id, .. } => Some((3, id, "", "")),
+        _ => None,
+    }
+}
+
+/// FNV-1a hash of a sequence of strings, stable across runs and platforms
+#[cfg(feature = "std")]
+pub(crate) fn stable_hash<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    parts
+        .into_iter()
+        .fold(FNV_OFFSET_BASIS, |hash, part| fnv1a(hash, part.as_bytes()))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes.iter().chain(&[0]) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// FNV-1a state fed one field at a time
+struct Fnv(u64);
+
+impl Fnv {
+    fn str(&mut self, text: &str) {
+        self.0 = fnv1a(self.0, text.as_bytes());
+    }
+
+    fn tagged(&mut self, tag: &str, text: &str) {
+        self.str(tag);
+        self.str(text);
+    }
+
+    fn opt_str(&mut self, text: Option<&str>) {
+        match text {
+            Some(text) => {
+                self.str("some");
+                self.str(text);
+            }
+            None => self.str("none"),
+        }
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0 = fnv1a(self.0, &value.to_le_bytes());
+    }
+
+    fn opt_u32(&mut self, value: Option<u32>) {
+        match value {
+            Some(value) => {
+                self.str("some");
+                self.u32(value);
+            }
+            None => self.str("none"),
+        }
+    }
+
+    fn opt_f32(&mut self, value: Option<f32>) {
+        self.opt_u32(value.map(f32::to_bits));
+    }
+
+    fn strs(&mut self, items: &[String]) {
+        self.u32(u32::try_from(items.len()).unwrap_or(u32::MAX));
+        for item in items {
+            self.str(item);
+        }
+    }
+}
+
+/// Feeds every field of a value to a [`Fnv`] hasher, for [`fingerprint`]
+trait Feed {
+    fn feed(&self, hasher: &mut Fnv);
+}
+
+impl Feed for GraphEvent {
+    fn feed(&self, h: &mut Fnv) {
+        match self {
+            Self::AddNode {
+                id,
+                label,
+                node_type,
+                properties,
+            } => {
+                h.str("AddNode");
+                h.str(id);
+                h.opt_str(label.as_deref());
+                node_type.feed(h);
+                properties.feed(h);
+            }
+            Self::UpdateNode {
+                id,
+                label,
+                properties,
+            } => {
+                h.str("UpdateNode");
+                h.str(id);
+                h.opt_str(label.as_deref());
+                properties.feed(h);
+            }
+            Self::RemoveNode { id } => h.tagged("RemoveNode", id),
+            Self::AddEdge {
+                id,
+                from,
+                to,
+                edge_type,
+                label,
+                properties,
+            } => {
+                h.str("AddEdge");
+                h.str(id);
+                h.str(from);
+                h.str(to);
+                edge_type.feed(h);
+                h.opt_str(label.as_deref());
+                properties.feed(h);
+            }
+            Self::UpdateEdge {
+                id,
+                label,
+                properties,
+            } => {
+                h.str("UpdateEdge");
+                h.str(id);
+                h.opt_str(label.as_deref());
+                properties.feed(h);
+            }
+            Self::RemoveEdge { id } => h.tagged("RemoveEdge", id),
+            Self::AddGroup {
+                id,
+                label,
+                members,
+                group_type,
+                properties,
+            } => {
+                h.str("AddGroup");
+                h.str(id);
+                h.opt_str(label.as_deref());
+                h.strs(members);
+                group_type.feed(h);
+                properties.feed(h);
+            }
+            Self::UpdateGroup { id, members } => {
+                h.str("UpdateGroup");
+                h.str(id);
+                h.strs(members);
+            }
+            Self::RemoveGroup { id } => h.tagged("RemoveGroup", id),
+            Self::SetLayout {
+                layout_type,
+                properties,
+            } => {
+                h.str("SetLayout");
+                layout_type.feed(h);
+                properties.feed(h);
+            }
+            Self::SetGraphMeta {
+                name,
+                directed,
+                strict,
+            } => {
+                h.str("SetGraphMeta");
+                h.opt_str(name.as_deref());
+                h.u32(u32::from(*directed));
+                h.u32(u32::from(*strict));
+            }
+            Self::Clear => h.str("Clear"),
+            Self::BatchStart => h.str("BatchStart"),
+            Self::BatchEnd => h.str("BatchEnd"),
+        }
+    }
+}
+
+impl Feed for NodeType {
+    fn feed(&self, h: &mut Fnv) {
+        match self {
+            Self::Node => h.str("Node"),
+            Self::Actor { actor_type } => {
+                h.str("Actor");
+                h.str(actor_type);
+            }
+            Self::State { state_type } => {
+                h.str("State");
+                h.str(match state_type {
+                    StateType::Initial => "Initial",
+                    StateType::Final => "Final",
+                    StateType::Normal => "Normal",
+                    StateType::Composite => "Composite",
+                    StateType::History => "History",
+                });
+            }
+            Self::Process => h.str("Process"),
+            Self::DataStore => h.str("DataStore"),
+            Self::External => h.str("External"),
+            Self::Custom(name) => {
+                h.str("Custom");
+                h.str(name);
+            }
+        }
+    }
+}
+
+impl Feed for EdgeType {
+    fn feed(&self, h: &mut Fnv) {
+        match self {
+            Self::Directed => h.str("Directed"),
+            Self::Undirected => h.str("Undirected"),
+            Self::Bidirectional => h.str("Bidirectional"),
+            Self::Message {
+                message_type,
+                sequence,
+            } => {
+                h.str("Message");
+                h.str(match message_type {
+                    MessageType::Synchronous => "Synchronous",
+                    MessageType::Asynchronous => "Asynchronous",
+                    MessageType::Return => "Return",
+                    MessageType::Create => "Create",
+                    MessageType::Destroy => "Destroy",
+                });
+                h.opt_u32(*sequence);
+            }
+            Self::Transition {
+                trigger,
+                guard,
+                action,
+            } => {
+                h.str("Transition");
+                h.opt_str(trigger.as_deref());
+                h.opt_str(guard.as_deref());
+                h.opt_str(action.as_deref());
+            }
+            Self::Association { association_type } => {
+                h.str("Association");
+                h.str(association_type);
+            }
+            Self::Custom(name) => {
+                h.str("Custom");
+                h.str(name);
+            }
+        }
+    }
+}
+
+impl Feed for GroupType {
+    fn feed(&self, h: &mut Fnv) {
+        match self {
+            Self::Cluster => h.str("Cluster"),
+            Self::Sequential { sequence_type } => {
+                h.str("Sequential");
+                h.str(sequence_type);
+            }
+            Self::Parallel => h.str("Parallel"),
+            Self::Container => h.str("Container"),
+            Self::Custom(name) => {
+                h.str("Custom");
+                h.str(name);
+            }
+        }
+    }
+}
+
+impl Feed for LayoutType {
+    fn feed(&self, h: &mut Fnv) {
+        let direction = |direction: &Direction| match direction {
+            Direction::TopToBottom => "TopToBottom",
+            Direction::BottomToTop => "BottomToTop",
+            Direction::LeftToRight => "LeftToRight",
+            Direction::RightToLeft => "RightToLeft",
+        };
+        match self {
+            Self::Hierarchical { direction: d } => {
+                h.str("Hierarchical");
+                h.str(direction(d));
+            }
+            Self::Force => h.str("Force"),
+            Self::Circular => h.str("Circular"),
+            Self::Grid { columns } => {
+                h.str("Grid");
+                h.opt_u32(*columns);
+            }
+            Self::Sequential { direction: d } => {
+                h.str("Sequential");
+                h.str(direction(d));
+            }
+            Self::Layered { direction: d } => {
+                h.str("Layered");
+                h.str(direction(d));
+            }
+            Self::Custom(name) => {
+                h.str("Custom");
+                h.str(name);
+            }
+        }
+    }
+}
+
+impl Feed for Properties {
+    fn feed(&self, h: &mut Fnv) {
+        match &self.style {
+            Some(style) => {
+                h.str("some");
+                style.feed(h);
+            }
+            None => h.str("none"),
+        }
+        match &self.position {
+            Some(position) => {
+                h.str("some");
+                position.feed(h);
+            }
+            None => h.str("none"),
+        }
+        let mut custom: Vec<(&String, &String)> = self.custom.iter().collect();
+        custom.sort_unstable();
+        h.u32(u32::try_from(custom.len()).unwrap_or(u32::MAX));
+        for (key, value) in custom {
+            h.str(key);
+            h.str(value);
+        }
+    }
+}
+
+impl Feed for Style {
+    fn feed(&self, h: &mut Fnv) {
+        let Self {
+            color,
+            background_color,
+            border_style,
+            border_color,
+            border_width,
+            shape,
+            size,
+            font_size,
+            font_family,
+            opacity,
+        } = self;
+        h.opt_str(color.as_deref());
+        h.opt_str(background_color.as_deref());
+        h.opt_str(border_style.as_deref());
+        h.opt_str(border_color.as_deref());
+        h.opt_f32(*border_width);
+        h.opt_str(shape.as_deref());
+        h.opt_f32(*size);
+        h.opt_f32(*font_size);
+        h.opt_str(font_family.as_deref());
+        h.opt_f32(*opacity);
+    }
+}
+
+impl Feed for Position {
+    fn feed(&self, h: &mut Fnv) {
+        match self {
+            Self::Absolute { x, y, z } => {
+                h.str("Absolute");
+                h.u32(x.to_bits());
+                h.u32(y.to_bits());
+                h.opt_f32(*z);
+            }
+            Self::Relative {
+                anchor,
+                offset_x,
+                offset_y,
+                offset_z,
+            } => {
+                h.str("Relative");
+                h.str(anchor);
+                h.u32(offset_x.to_bits());
+                h.u32(offset_y.to_bits());
+                h.opt_f32(*offset_z);
+            }
+            Self::Grid { row, column } => {
+                h.str("Grid");
+                h.u32(*row);
+                h.u32(*column);
+            }
+            Self::Sequential { order } => {
+                h.str("Sequential");
+                h.u32(*order);
+            }
+            Self::Layer { level } => {
+                h.str("Layer");
+                h.u32(*level);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::dot;
+
+    const NESTED_ORG_CHART: &str = r#"
+        digraph {
+            subgraph cluster_tenant {
+                label="Tenant: Acme Corp"
+                subgraph cluster_lob {
+                    label="LOB: Contact Center"
+                    subgraph cluster_site {
+                        label="Site: Main Site"
+                        user1 [label="Alice Smith"];
+                    }
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_group_tree_nested_org_chart() {
+        let events = dot::parse(NESTED_ORG_CHART);
+        let tree = group_tree(&events);
+
+        assert_eq!(tree.roots(), vec!["cluster_tenant"]);
+        assert_eq!(tree.children("cluster_tenant"), vec!["cluster_lob"]);
+        assert_eq!(tree.parent("cluster_site"), Some("cluster_lob"));
+
+        let descendants = tree.descendants("cluster_tenant");
+        assert!(descendants.contains(&"Alice Smith".to_string()));
+        assert!(descendants.contains(&"Acme Corp".to_string()));
+        assert!(descendants.contains(&"Main Site".to_string()));
+    }
+
+    #[test]
+    fn test_group_tree_ignores_non_container_nesting() {
+        let events = vec![
+            GraphEvent::AddGroup {
+                id: "outer".to_string(),
+                label: None,
+                members: vec!["inner".to_string(), "A".to_string()],
+                group_type: GroupType::Cluster,
+                properties: Properties::default(),
+            },
+            GraphEvent::AddGroup {
+                id: "inner".to_string(),
+                label: None,
+                members: vec!["B".to_string()],
+                group_type: GroupType::Cluster,
+                properties: Properties::default(),
+            },
+        ];
+
+        let tree = group_tree(&events);
+
+        assert!(tree.children("outer").is_empty());
+        assert_eq!(tree.parent("inner"), None);
+        assert_eq!(tree.descendants("outer"), vec!["inner", "A"]);
+    }
+
+    #[test]
+    fn test_style_to_css() {
+        let style = Style {
+            color: Some("black".to_string()),
+            background_color: Some("#ADD1B2".to_string()),
+            border_style: Some("dashed".to_string()),
+            border_color: Some("red".to_string()),
+            border_width: Some(1.5),
+            shape: Some("box".to_string()),
+            size: Some(10.0),
+            font_size: Some(12.0),
+            font_family: Some("Helvetica".to_string()),
+            opacity: Some(0.8),
+        };
+
+        assert_eq!(
+            style.to_css(),
+            "fill: #ADD1B2; stroke: red; stroke-width: 1.5; stroke-dasharray: 5,5; \
+             color: black; font-family: Helvetica; font-size: 12px; opacity: 0.8;"
+        );
+        assert_eq!(Style::default().to_css(), "");
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_declaration_order() {
+        let first = dot::parse(
+            "digraph {\n    A [label=\"Alpha\", color=red, shape=box];\n    B;\n    A -> B;\n}\n",
+        );
+        let second = dot::parse(
+            "digraph {\n    B;\n    A [shape=box, color=red, label=\"Alpha\"];\n    A -> B;\n}\n",
+        );
+        let relabeled = dot::parse(
+            "digraph {\n    A [label=\"Beta\", color=red, shape=box];\n    B;\n    A -> B;\n}\n",
+        );
+
+        assert_eq!(fingerprint(&first), fingerprint(&second));
+        assert_ne!(fingerprint(&first), fingerprint(&relabeled));
+
+        let recolored = dot::parse(
+            "digraph {\n    A [label=\"Alpha\", color=blue, shape=box];\n    B;\n    A -> B;\n}\n",
+        );
+        assert_ne!(fingerprint(&first), fingerprint(&recolored));
+    }
+
+    #[test]
+    fn test_canonicalize_is_idempotent() {
+        let first = dot::parse(
+            "digraph {\n    rankdir=LR;\n    C -> A;\n    B [color=red];\n    A -> B;\n}\n",
+        );
+        let second = dot::parse(
+            "digraph {\n    rankdir=LR;\n    A -> B;\n    B [color=red];\n    C -> A;\n}\n",
+        );
+
+        let canonical = canonicalize(&first);
+        assert_eq!(canonicalize(&canonical), canonical);
+        assert!(semantically_equal(&first, &second));
+        assert!(!semantically_equal(
+            &first,
+            &canonical[..canonical.len() - 2]
+        ));
+
+        let order: Vec<String> = canonical
+            .iter()
+            .map(|e| match e {
+                GraphEvent::AddNode { id, .. } | GraphEvent::AddEdge { id, .. } => id.clone(),
+                GraphEvent::BatchStart => "start".to_string(),
+                GraphEvent::BatchEnd => "end".to_string(),
+                GraphEvent::SetGraphMeta { .. } => "meta".to_string(),
+                GraphEvent::SetLayout { .. } => "layout".to_string(),
+                other => format!("{other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "start", "meta", "layout", "A", "B", "C", "A->B", "C->A", "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_removals_in_place() {
+        let remove = GraphEvent::RemoveNode {
+            id: "A".to_string(),
+        };
+        let readded = vec![
+            GraphEvent::simple_node("A", "A"),
+            remove.clone(),
+            GraphEvent::simple_node("A", "A"),
+        ];
+        let removed = vec![
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("A", "A"),
+            remove,
+        ];
+
+        assert_eq!(canonicalize(&readded), readded);
+        assert!(!semantically_equal(&readded, &removed));
+        assert_ne!(fingerprint(&readded), fingerprint(&removed));
+    }
+
+    #[test]
+    fn test_build_graph_folds_removals() {
+        let mut events =
+            dot::parse("digraph {\n    A -> B;\n    B -> C;\n    C -> A;\n    A [color=red];\n}\n");
+        events.push(GraphEvent::RemoveNode {
+            id: "B".to_string(),
+        });
+        events.push(GraphEvent::RemoveEdge {
+            id: "C->A".to_string(),
+        });
+        events.push(GraphEvent::UpdateNode {
+            id: "C".to_string(),
+            label: Some("Sink".to_string()),
+            properties: Properties::default(),
+        });
+
+        let graph = build_graph(&events);
+
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edge_count(), 0);
+        let a = graph.node_weights().find(|n| n.id == "A").unwrap();
+        let color = a.properties.style.as_ref().and_then(|s| s.color.as_deref());
+        assert_eq!(color, Some("red"));
+        let c = graph.node_weights().find(|n| n.id == "C").unwrap();
+        assert_eq!(c.label.as_deref(), Some("Sink"));
+
+        events.push(GraphEvent::Clear);
+        events.extend([
+            GraphEvent::simple_node("X", "X"),
+            GraphEvent::simple_node("Y", "Y"),
+            GraphEvent::simple_edge("X", "Y"),
+            GraphEvent::simple_edge("Y", "X"),
+        ]);
+        let graph = build_graph(&events);
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edge_count(), 2);
+        assert!(petgraph::algo::is_cyclic_directed(&graph));
+    }
+
+    #[test]
+    fn test_typed_digraph_edge_weights() {
+        let events = dot::parse("digraph {\n    A -> B [label=\"calls\"];\n    B -> C;\n}\n");
+
+        let graph = to_typed_digraph(&events);
+
+        assert_eq!(graph.node_count(), 3);
+        let mut edges: Vec<(&str, Option<&str>, &EdgeType)> = graph
+            .edge_weights()
+            .map(|edge| (edge.id.as_str(), edge.label.as_deref(), &edge.edge_type))
+            .collect();
+        edges.sort_by_key(|(id, ..)| *id);
+        assert_eq!(
+            edges,
+            vec![
+                ("A->B", Some("calls"), &EdgeType::Directed),
+                ("B->C", None, &EdgeType::Directed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convenience_constructors() {
+        assert_eq!(
+            GraphEvent::simple_group("cluster_a", ["A", "B"]),
+            GraphEvent::AddGroup {
+                id: "cluster_a".to_string(),
+                label: None,
+                members: vec!["A".to_string(), "B".to_string()],
+                group_type: GroupType::Cluster,
+                properties: Properties::default(),
+            }
+        );
+        assert_eq!(
+            GraphEvent::simple_group("empty", Vec::<String>::new()),
+            GraphEvent::AddGroup {
+                id: "empty".to_string(),
+                label: None,
+                members: Vec::new(),
+                group_type: GroupType::Cluster,
+                properties: Properties::default(),
+            }
+        );
+        assert_eq!(
+            GraphEvent::layout(LayoutType::Grid { columns: Some(3) }),
+            GraphEvent::SetLayout {
+                layout_type: LayoutType::Grid { columns: Some(3) },
+                properties: Properties::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_properties_flat_map() {
+        let mut properties = Properties {
+            style: Some(Style {
+                color: Some("red".to_string()),
+                border_width: Some(2.0),
+                ..Default::default()
+            }),
+            position: Some(Position::Layer { level: 3 }),
+            custom: HashMap::new(),
+        };
+        properties
+            .custom
+            .insert("team".to_string(), "core".to_string());
+
+        let flat = properties.to_flat_map();
+        let mut entries: Vec<_> = flat.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        entries.sort_unstable();
+        assert_eq!(
+            entries,
+            vec![
+                ("position.layer", "3"),
+                ("style.border_width", "2"),
+                ("style.color", "red"),
+                ("team", "core"),
+            ]
+        );
+
+        properties.position = Some(Position::Sequential { order: 7 });
+        assert_eq!(
+            properties.to_flat_map().get("position.order"),
+            Some(&"7".to_string())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let events = dot::parse(
+            r#"
+            digraph Services {
+                rankdir=LR;
+                gateway [shape=box, color=red, penwidth=1.5, pos="1.25,-3"];
+                subgraph cluster_core {
+                    label="Core";
+                    auth -> users [label="lookup", weight=2];
+                }
+                gateway -> auth [style=dashed];
+            }
+        "#,
+        );
+
+        let json = serde_json::to_string(&events).unwrap();
+        assert!(json.contains(r#""event":"AddNode""#));
+        assert!(json.contains(r#""border_width":1.5"#));
+
+        let decoded: Vec<GraphEvent> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, events);
+    }
 }
@@ -0,0 +1,697 @@
+use crate::dot::html::html_label_body;
+use crate::events::{
+    Direction, EdgeType, GraphEvent, GroupType, LayoutType, NodeType, Position, Properties, Style,
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Options controlling how events are written as DOT
+#[derive(Debug, Clone, Default)]
+pub struct DotWriteOptions {
+    /// Write labels flagged `custom["label_format"] = "html"`, as the DOT
+    /// parser flags `label=<...>`, in the HTML-like form instead of as quoted
+    /// strings
+    pub html_labels: bool,
+}
+
+/// Serialize an event stream back to DOT source
+///
+/// Events are folded into their final state first, so removed elements are
+/// not written. Groups become `subgraph cluster_<id>` blocks, nested when a
+/// group lists another group among its members, and each edge is written in
+/// the innermost cluster that contains both of its endpoints. Rank groups
+/// are written as anonymous `{ rank=same; ... }` subgraphs, not clusters.
+pub fn events_to_dot(events: &[GraphEvent]) -> String {
+    events_to_dot_with_options(events, &DotWriteOptions::default())
+}
+
+/// Serialize an event stream back to DOT source with custom options
+pub fn events_to_dot_with_options(events: &[GraphEvent], options: &DotWriteOptions) -> String {
+    let graph = DotGraph::from_events(events);
+    let mut out = String::new();
+
+    let directed = graph.is_directed();
+    let keyword = if directed { "digraph" } else { "graph" };
+    let strict = if graph.strict { "strict " } else { "" };
+    match graph.name {
+        Some(name) => {
+            let _ = writeln!(out, "{strict}{keyword} {} {{", quote_id(name));
+        }
+        None => {
+            let _ = writeln!(out, "{strict}{keyword} {{");
+        }
+    }
+
+    if let Some(LayoutType::Hierarchical { direction }) = graph.layout {
+        let rankdir = match direction {
+            Direction::TopToBottom => "TB",
+            Direction::BottomToTop => "BT",
+            Direction::LeftToRight => "LR",
+            Direction::RightToLeft => "RL",
+        };
+        let _ = writeln!(out, "    rankdir={rankdir};");
+    }
+
+    graph.write_scope(&mut out, None, 1, directed, options);
+
+    out.push_str("}\n");
+    out
+}
+
+struct NodeEntry<'a> {
+    id: &'a str,
+    label: Option<&'a str>,
+    node_type: &'a NodeType,
+    properties: &'a Properties,
+}
+
+struct EdgeEntry<'a> {
+    id: &'a str,
+    from: &'a str,
+    to: &'a str,
+    edge_type: &'a EdgeType,
+    label: Option<&'a str>,
+    properties: &'a Properties,
+}
+
+struct GroupEntry<'a> {
+    id: &'a str,
+    label: Option<&'a str>,
+    members: &'a [String],
+}
+
+/// Final graph state after folding an event stream
+#[derive(Default)]
+struct DotGraph<'a> {
+    nodes: Vec<NodeEntry<'a>>,
+    edges: Vec<EdgeEntry<'a>>,
+    groups: Vec<GroupEntry<'a>>,
+    /// `{ rank=same; ... }` constraints, written as anonymous subgraphs
+    ranks: Vec<GroupEntry<'a>>,
+    layout: Option<&'a LayoutType>,
+    /// Set by `SetGraphMeta`
+    strict: bool,
+    name: Option<&'a str>,
+    /// Directedness declared by `SetGraphMeta`, overriding the edge types
+    directed: Option<bool>,
+    /// Innermost group holding each node or group
+    parents: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> DotGraph<'a> {
+    fn from_events(events: &'a [GraphEvent]) -> Self {
+        let mut graph = DotGraph::default();
+
+        for event in events {
+            match event {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    node_type,
+                    properties,
+                } => {
+                    graph.nodes.retain(|n| n.id != id);
+                    graph.nodes.push(NodeEntry {
+                        id,
+                        label: label.as_deref(),
+                        node_type,
+                        properties,
+                    });
+                }
+                GraphEvent::RemoveNode { id } => {
+                    graph.nodes.retain(|n| n.id != id);
+                    graph.edges.retain(|e| e.from != id && e.to != id);
+                }
+                GraphEvent::AddEdge {
+                    id,
+                    from,
+                    to,
+                    edge_type,
+                    label,
+                    properties,
+                } => {
+                    graph.edges.retain(|e| e.id != id);
+                    graph.edges.push(EdgeEntry {
+                        id,
+                        from,
+                        to,
+                        edge_type,
+                        label: label.as_deref(),
+                        properties,
+                    });
+                }
+                GraphEvent::RemoveEdge { id } => graph.edges.retain(|e| e.id != id),
+                GraphEvent::AddGroup {
+                    id,
+                    label,
+                    members,
+                    group_type,
+                    properties,
+                } => {
+                    graph.groups.retain(|g| g.id != id);
+                    graph.ranks.retain(|g| g.id != id);
+                    let entry = GroupEntry {
+                        id,
+                        label: label.as_deref(),
+                        members,
+                    };
+                    let is_rank = matches!(group_type, GroupType::Custom(t) if t == "rank_same")
+                        || properties.custom.get("rank").is_some_and(|r| r == "same");
+                    if is_rank {
+                        graph.ranks.push(entry);
+                    } else if !matches!(group_type, GroupType::Sequential { .. }) {
+                        // Sequence fragments group messages, not nodes
+                        graph.groups.push(entry);
+                    }
+                }
+                GraphEvent::UpdateGroup { id, members } => {
+                    if let Some(group) = graph
+                        .groups
+                        .iter_mut()
+                        .chain(graph.ranks.iter_mut())
+                        .find(|g| g.id == id)
+                    {
+                        group.members = members;
+                    }
+                }
+                GraphEvent::RemoveGroup { id } => {
+                    graph.groups.retain(|g| g.id != id);
+                    graph.ranks.retain(|g| g.id != id);
+                }
+                GraphEvent::SetLayout { layout_type, .. } => graph.layout = Some(layout_type),
+                GraphEvent::SetGraphMeta {
+                    name,
+                    directed,
+                    strict,
+                } => {
+                    graph.name = name.as_deref();
+                    graph.directed = Some(*directed);
+                    graph.strict = *strict;
+                }
+                GraphEvent::Clear => graph = DotGraph::default(),
+                _ => {}
+            }
+        }
+
+        for group in &graph.groups {
+            for member in group.members {
+                graph.parents.entry(member).or_insert(group.id);
+            }
+        }
+
+        graph
+    }
+
+    fn is_directed(&self) -> bool {
+        if let Some(directed) = self.directed {
+            return directed;
+        }
+        self.edges.is_empty()
+            || self
+                .edges
+                .iter()
+                .any(|e| !matches!(e.edge_type, EdgeType::Undirected))
+    }
+
+    /// Chain of enclosing groups, innermost first
+    fn ancestors(&self, id: &str) -> Vec<&'a str> {
+        let mut chain = Vec::new();
+        let mut current = self.parents.get(id).copied();
+        while let Some(group) = current {
+            if chain.contains(&group) {
+                break;
+            }
+            chain.push(group);
+            current = self.parents.get(group).copied();
+        }
+        chain
+    }
+
+    /// Innermost group containing both endpoints of an edge
+    fn edge_scope(&self, edge: &EdgeEntry) -> Option<&'a str> {
+        let to_chain = self.ancestors(edge.to);
+        self.ancestors(edge.from)
+            .into_iter()
+            .find(|group| to_chain.contains(group))
+    }
+
+    fn write_scope(
+        &self,
+        out: &mut String,
+        scope: Option<&str>,
+        depth: usize,
+        directed: bool,
+        options: &DotWriteOptions,
+    ) {
+        let indent = "    ".repeat(depth);
+
+        for node in self
+            .nodes
+            .iter()
+            .filter(|n| self.parents.get(n.id).copied() == scope)
+        {
+            // Quoted, an HTML label keeps its `label_format` flag so it
+            // parses back to the same node; a plaintext table is written from
+            // its original markup
+            let custom = &node.properties.custom;
+            let html_label = if !options.html_labels {
+                None
+            } else if custom.get("label_format").is_some_and(|f| f == "html") {
+                node.label
+            } else {
+                custom
+                    .get("markup")
+                    .filter(|_| custom.contains_key("table"))
+                    .map(String::as_str)
+            };
+            let _ = writeln!(
+                out,
+                "{indent}{}{};",
+                quote_id(node.id),
+                format_attributes(&node_attributes(node, html_label.is_some()), html_label)
+            );
+        }
+
+        for group in self
+            .groups
+            .iter()
+            .filter(|g| self.parents.get(g.id).copied() == scope)
+        {
+            let name = if group.id.starts_with("cluster_") {
+                quote_id(group.id)
+            } else {
+                quote_id(&format!("cluster_{}", group.id))
+            };
+            let _ = writeln!(out, "{indent}subgraph {name} {{");
+            if let Some(label) = group.label {
+                let _ = writeln!(out, "{indent}    label={};", quote_string(label));
+            }
+            self.write_scope(out, Some(group.id), depth + 1, directed, options);
+            let _ = writeln!(out, "{indent}}}");
+        }
+
+        if scope.is_none() {
+            for rank in &self.ranks {
+                let _ = write!(out, "{indent}{{ rank=same;");
+                for member in rank.members {
+                    let _ = write!(out, " {};", quote_id(member));
+                }
+                out.push_str(" }\n");
+            }
+        }
+
+        let op = if directed { "->" } else { "--" };
+        for edge in self.edges.iter().filter(|e| self.edge_scope(e) == scope) {
+            let _ = writeln!(
+                out,
+                "{indent}{} {op} {}{};",
+                quote_id(edge.from),
+                quote_id(edge.to),
+                format_attributes(&edge_attributes(edge, directed), None)
+            );
+        }
+    }
+}
+
+fn node_attributes(node: &NodeEntry, html_label: bool) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    if let Some(label) = node.label.filter(|label| !html_label && *label != node.id) {
+        attrs.push(("label".to_string(), label.to_string()));
+    }
+    if let NodeType::Custom(node_type) = node.node_type {
+        attrs.push(("type".to_string(), node_type.clone()));
+    }
+    let mut has_pos = false;
+    match node.properties.position {
+        Some(Position::Layer { level }) => attrs.push(("level".to_string(), level.to_string())),
+        Some(Position::Absolute { x, y, z }) => {
+            let mut pos = format!("{x},{y}");
+            if let Some(z) = z {
+                let _ = write!(pos, ",{z}");
+            }
+            if node
+                .properties
+                .custom
+                .get("pinned")
+                .is_some_and(|p| p == "true")
+            {
+                pos.push('!');
+            }
+            attrs.push(("pos".to_string(), pos));
+            has_pos = true;
+        }
+        _ => {}
+    }
+    let mut visual: Vec<(String, String)> = node
+        .properties
+        .style
+        .as_ref()
+        .map(style_attributes)
+        .unwrap_or_default();
+    visual.extend(
+        sorted_custom(node.properties)
+            .into_iter()
+            .filter(|(key, _)| {
+                !html_label || !matches!(key.as_str(), "label_format" | "table" | "markup")
+            })
+            // `pinned` is written as the `!` of `pos`
+            .filter(|(key, _)| !has_pos || key != "pinned"),
+    );
+    visual.sort();
+    attrs.extend(visual);
+    attrs
+}
+
+/// Graphviz attributes for the populated fields of a `Style`
+fn style_attributes(style: &Style) -> Vec<(String, String)> {
+    let text = [
+        ("color", &style.color),
+        ("fillcolor", &style.background_color),
+        ("style", &style.border_style),
+        ("shape", &style.shape),
+        ("fontname", &style.font_family),
+    ];
+    let numbers = [
+        ("penwidth", style.border_width),
+        ("fontsize", style.font_size),
+        ("width", style.size),
+    ];
+    text.into_iter()
+        .filter_map(|(key, value)| value.as_ref().map(|v| (key.to_string(), v.clone())))
+        .chain(
+            numbers
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|v| (key.to_string(), v.to_string()))),
+        )
+        .collect()
+}
+
+fn edge_attributes(edge: &EdgeEntry, directed: bool) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    if let Some(label) = edge.label {
+        attrs.push(("label".to_string(), label.to_string()));
+    }
+    if directed {
+        match edge.edge_type {
+            EdgeType::Undirected => attrs.push(("dir".to_string(), "none".to_string())),
+            EdgeType::Bidirectional => attrs.push(("dir".to_string(), "both".to_string())),
+            _ => {}
+        }
+    }
+    attrs.extend(sorted_custom(edge.properties).into_iter().map(
+        |(key, value)| match key.as_str() {
+            "head_label" => ("headlabel".to_string(), value),
+            "tail_label" => ("taillabel".to_string(), value),
+            _ => (key, value),
+        },
+    ));
+    attrs
+}
+
+fn sorted_custom(properties: &Properties) -> Vec<(String, String)> {
+    let mut custom: Vec<(String, String)> = properties
+        .custom
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    custom.sort();
+    custom
+}
+
+/// Format an attribute list, led by an HTML-like label written unquoted
+fn format_attributes(attrs: &[(String, String)], html_label: Option<&str>) -> String {
+    let html = html_label.map(|markup| match html_label_body(markup) {
+        Some(_) => format!("label={markup}"),
+        None => format!("label=<{markup}>"),
+    });
+    let list: Vec<String> = html
+        .into_iter()
+        .chain(
+            attrs
+                .iter()
+                .map(|(key, value)| format!("{}={}", quote_id(key), quote_string(value))),
+        )
+        .collect();
+    if list.is_empty() {
+        return String::new();
+    }
+    format!(" [{}]", list.join(", "))
+}
+
+/// Quote an identifier unless it is a plain DOT id or number
+fn quote_id(id: &str) -> String {
+    // DOT treats any non-ASCII character as a letter
+    let is_letter = |c: char| !c.is_ascii() || c.is_ascii_alphabetic() || c == '_';
+    let is_plain = id.chars().next().is_some_and(is_letter)
+        && id.chars().all(|c| is_letter(c) || c.is_ascii_digit());
+    if is_plain || is_numeral(id) {
+        id.to_string()
+    } else {
+        quote_string(id)
+    }
+}
+
+/// Whether `id` matches DOT's numeral grammar `-?(\.[0-9]+|[0-9]+(\.[0-9]*)?)`
+fn is_numeral(id: &str) -> bool {
+    let digits = id.strip_prefix('-').unwrap_or(id);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    (!whole.is_empty() || !fraction.is_empty()) && all_digits(whole) && all_digits(fraction)
+}
+
+fn quote_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dot;
+
+    const NESTED_ORG_CHART: &str = r#"
+        digraph {
+            subgraph cluster_tenant {
+                label="Tenant: Acme Corp"
+                subgraph cluster_lob {
+                    label="LOB: Contact Center"
+                    subgraph cluster_site {
+                        label="Site: Main Site"
+                        user1 [label="Alice Smith"];
+                    }
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_simple_graph_to_dot() {
+        let dot = events_to_dot(&dot::parse("digraph {\n    A -> B;\n}\n"));
+
+        assert_eq!(dot, "digraph {\n    A;\n    B;\n    A -> B;\n}\n");
+    }
+
+    #[test]
+    fn test_graph_meta_header() {
+        let dot = events_to_dot(&dot::parse("graph Net {\n    A -- B;\n}\n"));
+        assert_eq!(dot, "graph Net {\n    A;\n    B;\n    A -- B;\n}\n");
+    }
+
+    #[test]
+    fn test_only_dot_numerals_unquoted() {
+        for id in ["7", "-7", "1.5", "1.", ".5", "-.5", "inf", "NaN"] {
+            assert_eq!(quote_id(id), id);
+        }
+        for id in ["1e5", "-inf", "+1", ".", "-", "1.2.3", "-NaN"] {
+            assert_eq!(quote_id(id), format!("\"{id}\""));
+        }
+    }
+
+    #[test]
+    fn test_nested_clusters_round_trip() {
+        let events = dot::parse(NESTED_ORG_CHART);
+        let output = events_to_dot(&events);
+
+        assert!(output.contains("subgraph cluster_tenant {"));
+        assert!(output.contains("        subgraph cluster_lob {"));
+        assert!(output.contains("            subgraph cluster_site {"));
+        assert!(output.contains("label=\"Acme Corp\";"));
+
+        // Edges between members of the same cluster stay inside it
+        let site_start = output.find("subgraph cluster_site").unwrap();
+        let user_edge = output.find("\"Main Site\" -> \"Alice Smith\";").unwrap();
+        assert!(user_edge > site_start);
+
+        let group_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddGroup { .. }))
+            .count();
+        assert_eq!(output.matches("subgraph cluster_").count(), group_count);
+    }
+
+    #[test]
+    fn test_parse_to_dot_round_trip() {
+        let source = r#"
+            digraph Services {
+                rankdir=LR;
+                "API Gateway" [shape=box];
+                lonely;
+                subgraph cluster_core {
+                    label="Core";
+                    auth -> users [label="lookup"];
+                }
+                "API Gateway" -> auth;
+            }
+        "#;
+        let counts = |events: &[GraphEvent]| {
+            let nodes = events
+                .iter()
+                .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
+                .count();
+            let edges = events
+                .iter()
+                .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+                .count();
+            (nodes, edges)
+        };
+
+        let events = dot::parse(source);
+        let output = dot::to_dot(&events);
+        let reparsed = dot::parse(&output);
+
+        assert_eq!(counts(&events), (4, 2));
+        assert_eq!(counts(&reparsed), counts(&events));
+        assert!(output.starts_with("digraph Services {\n    rankdir=LR;\n"));
+        assert!(output.contains("\"API Gateway\" -> auth;"));
+        assert!(output.contains("subgraph cluster_core {"));
+    }
+
+    #[test]
+    fn test_pos_round_trip() {
+        let source =
+            "digraph {\n    a [pos=\"1.5,-2!\"];\n    b [pos=\"3,4,5\"];\n    a -> b;\n}\n";
+        let events = dot::parse(source);
+
+        assert_eq!(events_to_dot(&events), source);
+    }
+
+    #[test]
+    fn test_rank_group_round_trip() {
+        let source = "digraph {\n    a;\n    b;\n    subgraph cluster_x {\n        c;\n    }\n    { rank=same; a; c; }\n    a -> b;\n}\n";
+        let events = dot::parse(source);
+        let output = events_to_dot(&events);
+
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn test_strict_round_trip() {
+        let events = dot::parse("strict digraph G {\n    A -> B;\n    A -> B;\n}\n");
+        let output = dot::to_dot(&events);
+
+        assert!(output.starts_with("strict digraph G {\n"));
+        assert_eq!(output.matches("A -> B;").count(), 1);
+        assert!(!output.contains("rankdir"), "{output}");
+        assert!(dot::to_dot(&dot::parse(&output)).starts_with("strict digraph G {\n"));
+
+        // Strictness comes from the graph metadata alone
+        let meta = vec![
+            GraphEvent::SetGraphMeta {
+                name: None,
+                directed: false,
+                strict: true,
+            },
+            GraphEvent::simple_node("A", "A"),
+        ];
+        assert!(dot::to_dot(&meta).starts_with("strict graph {\n"));
+
+        let plain = dot::to_dot(&dot::parse("digraph G {\n    A -> B;\n}\n"));
+        assert!(plain.starts_with("digraph G {\n"));
+    }
+
+    fn html_labeled_node() -> Vec<GraphEvent> {
+        let mut properties = Properties::default();
+        properties
+            .custom
+            .insert("label_format".to_string(), "html".to_string());
+        vec![GraphEvent::AddNode {
+            id: "A".to_string(),
+            label: Some("<b>Bold</b> \"move\"".to_string()),
+            node_type: NodeType::Node,
+            properties,
+        }]
+    }
+
+    #[test]
+    fn test_labels_quoted_by_default() {
+        let output = events_to_dot(&html_labeled_node());
+
+        assert!(output.contains("A [label=\"<b>Bold</b> \\\"move\\\"\", label_format=\"html\"];"));
+        assert!(!output.contains("label=<"));
+    }
+
+    #[test]
+    fn test_parsed_html_label_round_trip() {
+        let source = "digraph {\n    title [label=<<b>Title</b>>];\n}\n";
+        let events = dot::parse(source);
+        let options = DotWriteOptions { html_labels: true };
+        assert_eq!(events_to_dot_with_options(&events, &options), source);
+
+        // Quoted, the label and its flag still parse back unchanged
+        assert_eq!(dot::parse(&events_to_dot(&events)), events);
+
+        let table =
+            "digraph {\n    t [shape=plaintext, label=<<table><tr><td>a</td></tr></table>>];\n}\n";
+        let events = dot::parse(table);
+        let output = events_to_dot_with_options(&events, &options);
+        assert_eq!(
+            output,
+            "digraph {\n    t [label=<<table><tr><td>a</td></tr></table>>, shape=\"plaintext\"];\n}\n"
+        );
+        assert_eq!(dot::parse(&output), events);
+    }
+
+    #[test]
+    fn test_html_labels_option() {
+        let options = DotWriteOptions { html_labels: true };
+        let output = events_to_dot_with_options(&html_labeled_node(), &options);
+
+        assert!(output.contains("    A [label=<<b>Bold</b> \"move\">];"));
+    }
+
+    #[test]
+    fn test_style_round_trip() {
+        let events = dot::parse(
+            "digraph {\n    A [shape=box, fillcolor=\"#eee\", penwidth=2, tooltip=hi];\n}\n",
+        );
+
+        let output = events_to_dot(&events);
+
+        assert!(
+            output
+                .contains("A [fillcolor=\"#eee\", penwidth=\"2\", shape=\"box\", tooltip=\"hi\"];")
+        );
+
+        // Each axis is written back as the attribute it was read from
+        let sized = events_to_dot(&dot::parse(
+            "digraph {\n    A [height=2];\n    B [width=3, height=1];\n}\n",
+        ));
+        assert!(sized.contains("    A [height=\"2\"];"), "{sized}");
+        assert!(
+            sized.contains("    B [height=\"1\", width=\"3\"];"),
+            "{sized}"
+        );
+        let reparsed = dot::parse(&output);
+        let style = |events: &[GraphEvent]| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { properties, .. } => properties.style.clone(),
+                _ => None,
+            })
+        };
+        assert_eq!(style(&reparsed), style(&events));
+    }
+}
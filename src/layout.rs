@@ -0,0 +1,117 @@
+use crate::events::{GraphEvent, Position, Properties};
+use std::collections::HashMap;
+
+/// `(row, column)` of each node when laid out `columns` wide
+///
+/// Nodes fill the grid row by row in declaration order; a node removed by
+/// `RemoveNode` (or `Clear`) gives up its cell, and a repeated `AddNode`
+/// keeps the node's first position. `columns` of zero is treated as one.
+pub fn grid_layout(events: &[GraphEvent], columns: u32) -> HashMap<String, (u32, u32)> {
+    let columns = columns.max(1);
+    grid_order(events)
+        .into_iter()
+        .zip(0u32..)
+        .map(|(id, index)| (id.to_string(), (index / columns, index % columns)))
+        .collect()
+}
+
+/// `UpdateNode` events placing each node with a `Position::Grid` from
+/// [`grid_layout`], in declaration order
+pub fn grid_layout_events(events: &[GraphEvent], columns: u32) -> Vec<GraphEvent> {
+    let cells = grid_layout(events, columns);
+    grid_order(events)
+        .into_iter()
+        .map(|id| {
+            let (row, column) = cells[id];
+            GraphEvent::UpdateNode {
+                id: id.to_string(),
+                label: None,
+                properties: Properties {
+                    position: Some(Position::Grid { row, column }),
+                    ..Properties::default()
+                },
+            }
+        })
+        .collect()
+}
+
+/// Ids of the nodes left after folding the events, in declaration order
+///
+/// Removed nodes are left in place and skipped at the end, so each event is
+/// handled in constant time.
+fn grid_order(events: &[GraphEvent]) -> Vec<&str> {
+    let mut order: Vec<&str> = Vec::new();
+    // index into `order` of each node that is still present
+    let mut live: HashMap<&str, usize> = HashMap::new();
+    for event in events {
+        match event {
+            GraphEvent::AddNode { id, .. } if !live.contains_key(id.as_str()) => {
+                live.insert(id, order.len());
+                order.push(id);
+            }
+            GraphEvent::RemoveNode { id } => {
+                live.remove(id.as_str());
+            }
+            GraphEvent::Clear => {
+                order.clear();
+                live.clear();
+            }
+            _ => {}
+        }
+    }
+    order
+        .into_iter()
+        .enumerate()
+        .filter(|(index, id)| live.get(id) == Some(index))
+        .map(|(_, id)| id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::LayoutType;
+
+    #[test]
+    fn test_nine_nodes_in_three_columns() {
+        let mut events = vec![GraphEvent::layout(LayoutType::Grid { columns: Some(3) })];
+        events.extend((1..=9).map(|n| GraphEvent::simple_node(format!("n{n}"), format!("{n}"))));
+
+        let cells = grid_layout(&events, 3);
+
+        assert_eq!(cells.len(), 9);
+        assert_eq!(cells["n1"], (0, 0));
+        assert_eq!(cells["n3"], (0, 2));
+        assert_eq!(cells["n4"], (1, 0));
+        assert_eq!(cells["n8"], (2, 1));
+        assert_eq!(cells["n9"], (2, 2));
+
+        events.push(GraphEvent::RemoveNode {
+            id: "n2".to_string(),
+        });
+        let updates = grid_layout_events(&events, 3);
+        assert_eq!(updates.len(), 8);
+        assert!(matches!(
+            &updates[1],
+            GraphEvent::UpdateNode { id, properties, .. }
+                if id == "n3"
+                    && properties.position == Some(Position::Grid { row: 0, column: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_readded_node_moves_to_the_end() {
+        let events = vec![
+            GraphEvent::simple_node("a", "A"),
+            GraphEvent::simple_node("b", "B"),
+            GraphEvent::RemoveNode {
+                id: "a".to_string(),
+            },
+            GraphEvent::simple_node("c", "C"),
+            GraphEvent::simple_node("a", "A"),
+            GraphEvent::simple_node("b", "B"),
+        ];
+
+        assert_eq!(grid_order(&events), ["b", "c", "a"]);
+    }
+}
@@ -1,14 +1,40 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+mod detect;
+#[cfg(feature = "std")]
 pub mod dot;
 pub mod events;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod mermaid;
+#[cfg(feature = "std")]
 pub mod plantuml;
+#[cfg(feature = "std")]
+pub mod reducer;
+#[cfg(feature = "std")]
+pub mod transform;
+#[cfg(feature = "std")]
 mod types;
 
 // Main event-based API
 pub use events::{
     Direction, EdgeType, EventResult, GraphEvent, GroupType, LayoutType, MessageType, NodeType,
-    Position, Properties, StateType, Style,
+    Overlap, Position, Properties, Splines, StateType, Style,
 };
 
+// Format detection
+#[cfg(feature = "std")]
+pub use detect::{DetectedKind, detect, parse_auto};
+
 // Legacy types - deprecated
+#[cfg(feature = "std")]
 #[deprecated(note = "Use the event-based API instead")]
 pub use types::{GraphData, NodeInfo};
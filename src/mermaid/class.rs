@@ -0,0 +1,132 @@
+use crate::events::{EdgeType, GraphEvent, NodeType};
+use crate::reducer::EventReducer;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences, IntoNodeReferences};
+use std::fmt::Write;
+
+/// Write an event stream as a Mermaid `classDiagram`
+///
+/// Each node becomes a class, annotated with its type (`<<service>>` for a
+/// `NodeType::Custom("service")`) and labelled when the label differs from
+/// the id. Edges become relationships: `EdgeType::Association` picks the
+/// relation from its `association_type`, and other edges are plain
+/// associations. Removed elements are not written.
+pub fn events_to_class(events: &[GraphEvent]) -> String {
+    let mut reducer = EventReducer::new();
+    reducer.apply_all(events);
+    let graph = &reducer.data().graph;
+
+    let mut out = String::from("classDiagram\n");
+    for (_, node) in graph.node_references() {
+        let name = class_name(&node.id);
+        let _ = write!(out, "    class {name}");
+        if let Some(label) = node.label.as_deref().filter(|label| *label != node.id) {
+            let _ = write!(out, "[\"{}\"]", label.replace('"', "#quot;"));
+        }
+        match annotation(&node.node_type) {
+            Some(annotation) => {
+                let _ = writeln!(out, " {{\n        <<{annotation}>>\n    }}");
+            }
+            None => out.push('\n'),
+        }
+    }
+
+    for edge in graph.edge_references() {
+        let from = class_name(&graph[edge.source()].id);
+        let to = class_name(&graph[edge.target()].id);
+        let weight = edge.weight();
+        let _ = write!(out, "    {from} {} {to}", relation(&weight.edge_type));
+        if let Some(label) = &weight.label {
+            let _ = write!(out, " : {}", label.replace('\n', " "));
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// The `<<annotation>>` for a node type, if it has one
+fn annotation(node_type: &NodeType) -> Option<&str> {
+    match node_type {
+        NodeType::Node | NodeType::State { .. } => None,
+        NodeType::Actor { .. } => Some("actor"),
+        NodeType::Process => Some("process"),
+        NodeType::DataStore => Some("datastore"),
+        NodeType::External => Some("external"),
+        NodeType::Custom(kind) => Some(kind),
+    }
+}
+
+/// The Mermaid relationship arrow for an edge, read from `from` to `to`
+fn relation(edge_type: &EdgeType) -> &'static str {
+    match edge_type {
+        EdgeType::Association { association_type } => {
+            match association_type.to_ascii_lowercase().as_str() {
+                "inheritance" | "extends" | "generalization" => "--|>",
+                "realization" | "implements" => "..|>",
+                "composition" => "*--",
+                "aggregation" => "o--",
+                "dependency" => "..>",
+                "link" => "--",
+                _ => "-->",
+            }
+        }
+        EdgeType::Undirected => "--",
+        _ => "-->",
+    }
+}
+
+/// A class name Mermaid accepts, with other characters replaced by `_`
+fn class_name(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dot;
+    use crate::events::Properties;
+
+    fn association(from: &str, to: &str, association_type: &str) -> GraphEvent {
+        GraphEvent::AddEdge {
+            id: format!("{from}-{association_type}-{to}"),
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type: EdgeType::Association {
+                association_type: association_type.to_string(),
+            },
+            label: None,
+            properties: Properties::default(),
+        }
+    }
+
+    #[test]
+    fn test_typed_graph_to_class_diagram() {
+        let mut events = dot::parse(
+            r#"
+            digraph {
+                Order [type=entity];
+                Customer [type=entity, label="Customer Account"];
+                Billing [type=service];
+                Order -> Customer [label="placed by"];
+            }
+        "#,
+        );
+        events.push(GraphEvent::simple_node("LineItem", "LineItem"));
+        events.push(association("Billing", "Order", "dependency"));
+        events.push(association("Order", "LineItem", "composition"));
+
+        assert_eq!(
+            events_to_class(&events),
+            "classDiagram\n    \
+             class Order {\n        <<entity>>\n    }\n    \
+             class Customer[\"Customer Account\"] {\n        <<entity>>\n    }\n    \
+             class Billing {\n        <<service>>\n    }\n    \
+             class LineItem\n    \
+             Order --> Customer : placed by\n    \
+             Billing ..> Order\n    \
+             Order *-- LineItem\n"
+        );
+    }
+}
@@ -0,0 +1,649 @@
+use crate::events::{Direction, EdgeType, GraphEvent, LayoutType, NodeType, Properties, Style};
+use std::collections::HashMap;
+
+/// Node shapes by their opening and closing brackets, with the shape name
+/// recorded in `Style.shape` (Graphviz names where one exists)
+const SHAPES: &[(&str, &str, &str)] = &[
+    ("(((", ")))", "doublecircle"),
+    ("((", "))", "circle"),
+    ("([", "])", "stadium"),
+    ("[[", "]]", "subroutine"),
+    ("[(", ")]", "cylinder"),
+    ("{{", "}}", "hexagon"),
+    ("[/", "/]", "parallelogram"),
+    ("[/", "\\]", "trapezoid"),
+    ("[\\", "\\]", "parallelogram_alt"),
+    ("[\\", "/]", "trapezoid_alt"),
+    ("[", "]", "box"),
+    ("(", ")", "rounded"),
+    ("{", "}", "diamond"),
+    (">", "]", "asymmetric"),
+];
+
+/// Statements that style or group nodes without declaring any
+const IGNORED_KEYWORDS: &[&str] = &[
+    "classDef",
+    "class",
+    "style",
+    "linkStyle",
+    "click",
+    "subgraph",
+    "end",
+    "direction",
+];
+
+/// Parse a Mermaid flowchart into graph events
+///
+/// The `graph`/`flowchart` header sets a `Hierarchical` layout in its
+/// direction. Nodes are emitted before edges, each with the label and shape
+/// of its last definition. Styling statements and `subgraph` lines are
+/// skipped, though the nodes and links inside a subgraph are read.
+pub fn parse(input: &str) -> Result<Vec<GraphEvent>, String> {
+    let mut statements = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with("%%"))
+        .flat_map(|(index, line)| {
+            split_statements(line)
+                .into_iter()
+                .map(move |statement| (index + 1, statement))
+        });
+
+    let Some((line, header)) = statements.next() else {
+        return Err("Expected a `graph` or `flowchart` header".to_string());
+    };
+    let direction = parse_header(header).map_err(|e| format!("line {line}: {e}"))?;
+
+    let mut chart = Flowchart::default();
+    for (line, statement) in statements {
+        chart
+            .statement(statement)
+            .map_err(|e| format!("line {line}: {e}"))?;
+    }
+    Ok(chart.into_events(direction))
+}
+
+/// Read the layout direction from `graph TD` or `flowchart LR`
+fn parse_header(header: &str) -> Result<Direction, String> {
+    let mut words = header.split_whitespace();
+    if !matches!(words.next(), Some("graph" | "flowchart")) {
+        return Err(format!(
+            "expected a `graph` or `flowchart` header, found `{header}`"
+        ));
+    }
+    match words.next() {
+        None | Some("TD" | "TB") => Ok(Direction::TopToBottom),
+        Some("BT") => Ok(Direction::BottomToTop),
+        Some("LR") => Ok(Direction::LeftToRight),
+        Some("RL") => Ok(Direction::RightToLeft),
+        Some(other) => Err(format!("unknown direction `{other}`")),
+    }
+}
+
+/// Split a line at `;` outside quotes and brackets, dropping empty statements
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '[' | '(' | '{' if !in_quotes => depth += 1,
+            ']' | ')' | '}' if !in_quotes => depth = depth.saturating_sub(1),
+            ';' if !in_quotes && depth == 0 => {
+                statements.push(&line[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&line[start..]);
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+#[derive(Default)]
+struct Flowchart {
+    /// Node ids in order of first appearance
+    order: Vec<String>,
+    /// Label and shape from each node's last definition
+    nodes: HashMap<String, (Option<String>, Option<&'static str>)>,
+    edges: Vec<GraphEvent>,
+    /// Edges emitted per `from->to` id, to number repeated links
+    edge_ids: HashMap<String, usize>,
+}
+
+impl Flowchart {
+    /// Read one statement: a node, or a chain of links between node groups
+    fn statement(&mut self, statement: &str) -> Result<(), String> {
+        let keyword = statement.split_whitespace().next().unwrap_or_default();
+        if IGNORED_KEYWORDS.contains(&keyword) {
+            return Ok(());
+        }
+
+        let mut rest = statement;
+        let mut sources = self.node_group(&mut rest)?;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Ok(());
+            }
+            let link = parse_link(&mut rest)?;
+            let targets = self.node_group(&mut rest)?;
+            for from in &sources {
+                for to in &targets {
+                    self.add_edge(from, to, &link);
+                }
+            }
+            sources = targets;
+        }
+    }
+
+    /// Read nodes joined by `&`, as in `A & B --> C`
+    fn node_group(&mut self, rest: &mut &str) -> Result<Vec<String>, String> {
+        let mut group = vec![self.node(rest)?];
+        while let Some(after) = rest.trim_start().strip_prefix('&') {
+            *rest = after;
+            group.push(self.node(rest)?);
+        }
+        Ok(group)
+    }
+
+    /// Read a node id with an optional shape, such as `A[label]` or `B{choice}`
+    fn node(&mut self, rest: &mut &str) -> Result<String, String> {
+        let text = rest.trim_start();
+        let end = text
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(text.len());
+        if end == 0 {
+            return Err(format!("expected a node id at `{text}`"));
+        }
+        let id = text[..end].to_string();
+        *rest = &text[end..];
+        if !self.nodes.contains_key(&id) {
+            self.order.push(id.clone());
+            self.nodes.insert(id.clone(), (None, None));
+        }
+
+        if let Some((label, shape, consumed)) = parse_shape(rest) {
+            *rest = &rest[consumed..];
+            self.nodes.insert(id.clone(), (Some(label), Some(shape)));
+        }
+        // `:::className` applies a style class, which is not kept
+        if let Some(after) = rest.strip_prefix(":::") {
+            let end = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(after.len());
+            *rest = &after[end..];
+        }
+        Ok(id)
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, link: &Link) {
+        let key = format!("{from}->{to}");
+        let count = self.edge_ids.entry(key.clone()).or_default();
+        *count += 1;
+        let id = if *count == 1 {
+            key
+        } else {
+            format!("{key}#{count}")
+        };
+
+        let mut properties = Properties::default();
+        for (name, value) in [("style", link.style), ("arrowhead", link.arrowhead)] {
+            if let Some(value) = value {
+                properties
+                    .custom
+                    .insert(name.to_string(), value.to_string());
+            }
+        }
+        self.edges.push(GraphEvent::AddEdge {
+            id,
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type: link.edge_type.clone(),
+            label: link.label.clone(),
+            properties,
+        });
+    }
+
+    fn into_events(self, direction: Direction) -> Vec<GraphEvent> {
+        let mut events = vec![
+            GraphEvent::BatchStart,
+            GraphEvent::layout(LayoutType::Hierarchical { direction }),
+        ];
+        for id in self.order {
+            let (label, shape) = self.nodes.get(&id).cloned().unwrap_or_default();
+            let node_type = match shape {
+                Some("diamond") => NodeType::Custom("decision".to_string()),
+                Some("cylinder") => NodeType::DataStore,
+                _ => NodeType::Node,
+            };
+            let properties = Properties {
+                style: shape.map(|shape| Style {
+                    shape: Some(shape.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            events.push(GraphEvent::AddNode {
+                label: Some(label.unwrap_or_else(|| id.clone())),
+                id,
+                node_type,
+                properties,
+            });
+        }
+        events.extend(self.edges);
+        events.push(GraphEvent::BatchEnd);
+        events
+    }
+}
+
+/// Read a bracketed shape, returning its label, shape name, and length
+fn parse_shape(text: &str) -> Option<(String, &'static str, usize)> {
+    // The longest matching opener wins, then the closer found first
+    SHAPES
+        .iter()
+        .filter(|(open, _, _)| text.starts_with(open))
+        .filter_map(|&(open, close, shape)| {
+            let body = &text[open.len()..];
+            // Quoted labels may contain the closing brackets
+            let search_from = match body.strip_prefix('"') {
+                Some(quoted) => quoted.find('"').map(|end| end + 2)?,
+                None => 0,
+            };
+            let end = search_from + body[search_from..].find(close)?;
+            Some((open.len(), end, close.len(), shape))
+        })
+        .max_by_key(|&(open_len, end, _, _)| (open_len, std::cmp::Reverse(end)))
+        .map(|(open_len, end, close_len, shape)| {
+            let label = text[open_len..open_len + end].trim();
+            let label = label
+                .strip_prefix('"')
+                .and_then(|label| label.strip_suffix('"'))
+                .unwrap_or(label);
+            (label.to_string(), shape, open_len + end + close_len)
+        })
+}
+
+/// A link between nodes, such as `-->`, `-.->`, or `== text ==>`
+struct Link {
+    edge_type: EdgeType,
+    /// `dotted`, `bold`, or `invis`, as in DOT's edge `style`
+    style: Option<&'static str>,
+    /// `circle` or `cross` for `--o` and `--x`
+    arrowhead: Option<&'static str>,
+    label: Option<String>,
+}
+
+/// Read a link and its `|label|` or inline `-- label -->` text
+fn parse_link(rest: &mut &str) -> Result<Link, String> {
+    let text = rest.trim_start();
+    let starts_with_head = text.starts_with('<');
+    let body_start = usize::from(starts_with_head);
+    let body_len = text[body_start..]
+        .find(|c| !matches!(c, '-' | '.' | '=' | '~'))
+        .unwrap_or(text.len() - body_start);
+    let body = &text[body_start..body_start + body_len];
+    if body.len() < 2 {
+        return Err(format!("expected a link at `{text}`"));
+    }
+    let mut after = &text[body_start + body_len..];
+
+    // `o` and `x` heads must be followed by a space, since they could
+    // otherwise start the next node's id
+    let head = after.chars().next().filter(|c| match c {
+        '>' => true,
+        'o' | 'x' => after[1..]
+            .chars()
+            .next()
+            .is_none_or(|next| next.is_whitespace() || next == '|'),
+        _ => false,
+    });
+    if let Some(head) = head {
+        after = &after[head.len_utf8()..];
+    }
+
+    // `A -- text --> B`: a bare opener, then the text, then the real link
+    if head.is_none() && !starts_with_head && matches!(body, "--" | "-." | "==") {
+        let closing = after
+            .char_indices()
+            .find(|&(index, c)| {
+                c.is_whitespace()
+                    && ["--", "==", ".-"]
+                        .iter()
+                        .any(|link| after[index..].trim_start().starts_with(link))
+            })
+            .map(|(index, _)| index)
+            .ok_or_else(|| format!("unterminated link text at `{text}`"))?;
+        let label = after[..closing].trim();
+        *rest = &after[closing..];
+        let mut link = parse_link(rest)?;
+        if !label.is_empty() {
+            link.label = Some(unquote(label).to_string());
+        }
+        return Ok(link);
+    }
+
+    let edge_type = match (starts_with_head, head) {
+        (true, Some('>')) => EdgeType::Bidirectional,
+        (_, Some(_)) => EdgeType::Directed,
+        (_, None) => EdgeType::Undirected,
+    };
+    let style = if body.contains('~') {
+        Some("invis")
+    } else if body.contains('.') {
+        Some("dotted")
+    } else if body.contains('=') {
+        Some("bold")
+    } else {
+        None
+    };
+    let arrowhead = match head {
+        Some('o') => Some("circle"),
+        Some('x') => Some("cross"),
+        _ => None,
+    };
+
+    let mut label = None;
+    if let Some(piped) = after.trim_start().strip_prefix('|') {
+        // A quoted label may contain `|`
+        let search_from = piped.trim_start().strip_prefix('"').map_or(0, |quoted| {
+            quoted
+                .find('"')
+                .map_or(0, |end| piped.len() - quoted.len() + end)
+        });
+        let end = piped[search_from..]
+            .find('|')
+            .map(|end| search_from + end)
+            .ok_or_else(|| format!("unterminated link label at `{text}`"))?;
+        label = Some(unquote(piped[..end].trim()).to_string());
+        after = &piped[end + 1..];
+    }
+    *rest = after;
+
+    Ok(Link {
+        edge_type,
+        style,
+        arrowhead,
+        label,
+    })
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(events: &[GraphEvent]) -> Vec<(&str, Option<&str>, Option<&str>)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    properties,
+                    ..
+                } => Some((
+                    id.as_str(),
+                    label.as_deref(),
+                    properties
+                        .style
+                        .as_ref()
+                        .and_then(|style| style.shape.as_deref()),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn edges(events: &[GraphEvent]) -> Vec<(&str, &str, &str, Option<&str>)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    id,
+                    from,
+                    to,
+                    label,
+                    ..
+                } => Some((id.as_str(), from.as_str(), to.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simple_flowchart() {
+        let events = parse(
+            r"
+            graph TD
+                A --> B
+                B --> C
+        ",
+        )
+        .unwrap();
+
+        assert_eq!(events.first(), Some(&GraphEvent::BatchStart));
+        assert_eq!(
+            events[1],
+            GraphEvent::layout(LayoutType::Hierarchical {
+                direction: Direction::TopToBottom
+            })
+        );
+        assert_eq!(events.last(), Some(&GraphEvent::BatchEnd));
+        assert_eq!(
+            nodes(&events),
+            vec![
+                ("A", Some("A"), None),
+                ("B", Some("B"), None),
+                ("C", Some("C"), None)
+            ]
+        );
+        assert_eq!(
+            edges(&events),
+            vec![("A->B", "A", "B", None), ("B->C", "B", "C", None)]
+        );
+    }
+
+    #[test]
+    fn test_header_directions() {
+        let direction = |header: &str| {
+            parse(header).unwrap().into_iter().find_map(|e| match e {
+                GraphEvent::SetLayout {
+                    layout_type: LayoutType::Hierarchical { direction },
+                    ..
+                } => Some(direction),
+                _ => None,
+            })
+        };
+        assert_eq!(direction("flowchart LR"), Some(Direction::LeftToRight));
+        assert_eq!(direction("graph RL;"), Some(Direction::RightToLeft));
+        assert_eq!(direction("flowchart BT\nA"), Some(Direction::BottomToTop));
+        assert_eq!(direction("graph"), Some(Direction::TopToBottom));
+
+        assert!(parse("").is_err());
+        assert!(parse("A --> B").is_err());
+        assert!(parse("graph XY").is_err());
+    }
+
+    #[test]
+    fn test_node_shapes() {
+        let events = parse(
+            r#"
+            flowchart TD
+                A[Start here] --> B(Round)
+                B --> C{Is it ok?}
+                C --> D[(Orders DB)]
+                C --> E(("Done [x]"))
+                F>Flag]
+                G[/Input/] --> H[\Manual/]
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            nodes(&events),
+            vec![
+                ("A", Some("Start here"), Some("box")),
+                ("B", Some("Round"), Some("rounded")),
+                ("C", Some("Is it ok?"), Some("diamond")),
+                ("D", Some("Orders DB"), Some("cylinder")),
+                ("E", Some("Done [x]"), Some("circle")),
+                ("F", Some("Flag"), Some("asymmetric")),
+                ("G", Some("Input"), Some("parallelogram")),
+                ("H", Some("Manual"), Some("trapezoid_alt")),
+            ]
+        );
+
+        let node_type = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, node_type, .. } if id == node_id => {
+                    Some(node_type.clone())
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(node_type("A"), Some(NodeType::Node));
+        assert_eq!(
+            node_type("C"),
+            Some(NodeType::Custom("decision".to_string()))
+        );
+        assert_eq!(node_type("D"), Some(NodeType::DataStore));
+    }
+
+    #[test]
+    fn test_node_defined_after_edge_keeps_its_shape() {
+        let events = parse(
+            r"
+            graph LR
+                A --> B
+                B[Billing]
+        ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            nodes(&events),
+            vec![("A", Some("A"), None), ("B", Some("Billing"), Some("box"))]
+        );
+    }
+
+    #[test]
+    fn test_edge_labels_and_chains() {
+        let events = parse(
+            r#"
+            graph TD
+                A -->|yes| B -- "maybe" --> C
+                C -->|"a | b"| D
+                A & B --> E; E --> A
+                A --> B
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            edges(&events),
+            vec![
+                ("A->B", "A", "B", Some("yes")),
+                ("B->C", "B", "C", Some("maybe")),
+                ("C->D", "C", "D", Some("a | b")),
+                ("A->E", "A", "E", None),
+                ("B->E", "B", "E", None),
+                ("E->A", "E", "A", None),
+                ("A->B#2", "A", "B", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_styles() {
+        let events = parse(
+            r"
+            flowchart LR
+                A --- B
+                A -.-> C
+                A ==> D
+                A <--> E
+                A --o F
+                A --x G
+                A ~~~ H
+                A-->I
+                A -. async .-> J
+        ",
+        )
+        .unwrap();
+
+        let links: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    to,
+                    edge_type,
+                    label,
+                    properties,
+                    ..
+                } => Some((
+                    to.as_str(),
+                    edge_type.clone(),
+                    properties.custom.get("style").map(String::as_str),
+                    properties.custom.get("arrowhead").map(String::as_str),
+                    label.as_deref(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("B", EdgeType::Undirected, None, None, None),
+                ("C", EdgeType::Directed, Some("dotted"), None, None),
+                ("D", EdgeType::Directed, Some("bold"), None, None),
+                ("E", EdgeType::Bidirectional, None, None, None),
+                ("F", EdgeType::Directed, None, Some("circle"), None),
+                ("G", EdgeType::Directed, None, Some("cross"), None),
+                ("H", EdgeType::Undirected, Some("invis"), None, None),
+                ("I", EdgeType::Directed, None, None, None),
+                ("J", EdgeType::Directed, Some("dotted"), None, Some("async")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comments_styles_and_subgraphs_are_skipped() {
+        let events = parse(
+            r"
+            %% A comment
+            graph TD
+                subgraph one [Group]
+                    A --> B
+                end
+                classDef hot fill:#f96
+                class A hot
+                style B fill:#bbf
+                C:::hot --> A
+        ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            edges(&events),
+            vec![("A->B", "A", "B", None), ("C->A", "C", "A", None)]
+        );
+    }
+
+    #[test]
+    fn test_malformed_statement_reports_line() {
+        let error = parse("graph TD\n    A --> B\n    A -->\n").unwrap_err();
+        assert!(error.starts_with("line 3:"), "{error}");
+    }
+}
@@ -0,0 +1,23 @@
+pub mod class;
+pub mod flowchart;
+pub mod sequence;
+
+pub use class::events_to_class;
+
+use crate::events::GraphEvent;
+
+/// Parse a Mermaid flowchart or sequence diagram, chosen by its header
+///
+/// A `sequenceDiagram` goes to [`sequence::parse`] and anything else to
+/// [`flowchart::parse`].
+pub fn parse(input: &str) -> Result<Vec<GraphEvent>, String> {
+    let header = input
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("%%"));
+    if header == Some("sequenceDiagram") {
+        sequence::parse(input)
+    } else {
+        flowchart::parse(input)
+    }
+}
@@ -0,0 +1,497 @@
+use crate::events::{Direction, GraphEvent, LayoutType, NodeType, Position, Properties};
+use crate::plantuml::parser::{
+    Message, ParserState, activate, deactivate, ensure_participant, message_properties,
+    push_destruction, push_message, push_participant,
+};
+use crate::plantuml::types::{ArrowDecoration, ArrowType};
+use std::collections::{BTreeSet, HashSet};
+
+/// Mermaid arrows, longest first, with the `PlantUML` arrow they draw like
+/// and whether they end in a cross
+const ARROWS: &[(&str, ArrowType, bool)] = &[
+    ("<<-->>", ArrowType::BiDashedDirectional, false),
+    ("<<->>", ArrowType::BiDirectional, false),
+    ("-->>", ArrowType::DashedAsync, false),
+    ("->>", ArrowType::SolidAsync, false),
+    ("-->", ArrowType::DashedSync, false),
+    ("--)", ArrowType::DashedAsync, false),
+    ("--x", ArrowType::DashedSync, true),
+    ("->", ArrowType::SolidSync, false),
+    ("-)", ArrowType::SolidAsync, false),
+    ("-x", ArrowType::SolidSync, true),
+];
+
+/// Statements that structure or decorate the diagram without adding to it
+const IGNORED_KEYWORDS: &[&str] = &[
+    "autonumber",
+    "title",
+    "loop",
+    "alt",
+    "else",
+    "opt",
+    "par",
+    "and",
+    "critical",
+    "option",
+    "break",
+    "rect",
+    "box",
+    "end",
+    "link",
+    "links",
+];
+
+/// Parse a Mermaid `sequenceDiagram` into graph events
+///
+/// Events use the same vocabulary as [`crate::plantuml::parse`]: participants
+/// are nodes with `Sequential` orders, messages are `msg-N` edges whose
+/// arrows map to `MessageType` as `PlantUML`'s do, activations are
+/// `UpdateNode`s carrying `activation_depth`, and notes are
+/// `Custom("note")` nodes, and `create`/`destroy` mark lifelines
+/// `created`/`destroyed` as `PlantUML`'s do. Block statements such as `loop`
+/// and `alt` are skipped, though the messages inside them are read.
+pub fn parse(input: &str) -> Result<Vec<GraphEvent>, String> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with("%%"));
+
+    match lines.next() {
+        Some((_, "sequenceDiagram")) => {}
+        Some((line, header)) => {
+            return Err(format!(
+                "line {line}: expected a `sequenceDiagram` header, found `{header}`"
+            ));
+        }
+        None => return Err("Expected a `sequenceDiagram` header".to_string()),
+    }
+
+    let mut diagram = SequenceDiagram::default();
+    diagram.events.push(GraphEvent::BatchStart);
+    diagram
+        .events
+        .push(GraphEvent::layout(LayoutType::Sequential {
+            direction: Direction::LeftToRight,
+        }));
+    for (line, statement) in lines {
+        diagram
+            .statement(statement)
+            .map_err(|e| format!("line {line}: {e}"))?;
+    }
+    diagram.events.push(GraphEvent::BatchEnd);
+    Ok(diagram.events)
+}
+
+#[derive(Default)]
+struct SequenceDiagram {
+    events: Vec<GraphEvent>,
+    state: ParserState,
+    /// Participants whose lifelines end at the next message they take part in
+    pending_destroys: HashSet<String>,
+    note_count: u32,
+}
+
+/// Split a statement into its first word and the trimmed rest
+fn split_keyword(statement: &str) -> (&str, &str) {
+    statement
+        .split_once(char::is_whitespace)
+        .map_or((statement, ""), |(keyword, rest)| (keyword, rest.trim()))
+}
+
+impl SequenceDiagram {
+    fn statement(&mut self, statement: &str) -> Result<(), String> {
+        let (keyword, rest) = split_keyword(statement);
+        match keyword {
+            "participant" | "actor" => {
+                self.participant(keyword, rest);
+                Ok(())
+            }
+            "create" => {
+                let (keyword, declaration) = split_keyword(rest);
+                if !matches!(keyword, "participant" | "actor") {
+                    return Err(format!("cannot parse `{statement}`"));
+                }
+                let id = self.participant(keyword, declaration);
+                self.state.expect_create(id);
+                Ok(())
+            }
+            "destroy" => {
+                self.pending_destroys.insert(rest.to_string());
+                Ok(())
+            }
+            "activate" => {
+                activate(rest.to_string(), &mut self.events, &mut self.state);
+                Ok(())
+            }
+            "deactivate" => {
+                deactivate(rest.to_string(), &mut self.events, &mut self.state);
+                Ok(())
+            }
+            "Note" | "note" => self.note(rest),
+            _ if IGNORED_KEYWORDS.contains(&keyword) => Ok(()),
+            _ => self.message(statement),
+        }
+    }
+
+    /// Handle `participant A as Alice` or `actor B`, returning the id
+    fn participant(&mut self, keyword: &str, declaration: &str) -> String {
+        let (id, label) = match declaration.split_once(" as ") {
+            Some((id, label)) => (id.trim(), label.trim()),
+            None => (declaration, declaration),
+        };
+        if !self.state.knows(id) {
+            let actor_type = if keyword == "actor" {
+                "human"
+            } else {
+                "participant"
+            };
+            let node_type = NodeType::Actor {
+                actor_type: actor_type.to_string(),
+            };
+            push_participant(
+                id.to_string(),
+                label.to_string(),
+                node_type,
+                Properties::default(),
+                &mut self.events,
+                &mut self.state,
+            );
+        }
+        id.to_string()
+    }
+
+    /// Handle `A->>B: text`, with `+`/`-` after the arrow activating the
+    /// target or deactivating the sender
+    fn message(&mut self, statement: &str) -> Result<(), String> {
+        let (endpoints, text) = statement
+            .split_once(':')
+            .map_or((statement, ""), |(endpoints, text)| {
+                (endpoints, text.trim())
+            });
+        let from_end = endpoints
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(endpoints.len());
+        let from = &endpoints[..from_end];
+        let rest = endpoints[from_end..].trim_start();
+        let &(arrow, arrow_type, cross) = ARROWS
+            .iter()
+            .find(|(arrow, _, _)| rest.starts_with(arrow))
+            .filter(|_| !from.is_empty())
+            .ok_or_else(|| format!("cannot parse `{statement}`"))?;
+        let rest = &rest[arrow.len()..];
+        let (activation, rest) = match rest.chars().next() {
+            Some(marker @ ('+' | '-')) => (Some(marker), &rest[1..]),
+            _ => (None, rest),
+        };
+        let to = rest.trim();
+        if to.is_empty() || !to.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("cannot parse `{statement}`"));
+        }
+
+        ensure_participant(from, from.to_string(), &mut self.events, &mut self.state);
+        ensure_participant(to, to.to_string(), &mut self.events, &mut self.state);
+
+        let decorations: BTreeSet<ArrowDecoration> =
+            cross.then_some(ArrowDecoration::Lost).into_iter().collect();
+        let properties = message_properties(&decorations, from == to, None);
+        // `destroy B` ends B's lifeline at the next message to or from it
+        let destroys_target = self.pending_destroys.remove(to);
+        push_message(
+            Message {
+                from: from.to_string(),
+                to: to.to_string(),
+                kind: arrow_type.to_message_type(),
+                lost: cross || destroys_target,
+                label: (!text.is_empty()).then(|| text.to_string()),
+                properties,
+            },
+            &mut self.events,
+            &mut self.state,
+        );
+        if self.pending_destroys.remove(from) {
+            push_destruction(
+                from.to_string(),
+                self.state.last_message(),
+                &mut self.events,
+            );
+        }
+
+        match activation {
+            Some('+') => activate(to.to_string(), &mut self.events, &mut self.state),
+            Some('-') => deactivate(from.to_string(), &mut self.events, &mut self.state),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle `Note over A,B: text`, `Note left of A: text`, or
+    /// `Note right of A: text`
+    fn note(&mut self, rest: &str) -> Result<(), String> {
+        let (position, text) = rest
+            .split_once(':')
+            .ok_or_else(|| format!("note `{rest}` has no text"))?;
+        let (side, participants) = if let Some(participants) = position.strip_prefix("over ") {
+            ("over", participants)
+        } else if let Some(participants) = position.strip_prefix("left of ") {
+            ("left", participants)
+        } else if let Some(participants) = position.strip_prefix("right of ") {
+            ("right", participants)
+        } else {
+            return Err(format!("unknown note position `{position}`"));
+        };
+        let participants: Vec<&str> = participants.split(',').map(str::trim).collect();
+
+        let mut properties = Properties {
+            position: Some(Position::Sequential {
+                order: self.state.next_message(),
+            }),
+            ..Default::default()
+        };
+        for (key, value) in [
+            ("note_shape", "note".to_string()),
+            ("note_position", side.to_string()),
+            ("participants", participants.join(",")),
+        ] {
+            properties.custom.insert(key.to_string(), value);
+        }
+        self.events.push(GraphEvent::AddNode {
+            id: format!("note-{}", self.note_count),
+            label: Some(text.trim().to_string()),
+            node_type: NodeType::Custom("note".to_string()),
+            properties,
+        });
+        self.note_count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EdgeType, MessageType};
+    use crate::plantuml;
+
+    fn messages(events: &[GraphEvent]) -> Vec<(&str, &str, &str, MessageType, Option<&str>)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    id,
+                    from,
+                    to,
+                    edge_type: EdgeType::Message { message_type, .. },
+                    label,
+                    ..
+                } => Some((
+                    id.as_str(),
+                    from.as_str(),
+                    to.as_str(),
+                    message_type.clone(),
+                    label.as_deref(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_plantuml_events() {
+        let mermaid = parse(
+            r"
+            sequenceDiagram
+                actor User
+                participant Web as Web Server
+                User->>Web: HTTP Request
+                activate Web
+                Web-->>User: HTTP Response
+                deactivate Web
+                User-xWeb: Hang up
+        ",
+        )
+        .unwrap();
+        let plantuml = plantuml::parse(
+            r#"@startuml
+actor User
+participant "Web Server" as Web
+User ->> Web: HTTP Request
+activate Web
+Web -->> User: HTTP Response
+deactivate Web
+User ->x Web: Hang up
+@enduml"#,
+        )
+        .unwrap();
+
+        assert_eq!(mermaid, plantuml);
+    }
+
+    #[test]
+    fn test_arrow_message_types() {
+        let events = parse(
+            r"
+            sequenceDiagram
+                A->B: solid
+                A-->B: dotted
+                A->>B: call
+                A-->>B: reply
+                A-)B: async
+                A-xB: lost
+                A<<->>B: both
+                B->>B: self
+        ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            messages(&events),
+            vec![
+                ("msg-0", "A", "B", MessageType::Synchronous, Some("solid")),
+                ("msg-1", "A", "B", MessageType::Return, Some("dotted")),
+                ("msg-2", "A", "B", MessageType::Asynchronous, Some("call")),
+                ("msg-3", "A", "B", MessageType::Return, Some("reply")),
+                ("msg-4", "A", "B", MessageType::Asynchronous, Some("async")),
+                ("msg-5", "A", "B", MessageType::Destroy, Some("lost")),
+                ("msg-6", "A", "B", MessageType::Synchronous, Some("both")),
+                ("msg-7", "B", "B", MessageType::Asynchronous, Some("self")),
+            ]
+        );
+
+        let flagged = |key: &str| -> Vec<&str> {
+            events
+                .iter()
+                .filter_map(|e| match e {
+                    GraphEvent::AddEdge { id, properties, .. }
+                        if properties.custom.contains_key(key) =>
+                    {
+                        Some(id.as_str())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(flagged("lost"), vec!["msg-5"]);
+        assert_eq!(flagged("self_message"), vec!["msg-7"]);
+        assert_eq!(plantuml::undeclared_participants(&events), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_create_and_destroy() {
+        let mermaid = parse(
+            r"
+            sequenceDiagram
+                participant A
+                create participant B
+                A->>B: new
+                destroy B
+                A->>B: close
+                create actor C
+                B->>C: spawn
+                destroy C
+                C-->>A: bye
+        ",
+        )
+        .unwrap();
+        let plantuml = plantuml::parse(
+            r"@startuml
+participant A
+create participant B
+A ->> B: new
+A ->> B: close
+destroy B
+create actor C
+B ->> C: spawn
+C -->> A: bye
+destroy C
+@enduml",
+        )
+        .unwrap();
+
+        assert_eq!(mermaid, plantuml);
+        let types: Vec<MessageType> = messages(&mermaid)
+            .into_iter()
+            .map(|(_, _, _, message_type, _)| message_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                MessageType::Create,
+                MessageType::Destroy,
+                MessageType::Create,
+                MessageType::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_activation_shorthand_and_notes() {
+        let events = parse(
+            r"
+            sequenceDiagram
+                participant A
+                participant B
+                A->>+B: request
+                Note over A,B: in flight
+                loop retry
+                    B->>B: work
+                end
+                B-->>-A: done
+                Note right of A: finished
+        ",
+        )
+        .unwrap();
+
+        let entries = plantuml::timeline(&events);
+        let activations: Vec<Vec<&str>> = entries
+            .iter()
+            .map(|entry| entry.activations.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(activations, vec![vec![], vec!["B"], vec!["B"]]);
+
+        let notes: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    node_type: NodeType::Custom(kind),
+                    properties,
+                } if kind == "note" => Some((
+                    id.as_str(),
+                    label.as_deref(),
+                    properties.custom["note_position"].as_str(),
+                    properties.custom["participants"].as_str(),
+                    properties.position.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (
+                    "note-0",
+                    Some("in flight"),
+                    "over",
+                    "A,B",
+                    Some(Position::Sequential { order: 1 })
+                ),
+                (
+                    "note-1",
+                    Some("finished"),
+                    "right",
+                    "A",
+                    Some(Position::Sequential { order: 3 })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        assert!(parse("graph TD\nA --> B").is_err());
+        let error = parse("sequenceDiagram\n    A->>B: ok\n    A ~~ B\n").unwrap_err();
+        assert!(error.starts_with("line 3:"), "{error}");
+        assert!(parse("sequenceDiagram\n    Note above A: text").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::events::{EdgeType, GraphEvent, MessageType};
+
+/// Return the ids of participants that were never declared explicitly.
+///
+/// These participants only appear in messages and were auto-created by the
+/// parser, which usually points at a misspelled participant name. One
+/// declared after its first message is updated with `auto_created` false
+/// and is not listed.
+pub fn undeclared_participants(events: &[GraphEvent]) -> Vec<String> {
+    let mut undeclared: Vec<String> = Vec::new();
+    for event in events {
+        match event {
+            GraphEvent::AddNode { id, properties, .. }
+            | GraphEvent::UpdateNode { id, properties, .. } => {
+                match properties.custom.get("auto_created").map(String::as_str) {
+                    Some("true") if !undeclared.contains(id) => undeclared.push(id.clone()),
+                    Some("false") => undeclared.retain(|undeclared| undeclared != id),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    undeclared
+}
+
+/// Pair each synchronous message with the return that answers it
+///
+/// A return from `B` to `A` answers the most recent unanswered call from `A`
+/// to `B`, so nested calls pair inside out. Pairs are listed in call order;
+/// calls that are never answered pair with `None`.
+pub fn call_return_pairs(events: &[GraphEvent]) -> Vec<(String, Option<String>)> {
+    let mut pairs: Vec<(String, Option<String>)> = Vec::new();
+    // (from, to, index into `pairs`) of calls still waiting for a return
+    let mut open: Vec<(&str, &str, usize)> = Vec::new();
+
+    for event in events {
+        let GraphEvent::AddEdge {
+            id,
+            from,
+            to,
+            edge_type: EdgeType::Message { message_type, .. },
+            ..
+        } = event
+        else {
+            continue;
+        };
+        match message_type {
+            MessageType::Synchronous => {
+                open.push((from, to, pairs.len()));
+                pairs.push((id.clone(), None));
+            }
+            MessageType::Return => {
+                if let Some(position) = open
+                    .iter()
+                    .rposition(|(caller, callee, _)| caller == to && callee == from)
+                {
+                    let (_, _, index) = open.remove(position);
+                    pairs[index].1 = Some(id.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plantuml::parse;
+
+    #[test]
+    fn test_undeclared_participants_flags_typo() {
+        let input = r"@startuml
+participant Alice
+participant Bob
+Alice -> Bob: Hello
+Bob --> Alcie: Hi
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(undeclared_participants(&events), vec!["Alcie".to_string()]);
+    }
+
+    #[test]
+    fn test_call_return_pairs() {
+        let input = r"@startuml
+participant A
+participant B
+participant C
+A -> B: request
+B -> C: lookup
+C --> B: row
+B --> A: response
+A -> C: fire and forget
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            call_return_pairs(&events),
+            vec![
+                ("msg-0".to_string(), Some("msg-3".to_string())),
+                ("msg-1".to_string(), Some("msg-2".to_string())),
+                ("msg-4".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undeclared_participants_empty_when_all_declared() {
+        let input = r"@startuml
+participant A
+participant B
+A -> B: Hello
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert!(undeclared_participants(&events).is_empty());
+    }
+}
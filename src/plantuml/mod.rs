@@ -1,5 +1,9 @@
+pub mod analysis;
 pub mod parser;
+pub mod timeline;
 pub mod types;
 
-pub use parser::parse;
-pub use types::ArrowType;
+pub use analysis::{call_return_pairs, undeclared_participants};
+pub use parser::{ParseOptions, parse, parse_all, parse_with_options};
+pub use timeline::{TimelineEntry, lifeline_spans, timeline};
+pub use types::{ArrowDecoration, ArrowStyle, ArrowType};
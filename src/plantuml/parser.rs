@@ -1,20 +1,201 @@
-use crate::events::{Direction, EdgeType, GraphEvent, LayoutType, NodeType, Position, Properties};
-use crate::plantuml::types::ArrowType;
+use crate::events::{
+    Direction, EdgeType, GraphEvent, GroupType, LayoutType, MessageType, NodeType, Position,
+    Properties, Style,
+};
+use crate::plantuml::types::{ArrowDecoration, ArrowStyle, ArrowType, normalize_color};
 use pest::Parser;
 use pest_derive::Parser;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Parser)]
 #[grammar = "plantuml/grammar.pest"]
 pub struct PlantUMLParser;
 
+/// Options controlling how `PlantUML` input is turned into events
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Upgrade the arrow-derived `MessageType` from label suffixes:
+    /// `[async]` becomes `Asynchronous` and `[return]` becomes `Return`.
+    /// The suffix is stripped from the emitted label.
+    pub infer_message_types: bool,
+    /// Reject statements that don't match the activation state, such as a
+    /// `return` with no active participant, and `end X` lines naming a
+    /// different block than they close. Lenient parsing ignores them.
+    pub strict: bool,
+}
+
+/// Mutable state threaded through a single diagram block
+///
+/// Shared with the Mermaid sequence parser, which emits the same events.
+#[derive(Debug, Default)]
+pub(crate) struct ParserState {
+    participant_order: u32,
+    sequence_number: u32,
+    participants: HashMap<String, String>, // alias -> id mapping
+    known_ids: HashSet<String>,
+    /// Open activations, innermost last
+    activations: Vec<Activation>,
+    /// Most recent sender of a message to each participant
+    last_callers: HashMap<String, String>,
+    /// Combined fragments emitted so far, for unique group ids
+    fragment_count: u32,
+    /// Notes emitted so far, for unique node ids
+    note_count: u32,
+    /// Dividers and delays emitted so far, for unique node ids
+    marker_count: u32,
+    /// Numbering set by `autonumber`; messages are numbered by their
+    /// position until the first directive
+    autonumber: Option<Autonumber>,
+    /// Participants of each `box`, in declaration order
+    boxes: Vec<Vec<String>>,
+    /// Index into `boxes` of the box being parsed
+    open_box: Option<usize>,
+    /// Participants declared with `create` awaiting their creating message
+    pending_creates: HashSet<String>,
+}
+
+/// Message numbering configured by `autonumber`
+#[derive(Debug)]
+struct Autonumber {
+    next: u32,
+    step: u32,
+    stopped: bool,
+}
+
+impl Default for Autonumber {
+    fn default() -> Self {
+        Self {
+            next: 1,
+            step: 1,
+            stopped: false,
+        }
+    }
+}
+
+/// An `activate` waiting for its `deactivate` or `return`
+#[derive(Debug)]
+struct Activation {
+    participant: String,
+    caller: Option<String>,
+    /// Index of the message the activation bar starts at
+    start: u32,
+}
+
+impl ParserState {
+    fn resolve(&self, name: String) -> String {
+        self.participants.get(&name).cloned().unwrap_or(name)
+    }
+
+    /// Whether a participant with this id has been emitted
+    pub(crate) fn knows(&self, id: &str) -> bool {
+        self.known_ids.contains(id)
+    }
+
+    /// Make the next message to `id` the one that creates it
+    pub(crate) fn expect_create(&mut self, id: String) {
+        self.pending_creates.insert(id);
+    }
+
+    /// Record a newly declared participant, taking the next order
+    fn add_participant(&mut self, id: String) {
+        self.join_open_box(&id);
+        self.known_ids.insert(id);
+        self.participant_order += 1;
+    }
+
+    /// Add a participant to the innermost open `box`, if any
+    fn join_open_box(&mut self, id: &str) {
+        if let Some(members) = self.open_box.and_then(|index| self.boxes.get_mut(index))
+            && !members.iter().any(|member| member == id)
+        {
+            members.push(id.to_string());
+        }
+    }
+
+    /// The `autonumber` display number for the next message, advancing the
+    /// numbering
+    ///
+    /// This is only a label: messages are ordered and indexed by
+    /// `sequence_number`, which `msg-N` ids, activation extents, and
+    /// lifecycle markers all use.
+    fn next_message_number(&mut self) -> Option<u32> {
+        match &mut self.autonumber {
+            None => Some(self.sequence_number),
+            Some(numbering) if numbering.stopped => None,
+            Some(numbering) => {
+                let number = numbering.next;
+                numbering.next = numbering.next.saturating_add(numbering.step);
+                Some(number)
+            }
+        }
+    }
+
+    /// Index the next message will take
+    pub(crate) const fn next_message(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Index of the most recent message, where activation bars start and end
+    pub(crate) const fn last_message(&self) -> u32 {
+        self.sequence_number.saturating_sub(1)
+    }
+
+    fn activation_depth(&self, participant: &str) -> usize {
+        self.activations
+            .iter()
+            .filter(|activation| activation.participant == participant)
+            .count()
+    }
+}
+
 /// Parse a `PlantUML` sequence diagram and return events
+///
+/// The first `@startuml ... @enduml` block is located within the input, so
+/// diagrams embedded in markdown or other text parse without trimming.
 pub fn parse(input: &str) -> Result<Vec<GraphEvent>, String> {
+    parse_with_options(input, &ParseOptions::default())
+}
+
+/// Parse a `PlantUML` sequence diagram with custom options and return events
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> Result<Vec<GraphEvent>, String> {
+    let block = find_blocks(input).into_iter().next().unwrap_or(input);
+    parse_block(block, options)
+}
+
+/// Parse every `@startuml ... @enduml` block in the input
+///
+/// Each block produces its own event stream, in source order.
+pub fn parse_all(input: &str) -> Result<Vec<Vec<GraphEvent>>, String> {
+    let blocks = find_blocks(input);
+    if blocks.is_empty() {
+        return parse_block(input, &ParseOptions::default()).map(|events| vec![events]);
+    }
+    blocks
+        .into_iter()
+        .map(|block| parse_block(block, &ParseOptions::default()))
+        .collect()
+}
+
+/// Locate the `@startuml ... @enduml` regions of a larger document
+fn find_blocks(input: &str) -> Vec<&str> {
+    const START: &str = "@startuml";
+    const END: &str = "@enduml";
+
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = input[offset..].find(START).map(|pos| offset + pos) {
+        let Some(end) = input[start..].find(END).map(|pos| start + pos + END.len()) else {
+            break;
+        };
+        blocks.push(&input[start..end]);
+        offset = end;
+    }
+    blocks
+}
+
+fn parse_block(input: &str, options: &ParseOptions) -> Result<Vec<GraphEvent>, String> {
     let mut events = Vec::new();
-    let mut participant_order = 0;
-    let mut sequence_number = 0;
-    let mut participants = HashMap::new(); // alias -> id mapping
-    let mut known_ids = std::collections::HashSet::new(); // track all known IDs
+    let mut state = ParserState::default();
 
     // Start batch
     events.push(GraphEvent::BatchStart);
@@ -34,72 +215,325 @@ pub fn parse(input: &str) -> Result<Vec<GraphEvent>, String> {
         if pair.as_rule() == Rule::plantuml {
             for inner_pair in pair.into_inner() {
                 if inner_pair.as_rule() == Rule::diagram_content {
-                    process_diagram_content(
-                        inner_pair,
-                        &mut events,
-                        &mut participant_order,
-                        &mut sequence_number,
-                        &mut participants,
-                        &mut known_ids,
-                    )?;
+                    process_diagram_content(inner_pair, &mut events, &mut state, options)?;
                 }
             }
         }
     }
 
+    if !state.boxes.is_empty() {
+        keep_boxes_adjacent(&mut events, &state);
+    }
+
     // End batch
     events.push(GraphEvent::BatchEnd);
 
     Ok(events)
 }
 
+/// Parse a `box` and emit it as a container of the participants inside
+fn process_box(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    let index = state.boxes.len();
+    let enclosing = state.open_box.replace(index);
+    state.boxes.push(Vec::new());
+
+    let mut title = None;
+    let mut style = Style::default();
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::identifier => title = Some(extract_identifier(inner)),
+            Rule::participant_color => apply_participant_color(inner, &mut style),
+            Rule::diagram_content => process_diagram_content(inner, events, state, options)?,
+            Rule::block_end => check_block_end(&inner, "box", options)?,
+            _ => {}
+        }
+    }
+    state.open_box = enclosing;
+
+    events.push(GraphEvent::AddGroup {
+        id: format!("box-{index}"),
+        label: title,
+        members: state.boxes[index].clone(),
+        group_type: GroupType::Container,
+        properties: Properties {
+            style: (style != Style::default()).then_some(style),
+            ..Default::default()
+        },
+    });
+    Ok(())
+}
+
+/// Renumber participant orders so each box's members are consecutive
+///
+/// A box takes the place of its earliest member, and participants outside
+/// boxes keep their relative order.
+fn keep_boxes_adjacent(events: &mut [GraphEvent], state: &ParserState) {
+    let mut participants: Vec<(u32, &str)> = events
+        .iter()
+        .filter_map(|event| match event {
+            GraphEvent::AddNode { id, properties, .. } if state.known_ids.contains(id) => {
+                match properties.position {
+                    Some(Position::Sequential { order }) => Some((order, id.as_str())),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    participants.sort_by_key(|(order, _)| *order);
+
+    let box_of = |id: &str| {
+        state
+            .boxes
+            .iter()
+            .position(|members| members.iter().any(|member| member == id))
+    };
+    let mut orders: HashMap<String, u32> = HashMap::new();
+    for (_, id) in participants {
+        let ids =
+            box_of(id).map_or_else(|| vec![id.to_string()], |index| state.boxes[index].clone());
+        for id in ids {
+            let next = u32::try_from(orders.len()).unwrap_or(u32::MAX);
+            orders.entry(id).or_insert(next);
+        }
+    }
+
+    for event in events {
+        if let GraphEvent::AddNode { id, properties, .. } = event
+            && let Some(&order) = orders.get(id)
+            && matches!(properties.position, Some(Position::Sequential { .. }))
+        {
+            properties.position = Some(Position::Sequential { order });
+        }
+    }
+}
+
 fn process_diagram_content(
     pairs: pest::iterators::Pair<Rule>,
     events: &mut Vec<GraphEvent>,
-    participant_order: &mut u32,
-    sequence_number: &mut u32,
-    participants: &mut HashMap<String, String>, // alias -> id mapping
-    known_ids: &mut std::collections::HashSet<String>,
+    state: &mut ParserState,
+    options: &ParseOptions,
 ) -> Result<(), String> {
     for pair in pairs.into_inner() {
         match pair.as_rule() {
             Rule::participant_declaration => {
-                process_participant(pair, events, participant_order, participants, known_ids);
+                process_participant(pair, events, state);
             }
             Rule::message => {
-                process_message(
-                    pair,
-                    events,
-                    participant_order,
-                    sequence_number,
-                    participants,
-                    known_ids,
-                )?;
+                process_message(pair, events, state, options)?;
             }
             Rule::activation => {
-                process_activation(pair, events);
+                process_activation(pair, events, state);
             }
             Rule::deactivation => {
-                process_deactivation(pair, events);
+                process_deactivation(pair, events, state);
+            }
+            Rule::return_statement => {
+                process_return(pair, events, state, options)?;
+            }
+            Rule::destroy_statement => {
+                process_destroy(pair, events, state);
+            }
+            Rule::create_statement => process_create(pair, events, state),
+            Rule::note => process_note(pair, events, state, options)?,
+            Rule::diagram_text => process_diagram_text(pair, events, options)?,
+            Rule::divider | Rule::delay => process_marker(pair, events, state),
+            Rule::autonumber => process_autonumber(pair, state),
+            Rule::box_block => process_box(pair, events, state, options)?,
+            Rule::control_block => {
+                let first_message = state.sequence_number;
+                let first_event = events.len();
+                let fragment = fragment_kind(&pair);
+                if let Some(block) = pair.clone().into_inner().next() {
+                    let keyword = block.as_str().split_whitespace().next().unwrap_or_default();
+                    if let Some(end) = block
+                        .clone()
+                        .into_inner()
+                        .find(|p| p.as_rule() == Rule::block_end)
+                    {
+                        check_block_end(&end, keyword, options)?;
+                    }
+                }
+
+                // Walk nested content in source order so messages inside
+                // fragments keep their position in the sequence
+                process_block_contents(pair, events, state, options)?;
+
+                if let Some((sequence_type, condition)) = fragment {
+                    let members = fragment_members(first_message, &events[first_event..], state);
+                    push_fragment(sequence_type, condition, members, events, state);
+                }
             }
             _ => {
-                // TODO: Handle notes, control blocks, and other rules
+                // Comments and blank lines
             }
         }
     }
     Ok(())
 }
 
-fn process_participant(
+/// Reject an explicit `end X` that names a different construct than the
+/// `block` it closes, when parsing strictly
+fn check_block_end(
+    end: &pest::iterators::Pair<Rule>,
+    block: &str,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    let Some(kind) = end.clone().into_inner().next() else {
+        return Ok(());
+    };
+    let kind = kind.as_str();
+    let is_note = |keyword: &str| matches!(keyword, "note" | "hnote" | "rnote");
+    if options.strict && kind != block && !(is_note(kind) && is_note(block)) {
+        return Err(format!("`end {kind}` closes the `{block}` block"));
+    }
+    Ok(())
+}
+
+/// Process the `diagram_content` sections of a control block in order,
+/// emitting each `alt` block's `else` branch as an `else` group
+fn process_block_contents(
     pair: pest::iterators::Pair<Rule>,
     events: &mut Vec<GraphEvent>,
-    participant_order: &mut u32,
-    participants: &mut HashMap<String, String>,
-    known_ids: &mut std::collections::HashSet<String>,
+    state: &mut ParserState,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::diagram_content => process_diagram_content(inner, events, state, options)?,
+            Rule::else_clause => {
+                let first_message = state.sequence_number;
+                let first_event = events.len();
+                let condition = inner
+                    .clone()
+                    .into_inner()
+                    .find(|branch| branch.as_rule() == Rule::condition)
+                    .map(|condition| condition.as_str().trim().to_string());
+                process_block_contents(inner, events, state, options)?;
+                let members = fragment_members(first_message, &events[first_event..], state);
+                push_fragment("else", condition, members, events, state);
+            }
+            Rule::condition | Rule::block_end => {}
+            _ => process_block_contents(inner, events, state, options)?,
+        }
+    }
+    Ok(())
+}
+
+/// The `sequence_type` and condition of a control block emitted as a group
+fn fragment_kind(pair: &pest::iterators::Pair<Rule>) -> Option<(&'static str, Option<String>)> {
+    let block = pair.clone().into_inner().next()?;
+    let sequence_type = match block.as_rule() {
+        Rule::alt_block => "alt",
+        Rule::opt_block => "opt",
+        Rule::loop_block => "loop",
+        Rule::critical_block => "critical",
+        Rule::break_block => "break",
+        _ => return None,
+    };
+    let condition = block
+        .into_inner()
+        .find(|inner| inner.as_rule() == Rule::condition)
+        .map(|condition| condition.as_str().trim().to_string());
+    Some((sequence_type, condition))
+}
+
+/// Members of a fragment whose content emitted `nested` events: the messages
+/// sent since `first_message`, with those inside a nested fragment replaced
+/// by that fragment's id at the position of its first message
+fn fragment_members(first_message: u32, nested: &[GraphEvent], state: &ParserState) -> Vec<String> {
+    // Innermost fragment holding each message or nested fragment
+    let mut parents: HashMap<&str, &str> = HashMap::new();
+    let mut fragments = Vec::new();
+    for event in nested {
+        if let GraphEvent::AddGroup {
+            id,
+            members,
+            group_type: GroupType::Sequential { .. },
+            ..
+        } = event
+        {
+            for member in members {
+                parents.insert(member, id);
+            }
+            fragments.push(id.as_str());
+        }
+    }
+    let outermost = |message: &str| {
+        let mut id = message.to_string();
+        while let Some(parent) = parents.get(id.as_str()) {
+            id = (*parent).to_string();
+        }
+        id
+    };
+
+    let mut members: Vec<String> = Vec::new();
+    for sequence in first_message..state.sequence_number {
+        let message = format!("msg-{sequence}");
+        let member = outermost(&message);
+        if !members.contains(&member) {
+            members.push(member);
+        }
+    }
+    // Fragments without messages go last
+    for fragment in fragments {
+        if !parents.contains_key(fragment) && !members.iter().any(|m| m == fragment) {
+            members.push(fragment.to_string());
+        }
+    }
+    members
+}
+
+/// Emit a combined fragment group
+///
+/// The condition is the label as written; a bracketed guard such as
+/// `[x > 5]` is also kept without its brackets in `custom["guard"]`.
+fn push_fragment(
+    sequence_type: &str,
+    condition: Option<String>,
+    members: Vec<String>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
 ) {
+    let mut properties = Properties::default();
+    if let Some(guard) = condition
+        .as_deref()
+        .and_then(|c| c.strip_prefix('[')?.strip_suffix(']'))
+    {
+        properties
+            .custom
+            .insert("guard".to_string(), guard.trim().to_string());
+    }
+    events.push(GraphEvent::AddGroup {
+        id: format!("{sequence_type}-{}", state.fragment_count),
+        label: condition,
+        members,
+        group_type: GroupType::Sequential {
+            sequence_type: sequence_type.to_string(),
+        },
+        properties,
+    });
+    state.fragment_count += 1;
+}
+
+/// Emit a declared participant, returning its id
+fn process_participant(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) -> String {
     let mut participant_type = "participant";
     let mut id = String::new();
+    let mut id_is_display_name = false;
     let mut alias = None;
+    let mut alias_is_display_name = false;
+    let mut style = Style::default();
+    let mut stereotype = None;
 
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
@@ -107,25 +541,40 @@ fn process_participant(
                 participant_type = inner_pair.as_str();
             }
             Rule::identifier => {
+                id_is_display_name = is_display_name(&inner_pair);
                 id = extract_identifier(inner_pair);
             }
             Rule::alias => {
                 // Extract the identifier from the alias
                 for alias_inner in inner_pair.into_inner() {
                     if alias_inner.as_rule() == Rule::identifier {
+                        alias_is_display_name = is_display_name(&alias_inner);
                         alias = Some(extract_identifier(alias_inner));
                     }
                 }
             }
+            Rule::participant_color => apply_participant_color(inner_pair, &mut style),
+            Rule::participant_stereotype => {
+                stereotype = Some(inner_pair.as_str()[2..inner_pair.as_str().len() - 2].trim());
+            }
             _ => {}
         }
     }
 
+    // `participant "Web Server" as Web` puts the display name first; the plain
+    // alias is what messages refer to, so it becomes the id
+    if let Some(alias_name) = alias.as_mut()
+        && id_is_display_name
+        && !alias_is_display_name
+    {
+        std::mem::swap(&mut id, alias_name);
+    }
+
     let display_name = alias.clone().unwrap_or_else(|| id.clone());
 
     // Store mapping for message resolution
     if let Some(alias_name) = &alias {
-        participants.insert(alias_name.clone(), id.clone());
+        state.participants.insert(alias_name.clone(), id.clone());
     }
 
     let node_type = match participant_type {
@@ -140,36 +589,138 @@ fn process_participant(
         },
     };
 
-    let properties = Properties {
-        position: Some(Position::Sequential {
-            order: *participant_order,
-        }),
+    let mut properties = Properties {
+        style: (style != Style::default()).then_some(style),
         ..Default::default()
     };
+    if let Some(stereotype) = stereotype {
+        properties
+            .custom
+            .insert("stereotype".to_string(), stereotype.to_string());
+    }
+
+    push_participant(
+        id.clone(),
+        display_name,
+        node_type,
+        properties,
+        events,
+        state,
+    );
+    id
+}
 
+/// Emit a participant in the next lifeline column
+///
+/// A participant already emitted, such as one auto-created by an earlier
+/// message, is declared with an `UpdateNode` instead, keeping its column and
+/// clearing `auto_created`; its type stays as first emitted.
+pub(crate) fn push_participant(
+    id: String,
+    label: String,
+    node_type: NodeType,
+    mut properties: Properties,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    if state.knows(&id) {
+        state.join_open_box(&id);
+        properties
+            .custom
+            .insert("auto_created".to_string(), "false".to_string());
+        events.push(GraphEvent::UpdateNode {
+            id,
+            label: Some(label),
+            properties,
+        });
+        return;
+    }
+    properties.position = Some(Position::Sequential {
+        order: state.participant_order,
+    });
     events.push(GraphEvent::AddNode {
         id: id.clone(),
-        label: Some(display_name),
+        label: Some(label),
         node_type,
         properties,
     });
+    state.add_participant(id);
+}
+
+/// Read a participant's `#body;line:border` colors into its style
+///
+/// The grammar consumes the leading `#`, so hex codes, including all-letter
+/// ones such as `#FFFFFF`, get it back from [`normalize_color`].
+fn apply_participant_color(pair: pest::iterators::Pair<Rule>, style: &mut Style) {
+    for spec in pair.into_inner() {
+        let rule = spec.as_rule();
+        let text = spec.as_str();
+        let value = spec
+            .into_inner()
+            .next()
+            .map(|color| normalize_color(color.as_str()));
+        match rule {
+            Rule::body_color | Rule::back_color => style.background_color = value,
+            Rule::line_color => style.border_color = value,
+            Rule::line_style => {
+                style.border_style = text.strip_prefix("line.").map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Apply an `autonumber` directive to the message numbering
+fn process_autonumber(pair: pest::iterators::Pair<Rule>, state: &mut ParserState) {
+    let numbers = |pair: pest::iterators::Pair<Rule>| -> Vec<u32> {
+        pair.into_inner()
+            .filter_map(|number| number.as_str().parse().ok())
+            .collect()
+    };
+
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::autonumber_stop => {
+                state.autonumber.get_or_insert_default().stopped = true;
+                return;
+            }
+            Rule::autonumber_resume => {
+                let step = numbers(inner).first().copied();
+                let numbering = state.autonumber.get_or_insert_default();
+                numbering.stopped = false;
+                if let Some(step) = step {
+                    numbering.step = step;
+                }
+                return;
+            }
+            Rule::autonumber_start => {
+                let numbers = numbers(inner);
+                state.autonumber = Some(Autonumber {
+                    next: numbers.first().copied().unwrap_or(1),
+                    step: numbers.get(1).copied().unwrap_or(1),
+                    stopped: false,
+                });
+                return;
+            }
+            _ => {}
+        }
+    }
 
-    known_ids.insert(id);
-    *participant_order += 1;
+    // A bare `autonumber` restarts at 1
+    state.autonumber = Some(Autonumber::default());
 }
 
 fn process_message(
     pair: pest::iterators::Pair<Rule>,
     events: &mut Vec<GraphEvent>,
-    participant_order: &mut u32,
-    sequence_number: &mut u32,
-    participants: &HashMap<String, String>,
-    known_ids: &mut std::collections::HashSet<String>,
+    state: &mut ParserState,
+    options: &ParseOptions,
 ) -> Result<(), String> {
     let mut from = String::new();
     let mut to = String::new();
     let mut arrow_str = String::new();
     let mut text = String::new();
+    let mut lifecycle = Vec::new();
 
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
@@ -183,6 +734,12 @@ fn process_message(
             Rule::arrow => {
                 arrow_str = inner_pair.as_str().to_string();
             }
+            Rule::message_lifecycle => {
+                lifecycle = inner_pair
+                    .into_inner()
+                    .map(|token| token.as_str())
+                    .collect();
+            }
             Rule::message_label => {
                 // Extract message text
                 for label_inner in inner_pair.into_inner() {
@@ -196,7 +753,8 @@ fn process_message(
     }
 
     // Parse arrow type
-    let arrow_type = ArrowType::parse_arrow(&arrow_str)
+    let (arrow, arrow_style) = ArrowStyle::split(&arrow_str);
+    let (arrow_type, decorations) = ArrowType::parse_decorated(&arrow)
         .ok_or_else(|| format!("Unknown arrow type: {arrow_str}"))?;
 
     // Handle reversed arrows
@@ -207,157 +765,631 @@ fn process_message(
     };
 
     // Resolve aliases to IDs
-    let from_id = participants
-        .get(&actual_from)
-        .cloned()
-        .unwrap_or_else(|| actual_from.clone());
-    let to_id = participants
-        .get(&actual_to)
-        .cloned()
-        .unwrap_or_else(|| actual_to.clone());
+    let from_id = state.resolve(actual_from.clone());
+    let to_id = state.resolve(actual_to.clone());
 
     // Auto-create participants if not declared
-    if !known_ids.contains(&from_id) {
-        let properties = Properties {
-            position: Some(Position::Sequential {
-                order: *participant_order,
-            }),
-            ..Default::default()
-        };
+    ensure_participant(&from_id, actual_from, events, state);
+    ensure_participant(&to_id, actual_to, events, state);
 
-        events.push(GraphEvent::AddNode {
-            id: from_id.clone(),
-            label: Some(actual_from),
-            node_type: NodeType::Actor {
-                actor_type: "participant".to_string(),
-            },
+    // Create message edge
+    let inferred = if options.infer_message_types {
+        infer_message_type(&text)
+    } else {
+        None
+    };
+    let message_type = match inferred {
+        Some((stripped, message_type)) => {
+            text = stripped;
+            message_type
+        }
+        None => arrow_type.to_message_type(),
+    };
+    // `**` and `!!` create and destroy the target inline
+    if lifecycle.contains(&"**") {
+        state.expect_create(to_id.clone());
+    }
+    let properties = message_properties(&decorations, from_id == to_id, arrow_style);
+    push_message(
+        Message {
+            from: from_id.clone(),
+            to: to_id.clone(),
+            kind: message_type,
+            lost: decorations.contains(&ArrowDecoration::Lost) || lifecycle.contains(&"!!"),
+            label: if text.is_empty() { None } else { Some(text) },
             properties,
-        });
+        },
+        events,
+        state,
+    );
 
-        known_ids.insert(from_id.clone());
-        *participant_order += 1;
+    // Activation bars opened or closed inline start or end at this message
+    for token in lifecycle {
+        match token {
+            "++" => activate(to_id.clone(), events, state),
+            "--" => deactivate(from_id.clone(), events, state),
+            _ => {}
+        }
     }
 
-    if !known_ids.contains(&to_id) {
-        let properties = Properties {
-            position: Some(Position::Sequential {
-                order: *participant_order,
-            }),
-            ..Default::default()
-        };
-
-        events.push(GraphEvent::AddNode {
-            id: to_id.clone(),
-            label: Some(actual_to),
-            node_type: NodeType::Actor {
-                actor_type: "participant".to_string(),
-            },
-            properties,
-        });
+    Ok(())
+}
 
-        known_ids.insert(to_id.clone());
-        *participant_order += 1;
-    }
+/// A message ready to be emitted by [`push_message`]
+pub(crate) struct Message {
+    pub from: String,
+    pub to: String,
+    /// Type drawn by the arrow, before creation and destruction apply
+    pub kind: MessageType,
+    /// Whether the arrow ends in a cross (`->x`), ending the target's lifeline
+    pub lost: bool,
+    pub label: Option<String>,
+    pub properties: Properties,
+}
 
-    // Create message edge
-    let message_type = arrow_type.to_message_type();
+/// Emit a message as the next `msg-N` edge
+///
+/// The first message to a participant awaiting creation becomes
+/// `MessageType::Create` and marks the target `created`; otherwise a lost
+/// message becomes `MessageType::Destroy` and marks it `destroyed`.
+pub(crate) fn push_message(
+    message: Message,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    let Message {
+        from,
+        to,
+        kind,
+        lost,
+        label,
+        properties,
+    } = message;
+    let created = state.pending_creates.remove(&to);
+    let message_type = match (created, lost) {
+        (true, _) => MessageType::Create,
+        (false, true) => MessageType::Destroy,
+        (false, false) => kind,
+    };
     let edge_type = EdgeType::Message {
         message_type,
-        sequence: Some(*sequence_number),
+        sequence: state.next_message_number(),
     };
 
+    state.last_callers.insert(to.clone(), from.clone());
+
     events.push(GraphEvent::AddEdge {
-        id: format!("msg-{sequence_number}"),
-        from: from_id,
-        to: to_id,
+        id: format!("msg-{}", state.sequence_number),
+        from,
+        to: to.clone(),
         edge_type,
-        label: if text.is_empty() { None } else { Some(text) },
-        properties: Properties::default(),
+        label,
+        properties,
     });
 
-    *sequence_number += 1;
+    if created {
+        push_lifecycle_marker("created", to, state.sequence_number, events);
+    } else if lost {
+        push_destruction(to, state.sequence_number, events);
+    }
 
-    Ok(())
+    state.sequence_number += 1;
 }
 
-fn process_activation(pair: pest::iterators::Pair<Rule>, events: &mut Vec<GraphEvent>) {
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::identifier {
-            let id = extract_identifier(inner_pair);
+/// Edge properties recording a message's arrow decorations and inline style
+pub(crate) fn message_properties(
+    decorations: &BTreeSet<ArrowDecoration>,
+    self_message: bool,
+    arrow_style: Option<ArrowStyle>,
+) -> Properties {
+    let mut properties = Properties::default();
+    for decoration in decorations {
+        properties
+            .custom
+            .insert(decoration.custom_key().to_string(), "true".to_string());
+    }
+    // Renderers draw a call to oneself as a loopback
+    if self_message {
+        properties
+            .custom
+            .insert("self_message".to_string(), "true".to_string());
+    }
+    if let Some(arrow_style) = arrow_style {
+        properties.style = Some(Style {
+            color: arrow_style.color,
+            border_style: arrow_style.line_style,
+            border_width: arrow_style.thickness,
+            ..Default::default()
+        });
+    }
+    properties
+}
 
-            // Update node to show activation
-            let mut properties = Properties::default();
-            properties
-                .custom
-                .insert("activated".to_string(), "true".to_string());
+/// Read a `[async]`/`[return]` label suffix into a message type
+fn infer_message_type(text: &str) -> Option<(String, MessageType)> {
+    [
+        ("[async]", MessageType::Asynchronous),
+        ("[return]", MessageType::Return),
+    ]
+    .into_iter()
+    .find_map(|(marker, message_type)| {
+        text.strip_suffix(marker)
+            .map(|stripped| (stripped.trim_end().to_string(), message_type))
+    })
+}
 
-            events.push(GraphEvent::UpdateNode {
-                id,
-                label: None,
-                properties,
-            });
-        }
+/// Emit an auto-created participant for an undeclared id
+pub(crate) fn ensure_participant(
+    id: &str,
+    label: String,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    if state.knows(id) {
+        return;
     }
+    let mut properties = Properties::default();
+    properties
+        .custom
+        .insert("auto_created".to_string(), "true".to_string());
+    let node_type = NodeType::Actor {
+        actor_type: "participant".to_string(),
+    };
+    push_participant(id.to_string(), label, node_type, properties, events, state);
 }
 
-fn process_deactivation(pair: pest::iterators::Pair<Rule>, events: &mut Vec<GraphEvent>) {
+fn process_activation(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
     for inner_pair in pair.into_inner() {
         if inner_pair.as_rule() == Rule::identifier {
-            let id = extract_identifier(inner_pair);
-
-            // Update node to show deactivation
-            let mut properties = Properties::default();
-            properties
-                .custom
-                .insert("activated".to_string(), "false".to_string());
-
-            events.push(GraphEvent::UpdateNode {
-                id,
-                label: None,
-                properties,
-            });
+            let id = state.resolve(extract_identifier(inner_pair));
+            activate(id, events, state);
         }
     }
 }
 
-fn extract_identifier(pair: pest::iterators::Pair<Rule>) -> String {
-    match pair.as_rule() {
-        Rule::identifier => {
-            // Check if it's a quoted string or simple identifier
-            for inner in pair.into_inner() {
-                match inner.as_rule() {
-                    Rule::quoted_string => {
-                        return extract_quoted_string(inner).unwrap_or_default();
-                    }
-                    Rule::simple_identifier => {
-                        return inner.as_str().to_string();
-                    }
-                    _ => {}
-                }
-            }
-            String::new()
+fn process_deactivation(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() == Rule::identifier {
+            let id = state.resolve(extract_identifier(inner_pair));
+            deactivate(id, events, state);
         }
-        Rule::quoted_string => extract_quoted_string(pair).unwrap_or_default(),
-        _ => pair.as_str().to_string(),
     }
 }
 
-fn extract_quoted_string(pair: pest::iterators::Pair<Rule>) -> Option<String> {
-    for inner in pair.into_inner() {
-        if inner.as_rule() == Rule::inner_string {
-            return Some(inner.as_str().to_string());
-        }
-    }
-    None
+/// Open an activation bar on `id` at the most recent message
+pub(crate) fn activate(id: String, events: &mut Vec<GraphEvent>, state: &mut ParserState) {
+    let caller = state.last_callers.get(&id).cloned();
+    let start = state.last_message();
+    state.activations.push(Activation {
+        participant: id.clone(),
+        caller,
+        start,
+    });
+    push_activation_update(id, Some(start), None, events, state);
 }
 
-// ArrowType implementation is in plantuml/types.rs
+/// Close the innermost activation bar on `id` at the most recent message
+pub(crate) fn deactivate(id: String, events: &mut Vec<GraphEvent>, state: &mut ParserState) {
+    let start = state
+        .activations
+        .iter()
+        .rposition(|activation| activation.participant == id)
+        .map(|index| state.activations.remove(index).start);
+    let end = start.map(|_| state.last_message());
+    push_activation_update(id, start, end, events, state);
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Handle `return`: a reply from the innermost activation back to its caller
+fn process_return(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    let text = pair
+        .into_inner()
+        .find(|inner| inner.as_rule() == Rule::return_text)
+        .map(|inner| inner.as_str().trim().to_string())
+        .unwrap_or_default();
 
-    #[test]
+    let Some(activation) = state.activations.pop() else {
+        return Err("`return` with no active participant".to_string());
+    };
+
+    // A bar opened before its participant received any message has no
+    // caller; the reply is still drawn, back onto the participant itself
+    let mut properties = Properties::default();
+    let caller = match activation.caller {
+        Some(caller) => caller,
+        None if options.strict => {
+            return Err(format!(
+                "`return` from {} has no caller to return to",
+                activation.participant
+            ));
+        }
+        None => {
+            properties
+                .custom
+                .insert("unknown_caller".to_string(), "true".to_string());
+            activation.participant.clone()
+        }
+    };
+    push_message(
+        Message {
+            from: activation.participant.clone(),
+            to: caller,
+            kind: MessageType::Return,
+            lost: false,
+            label: if text.is_empty() { None } else { Some(text) },
+            properties,
+        },
+        events,
+        state,
+    );
+
+    let end = state.last_message();
+    push_activation_update(
+        activation.participant,
+        Some(activation.start),
+        Some(end),
+        events,
+        state,
+    );
+    Ok(())
+}
+
+/// Handle `destroy A`: end the lifeline at the most recent message
+fn process_destroy(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &ParserState,
+) {
+    for inner_pair in pair.into_inner() {
+        if inner_pair.as_rule() == Rule::identifier {
+            let id = state.resolve(extract_identifier(inner_pair));
+            let sequence = state.sequence_number.saturating_sub(1);
+
+            // A message to the participant just before `destroy` is the
+            // one that destroys it
+            let last_message = format!("msg-{sequence}");
+            let destroying = events.iter_mut().rev().find_map(|event| match event {
+                GraphEvent::AddEdge {
+                    id: edge_id,
+                    to,
+                    edge_type: EdgeType::Message { message_type, .. },
+                    ..
+                } if *edge_id == last_message && *to == id => Some(message_type),
+                _ => None,
+            });
+            if let Some(message_type) = destroying {
+                *message_type = MessageType::Destroy;
+            }
+
+            push_destruction(id, sequence, events);
+        }
+    }
+}
+
+/// Declare a participant with `create`, so the next message to it is
+/// emitted as `MessageType::Create`
+fn process_create(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    let id = process_participant(pair, events, state);
+    state.pending_creates.insert(id);
+}
+
+/// Emit a note as a `note` node placed before the next message
+///
+/// Its `Position::Sequential` order is that message's index, not a lifeline
+/// column as for participants. The spanned participants are listed in `custom["participants"]`, comma
+/// separated, and the keyword picks `custom["note_shape"]`: `note`,
+/// `hexagon` for `hnote`, or `rectangle` for `rnote`.
+fn process_note(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    let mut shape = "note";
+    let mut side = "";
+    let mut participants = Vec::new();
+    let mut text = String::new();
+
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::note_position => {
+                for part in inner_pair.into_inner() {
+                    match part.as_rule() {
+                        Rule::note_keyword => {
+                            shape = match part.as_str() {
+                                "hnote" => "hexagon",
+                                "rnote" => "rectangle",
+                                _ => "note",
+                            };
+                        }
+                        Rule::note_side => side = part.as_str(),
+                        Rule::identifier => {
+                            participants.push(state.resolve(extract_identifier(part)));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Rule::note_text => text = inner_pair.as_str().trim().to_string(),
+            Rule::note_body => {
+                let lines: Vec<&str> = inner_pair.as_str().lines().map(str::trim).collect();
+                text = lines.join("\n").trim().to_string();
+            }
+            Rule::block_end => check_block_end(&inner_pair, "note", options)?,
+            _ => {}
+        }
+    }
+
+    let mut properties = Properties {
+        position: Some(Position::Sequential {
+            order: state.sequence_number,
+        }),
+        ..Default::default()
+    };
+    for (key, value) in [
+        ("note_shape", shape.to_string()),
+        ("note_position", side.to_string()),
+        ("participants", participants.join(",")),
+    ] {
+        properties.custom.insert(key.to_string(), value);
+    }
+
+    events.push(GraphEvent::AddNode {
+        id: format!("note-{}", state.note_count),
+        label: Some(text),
+        node_type: NodeType::Custom("note".to_string()),
+        properties,
+    });
+    state.note_count += 1;
+    Ok(())
+}
+
+/// Handle `title`, `header`, and `footer`
+///
+/// The title names the graph through `SetGraphMeta`; headers and footers are
+/// recorded on the diagram's `SetLayout`, with any `left`/`center`/`right`
+/// alignment as `header_align`/`footer_align`.
+fn process_diagram_text(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    options: &ParseOptions,
+) -> Result<(), String> {
+    let mut keyword = "";
+    let mut alignment = None;
+    let mut text = String::new();
+
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::text_alignment => alignment = Some(inner_pair.as_str().to_string()),
+            Rule::diagram_text_keyword => keyword = inner_pair.as_str(),
+            Rule::diagram_text_line => text = inner_pair.as_str().trim().to_string(),
+            Rule::diagram_text_body => {
+                let lines: Vec<&str> = inner_pair.as_str().lines().map(str::trim).collect();
+                text = lines.join("\n").trim().to_string();
+            }
+            Rule::block_end => check_block_end(&inner_pair, keyword, options)?,
+            _ => {}
+        }
+    }
+
+    if keyword == "title" {
+        events.push(GraphEvent::SetGraphMeta {
+            name: Some(text),
+            directed: true,
+            strict: false,
+        });
+        return Ok(());
+    }
+
+    let layout = events.iter_mut().find_map(|event| match event {
+        GraphEvent::SetLayout { properties, .. } => Some(properties),
+        _ => None,
+    });
+    if let Some(properties) = layout {
+        properties.custom.insert(keyword.to_string(), text);
+        if let Some(alignment) = alignment {
+            properties
+                .custom
+                .insert(format!("{keyword}_align"), alignment);
+        }
+    }
+    Ok(())
+}
+
+/// Remove the creole tags `PlantUML` styles text with: `<b>`, `<i>`, `<u>`,
+/// `<color:...>`, `<size:...>`, and their closing forms
+///
+/// Any other `<` and all entities are left as written.
+fn strip_creole(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        plain.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if let Some(len) = creole_tag_len(after) {
+            rest = &after[len..];
+            continue;
+        }
+        plain.push('<');
+        rest = &after[1..];
+    }
+    plain.push_str(rest);
+    plain
+}
+
+/// Length of the creole tag at the start of `text`, if it begins with one
+fn creole_tag_len(text: &str) -> Option<usize> {
+    let end = text.find('>')?;
+    let inner = &text[1..end];
+    let tag = inner
+        .strip_prefix('/')
+        .unwrap_or(inner)
+        .to_ascii_lowercase();
+    let known = match tag.split_once(':') {
+        None => matches!(tag.as_str(), "b" | "i" | "u" | "color" | "size"),
+        Some((name, value)) => matches!(name, "color" | "size") && !value.contains('<'),
+    };
+    known.then_some(end + 1)
+}
+
+/// Emit a `==` divider or `...` delay as a node ordered before the next message
+///
+/// Text with `<b>` or `<color:red>` style markup is labelled with the plain
+/// text and keeps the raw markup in `custom["markup"]`.
+fn process_marker(
+    pair: pest::iterators::Pair<Rule>,
+    events: &mut Vec<GraphEvent>,
+    state: &mut ParserState,
+) {
+    let kind = if pair.as_rule() == Rule::divider {
+        "divider"
+    } else {
+        "delay"
+    };
+    let markup = pair
+        .into_inner()
+        .find(|p| matches!(p.as_rule(), Rule::divider_text | Rule::delay_text))
+        .map(|p| p.as_str().trim().to_string())
+        .filter(|text| !text.is_empty());
+    let text = markup.as_deref().map(strip_creole);
+
+    let mut properties = Properties {
+        position: Some(Position::Sequential {
+            order: state.sequence_number,
+        }),
+        ..Default::default()
+    };
+    if let Some(markup) = markup.filter(|markup| Some(markup) != text.as_ref()) {
+        properties.custom.insert("markup".to_string(), markup);
+    }
+
+    events.push(GraphEvent::AddNode {
+        id: format!("{kind}-{}", state.marker_count),
+        label: text,
+        node_type: NodeType::Custom(kind.to_string()),
+        properties,
+    });
+    state.marker_count += 1;
+}
+
+/// Mark a participant's lifeline as ending at `sequence`
+pub(crate) fn push_destruction(id: String, sequence: u32, events: &mut Vec<GraphEvent>) {
+    push_lifecycle_marker("destroyed", id, sequence, events);
+}
+
+/// Mark a participant `created` or `destroyed`, with the message index in
+/// `created_at`/`destroyed_at`
+fn push_lifecycle_marker(event: &str, id: String, sequence: u32, events: &mut Vec<GraphEvent>) {
+    let mut properties = Properties::default();
+    properties
+        .custom
+        .insert(event.to_string(), "true".to_string());
+    properties
+        .custom
+        .insert(format!("{event}_at"), sequence.to_string());
+
+    events.push(GraphEvent::UpdateNode {
+        id,
+        label: None,
+        properties,
+    });
+}
+
+/// Report a participant's current activation depth, with the message indices
+/// at which the bar being opened or closed starts and ends
+fn push_activation_update(
+    id: String,
+    start: Option<u32>,
+    end: Option<u32>,
+    events: &mut Vec<GraphEvent>,
+    state: &ParserState,
+) {
+    let depth = state.activation_depth(&id);
+
+    let mut properties = Properties::default();
+    properties
+        .custom
+        .insert("activated".to_string(), (depth > 0).to_string());
+    properties
+        .custom
+        .insert("activation_depth".to_string(), depth.to_string());
+    for (key, index) in [("activation_start", start), ("activation_end", end)] {
+        if let Some(index) = index {
+            properties.custom.insert(key.to_string(), index.to_string());
+        }
+    }
+
+    events.push(GraphEvent::UpdateNode {
+        id,
+        label: None,
+        properties,
+    });
+}
+
+fn extract_identifier(pair: pest::iterators::Pair<Rule>) -> String {
+    match pair.as_rule() {
+        Rule::identifier => {
+            // Check if it's a quoted string or simple identifier
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::quoted_string | Rule::bracketed_string => {
+                        return extract_quoted_string(inner).unwrap_or_default();
+                    }
+                    Rule::simple_identifier => {
+                        return inner.as_str().to_string();
+                    }
+                    _ => {}
+                }
+            }
+            String::new()
+        }
+        Rule::quoted_string => extract_quoted_string(pair).unwrap_or_default(),
+        _ => pair.as_str().to_string(),
+    }
+}
+
+/// Whether an identifier is a quoted or bracketed name that may contain spaces
+fn is_display_name(pair: &pest::iterators::Pair<Rule>) -> bool {
+    pair.clone().into_inner().any(|inner| {
+        matches!(
+            inner.as_rule(),
+            Rule::quoted_string | Rule::bracketed_string
+        )
+    })
+}
+
+fn extract_quoted_string(pair: pest::iterators::Pair<Rule>) -> Option<String> {
+    for inner in pair.into_inner() {
+        if matches!(inner.as_rule(), Rule::inner_string | Rule::inner_bracketed) {
+            return Some(inner.as_str().to_string());
+        }
+    }
+    None
+}
+
+// ArrowType implementation is in plantuml/types.rs
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
     fn test_simple_sequence_to_events() {
         let input = r"@startuml
 participant A
@@ -453,4 +1485,1338 @@ A -> B: Hello Bob
             assert_eq!(label.as_deref(), Some("Alice"));
         }
     }
+
+    #[test]
+    fn test_diagram_embedded_in_markdown() {
+        let input = r"# Login flow
+
+Some prose describing the diagram.
+
+```plantuml
+@startuml
+participant A
+participant B
+A -> B: Hello
+@enduml
+```
+
+More text after the fence.
+";
+
+        let events = parse(input).unwrap();
+
+        let node_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
+            .count();
+        assert_eq!(node_count, 2);
+    }
+
+    #[test]
+    fn test_parse_all_multiple_blocks() {
+        let input = r"```plantuml
+@startuml
+A -> B: first
+@enduml
+```
+
+```plantuml
+@startuml
+C -> D: second
+D -> E: third
+@enduml
+```
+";
+
+        let streams = parse_all(input).unwrap();
+        assert_eq!(streams.len(), 2);
+
+        let message_counts: Vec<usize> = streams
+            .iter()
+            .map(|events| {
+                events
+                    .iter()
+                    .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+                    .count()
+            })
+            .collect();
+        assert_eq!(message_counts, vec![1, 2]);
+    }
+
+    fn message_types(events: &[GraphEvent]) -> Vec<(Option<String>, MessageType)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    label,
+                    edge_type: EdgeType::Message { message_type, .. },
+                    ..
+                } => Some((label.clone(), message_type.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_infer_message_type_from_label() {
+        let input = r"@startuml
+A -> B: publish event [async]
+B -> A: ack [return]
+A -> B: plain call
+@enduml";
+
+        let options = ParseOptions {
+            infer_message_types: true,
+            ..Default::default()
+        };
+        let events = parse_with_options(input, &options).unwrap();
+
+        assert_eq!(
+            message_types(&events),
+            vec![
+                (Some("publish event".to_string()), MessageType::Asynchronous),
+                (Some("ack".to_string()), MessageType::Return),
+                (Some("plain call".to_string()), MessageType::Synchronous),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_inference_disabled_by_default() {
+        let input = r"@startuml
+A -> B: publish event [async]
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            message_types(&events),
+            vec![(
+                Some("publish event [async]".to_string()),
+                MessageType::Synchronous
+            )]
+        );
+    }
+
+    fn node_label<'a>(events: &'a [GraphEvent], node_id: &str) -> Option<&'a str> {
+        events.iter().find_map(|e| match e {
+            GraphEvent::AddNode { id, label, .. } if id == node_id => label.as_deref(),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_spaced_display_names() {
+        let input = r#"@startuml
+participant "Web Server" as W
+participant [Auth Service] as Auth
+database DB as "Primary Store"
+W -> Auth: Validate
+W -> DB: Query
+@enduml"#;
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(node_label(&events, "W"), Some("Web Server"));
+        assert_eq!(node_label(&events, "Auth"), Some("Auth Service"));
+        assert_eq!(node_label(&events, "DB"), Some("Primary Store"));
+
+        // Messages resolve to the declared participants without auto-creating
+        let node_count = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddNode { .. }))
+            .count();
+        assert_eq!(node_count, 3);
+    }
+
+    #[test]
+    fn test_participant_body_and_border_colors() {
+        let input = r"@startuml
+participant Web as W #lightblue;line:red;line.dashed
+database DB #FF9900
+actor User
+participant A #FFFFFF
+participant B #ABC
+W -> DB: Query
+@enduml";
+
+        let events = parse(input).unwrap();
+        let style = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => {
+                    Some(properties.style.clone())
+                }
+                _ => None,
+            })
+        };
+
+        let web = style("Web").flatten().unwrap();
+        assert_eq!(web.background_color.as_deref(), Some("lightblue"));
+        assert_eq!(web.border_color.as_deref(), Some("red"));
+        assert_eq!(web.border_style.as_deref(), Some("dashed"));
+
+        let db = style("DB").flatten().unwrap();
+        assert_eq!(db.background_color.as_deref(), Some("#FF9900"));
+        assert_eq!(db.border_color, None);
+
+        assert_eq!(style("User"), Some(None));
+        for (id, color) in [("A", "#FFFFFF"), ("B", "#ABC")] {
+            let style = style(id).flatten().unwrap();
+            assert_eq!(style.background_color.as_deref(), Some(color));
+        }
+    }
+
+    #[test]
+    fn test_participant_stereotypes() {
+        let input = r#"@startuml
+participant "DB" as D << (D,#ADD1B2) >>
+actor User #Yellow
+participant API <<service>> #LightGreen
+User -> API: request
+API -> D: query
+@enduml"#;
+
+        let events = parse(input).unwrap();
+        let properties = |node_id: &str| {
+            events.iter().find_map(|e| match e {
+                GraphEvent::AddNode { id, properties, .. } if id == node_id => Some(properties),
+                _ => None,
+            })
+        };
+        let stereotype =
+            |node_id: &str| properties(node_id).and_then(|p| p.custom.get("stereotype").cloned());
+        let color = |node_id: &str| {
+            properties(node_id)
+                .and_then(|p| p.style.as_ref())
+                .and_then(|style| style.background_color.clone())
+        };
+
+        assert_eq!(node_label(&events, "D"), Some("DB"));
+        assert_eq!(stereotype("D").as_deref(), Some("(D,#ADD1B2)"));
+        assert_eq!(color("D"), None);
+
+        assert_eq!(stereotype("User"), None);
+        assert_eq!(color("User").as_deref(), Some("Yellow"));
+
+        assert_eq!(stereotype("API").as_deref(), Some("service"));
+        assert_eq!(color("API").as_deref(), Some("LightGreen"));
+
+        let edges: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { from, to, .. } => Some((from.as_str(), to.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edges, vec![("User", "API"), ("API", "D")]);
+    }
+
+    #[test]
+    fn test_divider_inside_alt_keeps_sequence_order() {
+        let input = r"@startuml
+A -> B: before
+alt success
+B -> C: inside
+== Checkpoint ==
+C --> B: still inside
+else failure
+B --> A: error
+end
+A -> C: after
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let messages: Vec<(&str, Option<u32>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    label: Some(label),
+                    edge_type: EdgeType::Message { sequence, .. },
+                    ..
+                } => Some((label.as_str(), *sequence)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("before", Some(0)),
+                ("inside", Some(1)),
+                ("still inside", Some(2)),
+                ("error", Some(3)),
+                ("after", Some(4)),
+            ]
+        );
+
+        // The divider is emitted between the messages around it, ordered
+        // before the next one, inside the `alt`'s first branch
+        let ids: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. }
+                | GraphEvent::AddEdge { id, .. }
+                | GraphEvent::AddGroup { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .filter(|id| !matches!(*id, "A" | "B" | "C"))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "msg-0",
+                "msg-1",
+                "divider-0",
+                "msg-2",
+                "msg-3",
+                "else-0",
+                "alt-1",
+                "msg-4"
+            ]
+        );
+        let divider_order = events.iter().find_map(|e| match e {
+            GraphEvent::AddNode { id, properties, .. } if id == "divider-0" => {
+                properties.position.clone()
+            }
+            _ => None,
+        });
+        assert_eq!(divider_order, Some(Position::Sequential { order: 2 }));
+        let alt_members = events.iter().find_map(|e| match e {
+            GraphEvent::AddGroup { id, members, .. } if id == "alt-1" => Some(members.clone()),
+            _ => None,
+        });
+        assert_eq!(
+            alt_members,
+            Some(vec![
+                "msg-1".to_string(),
+                "msg-2".to_string(),
+                "else-0".to_string()
+            ])
+        );
+    }
+
+    fn message_flags(events: &[GraphEvent]) -> Vec<Vec<&str>> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { properties, .. } => {
+                    let mut keys: Vec<&str> =
+                        properties.custom.keys().map(String::as_str).collect();
+                    keys.sort_unstable();
+                    Some(keys)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arrow_decorations() {
+        let input = r"@startuml
+A o-> B: tail circle
+A ->o B: head circle
+A ->x B: lost
+A o->o B: both
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            message_flags(&events),
+            vec![
+                vec!["tail_circle"],
+                vec!["head_circle"],
+                vec!["lost"],
+                vec!["head_circle", "tail_circle"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_colored_arrows() {
+        let input = r"@startuml
+A -[#red]> B: alert
+B -[#0000FF,dashed]-> A: reply
+A <[#green]- B: back
+A -[#FFFFFF]> B: white
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let messages: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    from,
+                    to,
+                    edge_type: EdgeType::Message { message_type, .. },
+                    properties,
+                    ..
+                } => {
+                    let style = properties.style.clone().unwrap_or_default();
+                    Some((
+                        from.as_str(),
+                        to.as_str(),
+                        message_type.clone(),
+                        style.color,
+                        style.border_style,
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    "A",
+                    "B",
+                    MessageType::Synchronous,
+                    Some("red".to_string()),
+                    None
+                ),
+                (
+                    "B",
+                    "A",
+                    MessageType::Return,
+                    Some("#0000FF".to_string()),
+                    Some("dashed".to_string())
+                ),
+                (
+                    "B",
+                    "A",
+                    MessageType::Synchronous,
+                    Some("green".to_string()),
+                    None
+                ),
+                (
+                    "A",
+                    "B",
+                    MessageType::Synchronous,
+                    Some("#FFFFFF".to_string()),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_character_arrows() {
+        let input = r"@startuml
+A -->> B: dashed async
+A <-> B: both ways
+B <<-- A: left dashed async
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            message_types(&events),
+            vec![
+                (Some("dashed async".to_string()), MessageType::Return),
+                (Some("both ways".to_string()), MessageType::Synchronous),
+                (Some("left dashed async".to_string()), MessageType::Return),
+            ]
+        );
+    }
+
+    /// Last reported activation depth of each participant
+    fn activation_depths(events: &[GraphEvent]) -> HashMap<&str, &str> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::UpdateNode { id, properties, .. } => properties
+                    .custom
+                    .get("activation_depth")
+                    .map(|depth| (id.as_str(), depth.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_activation_shorthand() {
+        let input = r"@startuml
+A -> B ++: start
+B -> C **: make
+B --> A --: done
+A -> C !!: stop
+A -> B ++ : again
+B -> A --++ : handoff
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            message_types(&events),
+            vec![
+                (Some("start".to_string()), MessageType::Synchronous),
+                (Some("make".to_string()), MessageType::Create),
+                (Some("done".to_string()), MessageType::Return),
+                (Some("stop".to_string()), MessageType::Destroy),
+                (Some("again".to_string()), MessageType::Synchronous),
+                (Some("handoff".to_string()), MessageType::Synchronous),
+            ]
+        );
+
+        let bars: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::UpdateNode { id, properties, .. } => {
+                    let get = |key: &str| properties.custom.get(key).map(String::as_str);
+                    get("activation_depth").map(|depth| {
+                        (
+                            id.as_str(),
+                            depth,
+                            get("activation_start"),
+                            get("activation_end"),
+                        )
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            bars,
+            vec![
+                ("B", "1", Some("0"), None),
+                ("B", "0", Some("0"), Some("2")),
+                ("B", "1", Some("4"), None),
+                ("B", "0", Some("4"), Some("5")),
+                ("A", "1", Some("5"), None),
+            ]
+        );
+
+        let lifecycle: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::UpdateNode { id, properties, .. } => {
+                    ["created_at", "destroyed_at"].into_iter().find_map(|key| {
+                        properties
+                            .custom
+                            .get(key)
+                            .map(|at| (id.as_str(), key, at.as_str()))
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            lifecycle,
+            vec![("C", "created_at", "1"), ("C", "destroyed_at", "3")]
+        );
+    }
+
+    #[test]
+    fn test_activation_bar_extents() {
+        let input = r"@startuml
+A -> B: one
+activate B
+B -> B: self
+activate B
+B -> C: work
+deactivate B
+B --> A: done
+deactivate B
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let bars: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::UpdateNode { id, properties, .. } if id == "B" => {
+                    let get = |key: &str| properties.custom.get(key).map(String::as_str);
+                    Some((
+                        get("activation_depth"),
+                        get("activation_start"),
+                        get("activation_end"),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            bars,
+            vec![
+                (Some("1"), Some("0"), None),
+                (Some("2"), Some("1"), None),
+                (Some("1"), Some("1"), Some("2")),
+                (Some("0"), Some("0"), Some("3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_return_unwinds_activations() {
+        let input = r"@startuml
+participant Client
+participant Server
+participant DB
+Client -> Server: request
+activate Server
+Server -> DB: query
+activate DB
+return rows
+Server -> Server: render
+activate Server
+return
+return response
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let returns: Vec<(&str, &str, Option<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    from,
+                    to,
+                    label,
+                    edge_type:
+                        EdgeType::Message {
+                            message_type: MessageType::Return,
+                            ..
+                        },
+                    ..
+                } => Some((from.as_str(), to.as_str(), label.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            returns,
+            vec![
+                ("DB", "Server", Some("rows")),
+                ("Server", "Server", None),
+                ("Server", "Client", Some("response")),
+            ]
+        );
+
+        let depths = activation_depths(&events);
+        assert_eq!(depths.get("Server"), Some(&"0"));
+        assert_eq!(depths.get("DB"), Some(&"0"));
+    }
+
+    #[test]
+    fn test_unmatched_return() {
+        let input = r"@startuml
+A -> B: hello
+return nothing
+@enduml";
+
+        let error = parse(input).unwrap_err();
+        assert!(
+            error.contains("`return` with no active participant"),
+            "{error}"
+        );
+
+        // `deactivate` pops the activation, leaving nothing to return from
+        let deactivated = r"@startuml
+A -> B: hello
+activate B
+deactivate B
+return late
+@enduml";
+        assert!(parse(deactivated).is_err());
+
+        // A bar with no caller still replies, flagged, onto itself
+        let uncalled = r"@startuml
+participant A
+activate A
+A -> B: work
+return done
+@enduml";
+        let events = parse(uncalled).unwrap();
+        let reply = events.iter().find_map(|e| match e {
+            GraphEvent::AddEdge {
+                from,
+                to,
+                label,
+                edge_type:
+                    EdgeType::Message {
+                        message_type: MessageType::Return,
+                        ..
+                    },
+                properties,
+                ..
+            } => Some((
+                from.as_str(),
+                to.as_str(),
+                label.as_deref(),
+                properties.custom.get("unknown_caller").map(String::as_str),
+            )),
+            _ => None,
+        });
+        assert_eq!(reply, Some(("A", "A", Some("done"), Some("true"))));
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let error = parse_with_options(uncalled, &strict).unwrap_err();
+        assert!(error.contains("has no caller"), "{error}");
+    }
+
+    /// Index and `destroyed_at` of each destruction marker
+    fn destructions(events: &[GraphEvent]) -> Vec<(usize, &str, &str)> {
+        events
+            .iter()
+            .enumerate()
+            .filter_map(|(index, e)| match e {
+                GraphEvent::UpdateNode { id, properties, .. } => properties
+                    .custom
+                    .get("destroyed_at")
+                    .map(|at| (index, id.as_str(), at.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lifeline_destruction_markers() {
+        let input = r"@startuml
+A -> B: create
+B -> C: work
+destroy C
+A ->x B: stop
+A -> A: continue
+@enduml";
+
+        let events = parse(input).unwrap();
+        let position = |edge_id: &str| {
+            events
+                .iter()
+                .position(|e| matches!(e, GraphEvent::AddEdge { id, .. } if id == edge_id))
+                .unwrap()
+        };
+
+        let markers = destructions(&events);
+        assert_eq!(markers.len(), 2);
+
+        let (index, id, at) = markers[0];
+        assert_eq!((id, at), ("C", "1"));
+        assert!(position("msg-1") < index && index < position("msg-2"));
+
+        let (index, id, at) = markers[1];
+        assert_eq!((id, at), ("B", "2"));
+        assert!(position("msg-2") < index && index < position("msg-3"));
+    }
+
+    #[test]
+    fn test_self_message() {
+        let input = r"@startuml
+A -> A: process
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let nodes: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nodes, vec!["A"]);
+
+        let edges: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    from,
+                    to,
+                    properties,
+                    ..
+                } => Some((
+                    from.as_str(),
+                    to.as_str(),
+                    properties.custom.get("self_message").map(String::as_str),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edges, vec![("A", "A", Some("true"))]);
+    }
+
+    #[test]
+    fn test_create_and_destroy_messages() {
+        let input = r"@startuml
+participant Client
+Client -> Server: connect
+create control Session
+Server -> Session: new
+Session -> Server: ready
+Server -> Session: close
+destroy Session
+Client ->x Server: kill
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let message_types: Vec<MessageType> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    edge_type: EdgeType::Message { message_type, .. },
+                    ..
+                } => Some(message_type.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            message_types,
+            vec![
+                MessageType::Synchronous,
+                MessageType::Create,
+                MessageType::Synchronous,
+                MessageType::Destroy,
+                MessageType::Destroy,
+            ]
+        );
+
+        let markers: Vec<(&str, &str, &str)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::UpdateNode { id, properties, .. } => {
+                    let (key, at) = ["created_at", "destroyed_at"]
+                        .into_iter()
+                        .find_map(|key| Some((key, properties.custom.get(key)?)))?;
+                    Some((id.as_str(), key, at.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                ("Session", "created_at", "1"),
+                ("Session", "destroyed_at", "3"),
+                ("Server", "destroyed_at", "4"),
+            ]
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddNode { id, node_type: NodeType::Process, .. } if id == "Session"
+        )));
+    }
+
+    /// `(id, sequence_type, label, members)` of each emitted group
+    fn fragments(events: &[GraphEvent]) -> Vec<(&str, &str, Option<&str>, Vec<&str>)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddGroup {
+                    id,
+                    label,
+                    members,
+                    group_type: GroupType::Sequential { sequence_type },
+                    ..
+                } => Some((
+                    id.as_str(),
+                    sequence_type.as_str(),
+                    label.as_deref(),
+                    members.iter().map(String::as_str).collect(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_critical_block_group() {
+        let input = r"@startuml
+A -> B: prepare
+critical commit
+B -> C: write
+C --> B: ack
+end
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            fragments(&events),
+            vec![(
+                "critical-0",
+                "critical",
+                Some("commit"),
+                vec!["msg-1", "msg-2"]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_alt_else_and_nested_fragments() {
+        let input = r"@startuml
+A -> B: login
+alt valid credentials
+B --> A: token
+loop every minute
+A -> B: refresh
+end
+else invalid
+B --> A: denied
+end
+opt audit
+B -> C: log
+end
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            fragments(&events),
+            vec![
+                ("loop-0", "loop", Some("every minute"), vec!["msg-2"]),
+                ("else-1", "else", Some("invalid"), vec!["msg-3"]),
+                (
+                    "alt-2",
+                    "alt",
+                    Some("valid credentials"),
+                    vec!["msg-1", "loop-0", "else-1"]
+                ),
+                ("opt-3", "opt", Some("audit"), vec!["msg-4"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bracketed_alt_guard() {
+        let input = r"@startuml
+alt [x > 5]
+A -> B: big
+else [x < 0]
+A -> B: negative
+else
+A -> B: small
+end
+loop [ i < 10 ]
+A -> B: tick
+end
+opt cached
+B --> A: hit
+end
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let guards: Vec<(&str, Option<&str>, Option<&str>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddGroup {
+                    id,
+                    label,
+                    properties,
+                    ..
+                } => Some((
+                    id.as_str(),
+                    label.as_deref(),
+                    properties.custom.get("guard").map(String::as_str),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            guards,
+            vec![
+                ("else-0", Some("[x < 0]"), Some("x < 0")),
+                ("else-1", None, None),
+                ("alt-2", Some("[x > 5]"), Some("x > 5")),
+                ("loop-3", Some("[ i < 10 ]"), Some("i < 10")),
+                ("opt-4", Some("cached"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_autonumber_directives() {
+        let input = r#"@startuml
+A -> B: before
+autonumber 10 5
+A -> B: first
+B --> A: second
+autonumber stop
+A -> B: unnumbered
+autonumber resume 2
+A -> B: resumed
+autonumber "<b>[000]"
+B -> A: restarted
+@enduml"#;
+
+        let events = parse(input).unwrap();
+
+        let sequences: Vec<(&str, Option<u32>)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge {
+                    id,
+                    edge_type: EdgeType::Message { sequence, .. },
+                    ..
+                } => Some((id.as_str(), *sequence)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            sequences,
+            vec![
+                ("msg-0", Some(0)),
+                ("msg-1", Some(10)),
+                ("msg-2", Some(15)),
+                ("msg-3", None),
+                ("msg-4", Some(20)),
+                ("msg-5", Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_box_members_have_consecutive_orders() {
+        let input = r#"@startuml
+A -> C: early
+participant D
+box "Internal"
+participant B
+participant C
+end box
+D -> A: later
+@enduml"#;
+
+        let events = parse(input).unwrap();
+
+        let mut orders: Vec<(&str, u32)> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    properties:
+                        Properties {
+                            position: Some(Position::Sequential { order }),
+                            ..
+                        },
+                    ..
+                } => Some((id.as_str(), *order)),
+                _ => None,
+            })
+            .collect();
+        orders.sort_by_key(|(_, order)| *order);
+        assert_eq!(orders, vec![("A", 0), ("B", 1), ("C", 2), ("D", 3)]);
+
+        // C, auto-created by the first message, is declared by an update
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::UpdateNode { id, properties, .. }
+                if id == "C" && properties.custom["auto_created"] == "false"
+        )));
+        assert_eq!(crate::plantuml::undeclared_participants(&events), vec!["A"]);
+    }
+
+    #[test]
+    fn test_box_container_group() {
+        let input = r#"@startuml
+actor User
+box "Internal" #LightBlue
+participant API
+database DB
+end box
+User -> API: request
+API -> DB: query
+@enduml"#;
+
+        let events = parse(input).unwrap();
+
+        let group = events.iter().find_map(|e| match e {
+            GraphEvent::AddGroup {
+                id,
+                label,
+                members,
+                group_type: GroupType::Container,
+                properties,
+            } => Some((id, label, members, properties)),
+            _ => None,
+        });
+        let (id, label, members, properties) = group.unwrap();
+        assert_eq!(id, "box-0");
+        assert_eq!(label.as_deref(), Some("Internal"));
+        assert_eq!(members, &["API".to_string(), "DB".to_string()]);
+        let style = properties.style.as_ref().unwrap();
+        assert_eq!(style.background_color.as_deref(), Some("LightBlue"));
+
+        assert_eq!(node_label(&events, "API"), Some("API"));
+        assert_eq!(node_label(&events, "DB"), Some("DB"));
+    }
+
+    #[test]
+    fn test_dividers_and_delays() {
+        let input = r"@startuml
+Alice -> Bob: login
+== Phase 2 ==
+Bob -> Alice: token
+...
+... 5 minutes later ...
+Alice -> Bob: refresh
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let markers: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    node_type: NodeType::Custom(kind),
+                    properties,
+                } if kind != "note" => Some((
+                    id.as_str(),
+                    kind.as_str(),
+                    label.as_deref(),
+                    properties.position.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                (
+                    "divider-0",
+                    "divider",
+                    Some("Phase 2"),
+                    Some(Position::Sequential { order: 1 })
+                ),
+                (
+                    "delay-1",
+                    "delay",
+                    None,
+                    Some(Position::Sequential { order: 2 })
+                ),
+                (
+                    "delay-2",
+                    "delay",
+                    Some("5 minutes later"),
+                    Some(Position::Sequential { order: 2 })
+                ),
+            ]
+        );
+        let messages = events
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddEdge { .. }))
+            .count();
+        assert_eq!(messages, 3);
+    }
+
+    #[test]
+    fn test_title_header_and_footer() {
+        let input = r"@startuml
+title Checkout Flow
+right header
+Draft
+Confidential
+end header
+footer Page 1
+A -> B: pay
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let names: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::SetGraphMeta { name, directed, .. } => {
+                    Some((name.as_deref(), *directed))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec![(Some("Checkout Flow"), true)]);
+
+        let layout = events
+            .iter()
+            .find_map(|e| match e {
+                GraphEvent::SetLayout { properties, .. } => Some(&properties.custom),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(layout["header"], "Draft\nConfidential");
+        assert_eq!(layout["header_align"], "right");
+        assert_eq!(layout["footer"], "Page 1");
+        assert!(!layout.contains_key("footer_align"));
+
+        let block = r"@startuml
+title
+  Multi-line
+  Title
+end title
+A -> B: pay
+@enduml";
+        let events = parse(block).unwrap();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::SetGraphMeta { name: Some(name), .. } if name == "Multi-line\nTitle"
+        )));
+    }
+
+    #[test]
+    fn test_divider_markup() {
+        let input = r"@startuml
+A -> B: hello
+== <b>Phase 2</b> <color:red>(beta)</color> ==
+== Plain ==
+== Retry while n < 3 ==
+== Q&amp;A <size:18>big</size> ==
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let dividers: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    label,
+                    node_type: NodeType::Custom(kind),
+                    properties,
+                    ..
+                } if kind == "divider" => Some((
+                    label.as_deref(),
+                    properties.custom.get("markup").map(String::as_str),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            dividers,
+            vec![
+                (
+                    Some("Phase 2 (beta)"),
+                    Some("<b>Phase 2</b> <color:red>(beta)</color>")
+                ),
+                (Some("Plain"), None),
+                (Some("Retry while n < 3"), None),
+                (Some("Q&amp;A big"), Some("Q&amp;A <size:18>big</size>")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_named_block_ends() {
+        let input = r"@startuml
+box Backend
+participant API
+end box
+alt cached
+note over API
+  served from
+  memory
+end note
+API -> A: hit
+end
+@enduml";
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let events = parse_with_options(input, &strict).unwrap();
+
+        assert_eq!(
+            fragments(&events),
+            vec![("alt-0", "alt", Some("cached"), vec!["msg-0"])]
+        );
+        assert_eq!(node_label(&events, "note-0"), Some("served from\nmemory"));
+    }
+
+    #[test]
+    fn test_mismatched_block_end() {
+        let input = r"@startuml
+alt cached
+A -> B: hit
+end note
+@enduml";
+
+        // Leniently, any `end` closes the innermost block
+        let events = parse(input).unwrap();
+        assert_eq!(
+            fragments(&events),
+            vec![("alt-0", "alt", Some("cached"), vec!["msg-0"])]
+        );
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_with_options(input, &strict).unwrap_err(),
+            "`end note` closes the `alt` block"
+        );
+    }
+
+    #[test]
+    fn test_break_block_group() {
+        let input = r"@startuml
+A -> B: request
+break quota exceeded
+B --> A: rejected
+end
+B -> C: continue
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        assert_eq!(
+            fragments(&events),
+            vec![("break-0", "break", Some("quota exceeded"), vec!["msg-1"])]
+        );
+    }
+
+    #[test]
+    fn test_rnote_spanning_participants() {
+        let input = r"@startuml
+participant A
+participant B
+participant C
+A -> B: hello
+rnote over A, B, C : shared state
+hnote left B : waiting
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let notes: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode {
+                    id,
+                    label,
+                    node_type: NodeType::Custom(kind),
+                    properties,
+                } if kind == "note" => Some((
+                    id.as_str(),
+                    label.as_deref(),
+                    properties.custom["note_shape"].as_str(),
+                    properties.custom["participants"].as_str(),
+                    properties.position.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (
+                    "note-0",
+                    Some("shared state"),
+                    "rectangle",
+                    "A,B,C",
+                    Some(Position::Sequential { order: 1 })
+                ),
+                (
+                    "note-1",
+                    Some("waiting"),
+                    "hexagon",
+                    "B",
+                    Some(Position::Sequential { order: 1 })
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,236 @@
+use crate::events::{EdgeType, GraphEvent, MessageType, NodeType};
+use std::collections::HashMap;
+
+/// One message of a sequence diagram, in render order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineEntry {
+    /// Position among the diagram's messages, the `N` of its `msg-N` id
+    pub index: u32,
+    /// Number shown by `autonumber`, `None` while numbering is stopped
+    pub sequence: Option<u32>,
+    pub from: String,
+    pub to: String,
+    pub label: Option<String>,
+    pub message_type: MessageType,
+    /// Participants with an open activation when the message is sent,
+    /// in the order they were activated
+    pub activations: Vec<String>,
+}
+
+/// Flatten a sequence diagram's events into its ordered messages
+///
+/// Messages are listed in the order they were sent, whatever `autonumber`
+/// displays for them. Activation state is read from the `activation_depth`
+/// the parser records on `UpdateNode` events.
+pub fn timeline(events: &[GraphEvent]) -> Vec<TimelineEntry> {
+    let mut entries = Vec::new();
+    let mut active: Vec<String> = Vec::new();
+    let mut index = 0;
+
+    for event in events {
+        match event {
+            GraphEvent::UpdateNode { id, properties, .. } => {
+                let Some(depth) = properties.custom.get("activation_depth") else {
+                    continue;
+                };
+                if depth == "0" {
+                    active.retain(|participant| participant != id);
+                } else if !active.contains(id) {
+                    active.push(id.clone());
+                }
+            }
+            GraphEvent::AddEdge {
+                from,
+                to,
+                label,
+                edge_type:
+                    EdgeType::Message {
+                        message_type,
+                        sequence,
+                    },
+                ..
+            } => {
+                entries.push(TimelineEntry {
+                    index,
+                    sequence: *sequence,
+                    from: from.clone(),
+                    to: to.clone(),
+                    label: label.clone(),
+                    message_type: message_type.clone(),
+                    activations: active.clone(),
+                });
+                index += 1;
+            }
+            GraphEvent::Clear => {
+                entries.clear();
+                active.clear();
+                index = 0;
+            }
+            _ => {}
+        }
+    }
+
+    entries
+}
+
+/// First and last message index each participant sends or receives
+///
+/// Indices count messages in the order they were sent, as
+/// [`TimelineEntry::index`], so they are unaffected by `autonumber`. Lets a
+/// renderer trim lifelines to the messages that use them. Participants that
+/// are declared but never messaged map to `None`, so they can't be mistaken
+/// for one used only by the first message; notes, dividers, and delays are
+/// not participants.
+pub fn lifeline_spans(events: &[GraphEvent]) -> HashMap<String, Option<(u32, u32)>> {
+    let mut spans: HashMap<String, Option<(u32, u32)>> = HashMap::new();
+    let mut messages = 0;
+
+    for event in events {
+        match event {
+            GraphEvent::AddNode { id, node_type, .. }
+                if !matches!(node_type, NodeType::Custom(_)) =>
+            {
+                spans.entry(id.clone()).or_insert(None);
+            }
+            GraphEvent::AddEdge {
+                from,
+                to,
+                edge_type: EdgeType::Message { .. },
+                ..
+            } => {
+                let index = messages;
+                messages += 1;
+                for participant in [from, to] {
+                    let span = spans.entry(participant.clone()).or_insert(None);
+                    *span = Some(span.map_or((index, index), |(first, _)| (first, index)));
+                }
+            }
+            GraphEvent::Clear => {
+                spans.clear();
+                messages = 0;
+            }
+            _ => {}
+        }
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plantuml::parse;
+
+    #[test]
+    fn test_web_database_timeline() {
+        let input = r#"@startuml
+actor User
+participant "Web Server" as Web
+database "Database" as DB
+
+User -> Web: HTTP Request
+activate Web
+Web -> DB: Query
+activate DB
+DB --> Web: Results
+deactivate DB
+Web --> User: HTTP Response
+deactivate Web
+@enduml"#;
+
+        let entries = timeline(&parse(input).unwrap());
+
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.sequence,
+                    entry.from.as_str(),
+                    entry.to.as_str(),
+                    entry.label.as_deref(),
+                    entry.activations.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some(0), "User", "Web", Some("HTTP Request"), vec![]),
+                (Some(1), "Web", "DB", Some("Query"), vec!["Web"]),
+                (Some(2), "DB", "Web", Some("Results"), vec!["Web", "DB"]),
+                (Some(3), "Web", "User", Some("HTTP Response"), vec!["Web"]),
+            ]
+        );
+        assert_eq!(entries[0].message_type, MessageType::Synchronous);
+        assert_eq!(entries[2].message_type, MessageType::Return);
+    }
+
+    #[test]
+    fn test_web_database_lifeline_spans() {
+        let input = r#"@startuml
+actor User
+participant "Web Server" as Web
+database "Database" as DB
+participant Cache
+
+User -> Web: HTTP Request
+note over Web: routing
+Web -> DB: Query
+DB --> Web: Results
+Web --> User: HTTP Response
+@enduml"#;
+
+        let spans = lifeline_spans(&parse(input).unwrap());
+
+        let mut summary: Vec<_> = spans
+            .iter()
+            .map(|(id, span)| (id.as_str(), *span))
+            .collect();
+        summary.sort_unstable();
+        assert_eq!(
+            summary,
+            vec![
+                ("Cache", None),
+                ("DB", Some((1, 2))),
+                ("User", Some((0, 3))),
+                ("Web", Some((0, 3))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_autonumber_keeps_emission_order() {
+        let input = r"@startuml
+A -> B: one
+autonumber 10
+B -> C: two
+autonumber stop
+C -> D: three
+autonumber
+D -> A: four
+@enduml";
+
+        let events = parse(input).unwrap();
+
+        let entries = timeline(&events);
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.index, entry.sequence, entry.label.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, Some(0), Some("one")),
+                (1, Some(10), Some("two")),
+                (2, None, Some("three")),
+                (3, Some(1), Some("four")),
+            ]
+        );
+
+        let spans = lifeline_spans(&events);
+        assert_eq!(spans["B"], Some((0, 1)));
+        assert_eq!(spans["C"], Some((1, 2)));
+        assert_eq!(spans["D"], Some((2, 3)));
+        assert_eq!(spans["A"], Some((0, 3)));
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 /// Arrow types in `PlantUML` - used only during parsing
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArrowType {
@@ -36,6 +38,48 @@ impl ArrowType {
         }
     }
 
+    /// Parse an arrow with optional end decorations such as `o->`, `->o`,
+    /// `->x`, or `->?`, returning the base arrow and its decorations
+    pub fn parse_decorated(s: &str) -> Option<(Self, BTreeSet<ArrowDecoration>)> {
+        let mut decorations = BTreeSet::new();
+        let mut body = s;
+
+        if let Some(rest) = body.strip_prefix('o') {
+            decorations.insert(ArrowDecoration::TailCircle);
+            body = rest;
+        } else if let Some(rest) = body.strip_prefix('?') {
+            decorations.insert(ArrowDecoration::Short);
+            body = rest;
+        }
+
+        if let Some(rest) = body.strip_suffix('o') {
+            decorations.insert(ArrowDecoration::HeadCircle);
+            body = rest;
+        } else if let Some(rest) = body.strip_suffix('x') {
+            decorations.insert(ArrowDecoration::Lost);
+            body = rest;
+        } else if let Some(rest) = body.strip_suffix('?') {
+            decorations.insert(ArrowDecoration::Short);
+            body = rest;
+        }
+
+        let arrow_type = Self::parse_arrow(body)?;
+
+        // Reversed arrows point left, so their head is the written prefix
+        if arrow_type.is_reversed() {
+            decorations = decorations
+                .into_iter()
+                .map(|decoration| match decoration {
+                    ArrowDecoration::TailCircle => ArrowDecoration::HeadCircle,
+                    ArrowDecoration::HeadCircle => ArrowDecoration::TailCircle,
+                    other => other,
+                })
+                .collect();
+        }
+
+        Some((arrow_type, decorations))
+    }
+
     pub fn to_message_type(self) -> crate::events::MessageType {
         match self {
             Self::SolidSync | Self::LeftSync | Self::BiDirectional => {
@@ -62,3 +106,81 @@ impl ArrowType {
         )
     }
 }
+
+/// Decorations drawn at the ends of a `PlantUML` arrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArrowDecoration {
+    /// Circle at the arrow head (`->o`)
+    HeadCircle,
+    /// Circle at the arrow tail (`o->`)
+    TailCircle,
+    /// Lost message ending in a cross (`->x`)
+    Lost,
+    /// Short arrow to or from the diagram edge (`?->`, `->?`)
+    Short,
+}
+
+impl ArrowDecoration {
+    /// Key under which the decoration is recorded in edge `properties.custom`
+    pub fn custom_key(self) -> &'static str {
+        match self {
+            Self::HeadCircle => "head_circle",
+            Self::TailCircle => "tail_circle",
+            Self::Lost => "lost",
+            Self::Short => "short",
+        }
+    }
+}
+
+/// Inline style of a `PlantUML` arrow, written in brackets as in
+/// `-[#red]>` or `-[#0000FF,dashed]->`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArrowStyle {
+    /// Line color, keeping its `#` for hex codes
+    pub color: Option<String>,
+    /// `dashed`, `dotted`, `bold`, or `plain`
+    pub line_style: Option<String>,
+    /// Line width from `thickness=N`
+    pub thickness: Option<f32>,
+}
+
+impl ArrowStyle {
+    /// Split an arrow into the plain arrow and its bracketed style, if any
+    ///
+    /// `-[#red]>` becomes `->` with color `red`; an arrow without brackets
+    /// is returned unchanged.
+    pub fn split(arrow: &str) -> (String, Option<Self>) {
+        let Some((before, rest)) = arrow.split_once('[') else {
+            return (arrow.to_string(), None);
+        };
+        let Some((modifiers, after)) = rest.split_once(']') else {
+            return (arrow.to_string(), None);
+        };
+
+        let mut style = Self::default();
+        for modifier in modifiers.split(',').map(str::trim) {
+            if let Some(color) = modifier.strip_prefix('#') {
+                style.color = Some(normalize_color(color));
+            } else if let Some(thickness) = modifier.strip_prefix("thickness=") {
+                style.thickness = thickness.parse().ok();
+            } else if !modifier.is_empty() {
+                style.line_style = Some(modifier.to_string());
+            }
+        }
+
+        // `-[#red]>` keeps the single dash of `->`, while `-[#red]->`
+        // writes both halves of `-->`
+        (format!("{before}{after}"), Some(style))
+    }
+}
+
+/// Restore the `#` of a hex code such as `FF0000` or `FFFFFF` written after
+/// `#`; color names pass through
+pub(crate) fn normalize_color(value: &str) -> String {
+    let is_hex = matches!(value.len(), 3 | 6) && value.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        format!("#{value}")
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,483 @@
+use crate::events::{
+    EdgeType, EventResult, GraphEvent, GroupType, LayoutType, NodeType, Properties,
+};
+use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableDiGraph};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Node weight carried by the reduced graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeData {
+    pub id: String,
+    pub label: Option<String>,
+    pub node_type: NodeType,
+    pub properties: Properties,
+}
+
+/// Edge weight carried by the reduced graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeData {
+    pub id: String,
+    pub label: Option<String>,
+    pub edge_type: EdgeType,
+    pub properties: Properties,
+}
+
+/// A group tracked by the reducer
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupData {
+    pub id: String,
+    pub label: Option<String>,
+    pub members: Vec<String>,
+    pub group_type: GroupType,
+    pub properties: Properties,
+}
+
+/// Graph header from `SetGraphMeta`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphMeta {
+    pub name: Option<String>,
+    pub directed: bool,
+    pub strict: bool,
+}
+
+/// Graph state produced by folding events
+///
+/// A stable graph is used so node and edge indices stay valid when
+/// elements are removed.
+#[derive(Debug, Clone, Default)]
+pub struct GraphData {
+    pub graph: StableDiGraph<NodeData, EdgeData>,
+    pub node_map: HashMap<String, NodeIndex>,
+    pub edge_map: HashMap<String, EdgeIndex>,
+    /// Edge id to `(from, to)` node ids
+    pub edge_endpoints: HashMap<String, (String, String)>,
+    pub groups: HashMap<String, GroupData>,
+    pub layout: Option<(LayoutType, Properties)>,
+    /// Graph name, directedness, and strictness from `SetGraphMeta`
+    pub meta: Option<GraphMeta>,
+}
+
+impl GraphData {
+    /// Whether the graph holds no nodes, edges, or groups
+    pub fn is_empty(&self) -> bool {
+        self.graph.node_count() == 0 && self.graph.edge_count() == 0 && self.groups.is_empty()
+    }
+}
+
+/// Folds a stream of `GraphEvent`s into graph state
+#[derive(Debug, Clone, Default)]
+pub struct EventReducer {
+    data: GraphData,
+}
+
+impl EventReducer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current graph state
+    pub fn data(&self) -> &GraphData {
+        &self.data
+    }
+
+    /// Clone of the current graph state, e.g. to render an intermediate step
+    pub fn snapshot(&self) -> GraphData {
+        self.data.clone()
+    }
+
+    /// Node ids along a shortest directed path from `from` to `to`
+    ///
+    /// Found by breadth-first search, so every edge counts as one step. The
+    /// path includes both ends; `None` means `to` is unreachable or either
+    /// node is missing.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let graph = &self.data.graph;
+        let start = *self.data.node_map.get(from)?;
+        let goal = *self.data.node_map.get(to)?;
+
+        let mut previous: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            if node == goal {
+                break;
+            }
+            for next in graph.neighbors(node) {
+                if seen.insert(next) {
+                    previous.insert(next, node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        if !seen.contains(&goal) {
+            return None;
+        }
+
+        let mut path = vec![graph[goal].id.clone()];
+        let mut node = goal;
+        while let Some(&prior) = previous.get(&node) {
+            path.push(graph[prior].id.clone());
+            node = prior;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Apply every event in order
+    pub fn apply_all(&mut self, events: &[GraphEvent]) -> Vec<EventResult> {
+        events.iter().map(|event| self.apply(event)).collect()
+    }
+
+    /// Apply a single event
+    pub fn apply(&mut self, event: &GraphEvent) -> EventResult {
+        match event {
+            GraphEvent::AddNode {
+                id,
+                label,
+                node_type,
+                properties,
+            } => self.add_node(NodeData {
+                id: id.clone(),
+                label: label.clone(),
+                node_type: node_type.clone(),
+                properties: properties.clone(),
+            }),
+            GraphEvent::UpdateNode {
+                id,
+                label,
+                properties,
+            } => self.update_node(id, label.as_ref(), properties),
+            GraphEvent::RemoveNode { id } => self.remove_node(id),
+            GraphEvent::AddEdge {
+                id,
+                from,
+                to,
+                edge_type,
+                label,
+                properties,
+            } => self.add_edge(
+                from,
+                to,
+                EdgeData {
+                    id: id.clone(),
+                    label: label.clone(),
+                    edge_type: edge_type.clone(),
+                    properties: properties.clone(),
+                },
+            ),
+            GraphEvent::UpdateEdge {
+                id,
+                label,
+                properties,
+            } => self.update_edge(id, label.as_ref(), properties),
+            GraphEvent::RemoveEdge { id } => self.remove_edge(id),
+            GraphEvent::AddGroup {
+                id,
+                label,
+                members,
+                group_type,
+                properties,
+            } => {
+                self.data.groups.insert(
+                    id.clone(),
+                    GroupData {
+                        id: id.clone(),
+                        label: label.clone(),
+                        members: members.clone(),
+                        group_type: group_type.clone(),
+                        properties: properties.clone(),
+                    },
+                );
+                EventResult::Success
+            }
+            GraphEvent::UpdateGroup { id, members } => match self.data.groups.get_mut(id) {
+                Some(group) => {
+                    group.members.clone_from(members);
+                    EventResult::Success
+                }
+                None => EventResult::Invalid(format!("Group not found: {id}")),
+            },
+            GraphEvent::RemoveGroup { id } => match self.data.groups.remove(id) {
+                Some(_) => EventResult::Success,
+                None => EventResult::Invalid(format!("Group not found: {id}")),
+            },
+            GraphEvent::SetLayout {
+                layout_type,
+                properties,
+            } => {
+                self.data.layout = Some((layout_type.clone(), properties.clone()));
+                EventResult::Success
+            }
+            GraphEvent::SetGraphMeta {
+                name,
+                directed,
+                strict,
+            } => {
+                self.data.meta = Some(GraphMeta {
+                    name: name.clone(),
+                    directed: *directed,
+                    strict: *strict,
+                });
+                EventResult::Success
+            }
+            GraphEvent::Clear => {
+                self.data = GraphData::default();
+                EventResult::Success
+            }
+            GraphEvent::BatchStart | GraphEvent::BatchEnd => EventResult::Success,
+        }
+    }
+
+    fn remove_edge(&mut self, id: &str) -> EventResult {
+        match self.data.edge_map.remove(id) {
+            Some(index) => {
+                self.data.graph.remove_edge(index);
+                self.data.edge_endpoints.remove(id);
+                EventResult::Success
+            }
+            None => EventResult::EdgeNotFound(id.to_string()),
+        }
+    }
+
+    fn add_node(&mut self, node: NodeData) -> EventResult {
+        if self.data.node_map.contains_key(&node.id) {
+            return EventResult::NodeExists(node.id);
+        }
+        let id = node.id.clone();
+        let index = self.data.graph.add_node(node);
+        self.data.node_map.insert(id, index);
+        EventResult::Success
+    }
+
+    fn update_node(
+        &mut self,
+        id: &str,
+        label: Option<&String>,
+        properties: &Properties,
+    ) -> EventResult {
+        let Some(node) = self
+            .data
+            .node_map
+            .get(id)
+            .and_then(|&index| self.data.graph.node_weight_mut(index))
+        else {
+            return EventResult::NodeNotFound(id.to_string());
+        };
+        if let Some(label) = label {
+            node.label = Some(label.clone());
+        }
+        merge_properties(&mut node.properties, properties);
+        EventResult::Success
+    }
+
+    fn update_edge(
+        &mut self,
+        id: &str,
+        label: Option<&String>,
+        properties: &Properties,
+    ) -> EventResult {
+        let Some(edge) = self
+            .data
+            .edge_map
+            .get(id)
+            .and_then(|&index| self.data.graph.edge_weight_mut(index))
+        else {
+            return EventResult::EdgeNotFound(id.to_string());
+        };
+        if let Some(label) = label {
+            edge.label = Some(label.clone());
+        }
+        merge_properties(&mut edge.properties, properties);
+        EventResult::Success
+    }
+
+    fn remove_node(&mut self, id: &str) -> EventResult {
+        let Some(index) = self.data.node_map.remove(id) else {
+            return EventResult::NodeNotFound(id.to_string());
+        };
+        // Removing a node drops its incident edges from the graph; forget
+        // their ids too so they can be added again
+        self.data.graph.remove_node(index);
+        let edge_map = &mut self.data.edge_map;
+        self.data.edge_endpoints.retain(|edge_id, (from, to)| {
+            let incident = from == id || to == id;
+            if incident {
+                edge_map.remove(edge_id);
+            }
+            !incident
+        });
+        EventResult::Success
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, edge: EdgeData) -> EventResult {
+        if self.data.edge_map.contains_key(&edge.id) {
+            return EventResult::EdgeExists(edge.id);
+        }
+        let Some(&from_index) = self.data.node_map.get(from) else {
+            return EventResult::NodeNotFound(from.to_string());
+        };
+        let Some(&to_index) = self.data.node_map.get(to) else {
+            return EventResult::NodeNotFound(to.to_string());
+        };
+        let id = edge.id.clone();
+        let index = self.data.graph.add_edge(from_index, to_index, edge);
+        self.data.edge_map.insert(id.clone(), index);
+        self.data
+            .edge_endpoints
+            .insert(id, (from.to_string(), to.to_string()));
+        EventResult::Success
+    }
+}
+
+/// Overlay `update` onto `target`, keeping fields the update leaves unset
+fn merge_properties(target: &mut Properties, update: &Properties) {
+    if update.style.is_some() {
+        target.style.clone_from(&update.style);
+    }
+    if update.position.is_some() {
+        target.position.clone_from(&update.position);
+    }
+    target
+        .custom
+        .extend(update.custom.iter().map(|(k, v)| (k.clone(), v.clone())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dot;
+
+    #[test]
+    fn test_reduce_dot_events() {
+        let events = dot::parse("digraph {\n    A -> B;\n    B -> C;\n}\n");
+
+        let mut reducer = EventReducer::new();
+        let results = reducer.apply_all(&events);
+
+        assert!(results.iter().all(|r| *r == EventResult::Success));
+        assert_eq!(reducer.data().graph.node_count(), 3);
+        assert_eq!(reducer.data().graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_graph_meta_keeps_strict() {
+        let mut reducer = EventReducer::new();
+        reducer.apply_all(&dot::parse("strict graph G {\n    A -- B;\n}\n"));
+
+        assert_eq!(
+            reducer.data().meta,
+            Some(GraphMeta {
+                name: Some("G".to_string()),
+                directed: false,
+                strict: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_in_org_chart() {
+        let events = dot::parse(
+            r#"
+            digraph {
+                subgraph cluster_tenant {
+                    label="Tenant: Acme Corp"
+                    subgraph cluster_lob {
+                        label="LOB: Contact Center"
+                        subgraph cluster_site {
+                            label="Site: Main Site"
+                            user1 [label="Alice Smith"];
+                        }
+                    }
+                }
+            }
+        "#,
+        );
+        let mut reducer = EventReducer::new();
+        reducer.apply_all(&events);
+
+        assert_eq!(
+            reducer.shortest_path("Acme Corp", "Alice Smith"),
+            Some(vec![
+                "Acme Corp".to_string(),
+                "Contact Center".to_string(),
+                "Main Site".to_string(),
+                "Alice Smith".to_string(),
+            ])
+        );
+        assert_eq!(
+            reducer.shortest_path("Main Site", "Main Site"),
+            Some(vec!["Main Site".to_string()])
+        );
+        assert_eq!(reducer.shortest_path("Alice Smith", "Acme Corp"), None);
+        assert_eq!(reducer.shortest_path("Acme Corp", "nobody"), None);
+    }
+
+    #[test]
+    fn test_snapshot_then_clear() {
+        let mut reducer = EventReducer::new();
+        reducer.apply_all(&[
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("B", "B"),
+            GraphEvent::simple_edge("A", "B"),
+            GraphEvent::AddGroup {
+                id: "g".to_string(),
+                label: None,
+                members: vec!["A".to_string(), "B".to_string()],
+                group_type: GroupType::Cluster,
+                properties: Properties::default(),
+            },
+        ]);
+
+        let snapshot = reducer.snapshot();
+        assert_eq!(reducer.apply(&GraphEvent::Clear), EventResult::Success);
+
+        assert!(reducer.data().is_empty());
+        assert!(reducer.data().node_map.is_empty());
+        assert!(reducer.data().edge_map.is_empty());
+
+        // The snapshot is unaffected by later events
+        assert_eq!(snapshot.graph.node_count(), 2);
+        assert_eq!(snapshot.graph.edge_count(), 1);
+        assert_eq!(snapshot.groups.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_and_missing_elements() {
+        let mut reducer = EventReducer::new();
+
+        reducer.apply(&GraphEvent::simple_node("A", "A"));
+        assert_eq!(
+            reducer.apply(&GraphEvent::simple_node("A", "A")),
+            EventResult::NodeExists("A".to_string())
+        );
+        assert_eq!(
+            reducer.apply(&GraphEvent::simple_edge("A", "B")),
+            EventResult::NodeNotFound("B".to_string())
+        );
+    }
+
+    #[test]
+    fn test_remove_node_frees_incident_edge_ids() {
+        let mut reducer = EventReducer::new();
+        reducer.apply_all(&[
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("B", "B"),
+            GraphEvent::simple_edge("A", "B"),
+            GraphEvent::RemoveNode {
+                id: "A".to_string(),
+            },
+        ]);
+
+        let data = reducer.data();
+        assert_eq!(data.graph.edge_count(), 0);
+        assert!(data.edge_map.is_empty());
+        assert!(data.edge_endpoints.is_empty());
+
+        let results = reducer.apply_all(&[
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_edge("A", "B"),
+        ]);
+        assert_eq!(results, vec![EventResult::Success, EventResult::Success]);
+        assert_eq!(reducer.data().graph.edge_count(), 1);
+    }
+}
@@ -0,0 +1,488 @@
+use crate::events::{GraphEvent, NodeType, Properties, group_tree};
+use std::collections::{HashMap, HashSet};
+
+/// Extract a single group as a self-contained event stream
+///
+/// The result holds the group's nodes (including those of nested container
+/// groups), the groups themselves, and the edges between those nodes. Edges
+/// crossing the group boundary are dropped.
+pub fn extract_group(events: &[GraphEvent], group_id: &str) -> Vec<GraphEvent> {
+    extract(events, group_id, false)
+}
+
+/// Extract a single group, keeping edges that cross its boundary
+///
+/// Like [`extract_group`], but an edge with one endpoint outside the group is
+/// kept and the outside endpoint is added as a stub node of type
+/// `NodeType::External` with `custom["stub"] = "true"`.
+pub fn extract_group_with_stubs(events: &[GraphEvent], group_id: &str) -> Vec<GraphEvent> {
+    extract(events, group_id, true)
+}
+
+fn extract(events: &[GraphEvent], group_id: &str, stub_boundary: bool) -> Vec<GraphEvent> {
+    let tree = group_tree(events);
+    let nodes: HashSet<String> = tree.descendants(group_id).into_iter().collect();
+
+    let mut groups = HashSet::new();
+    let mut pending = vec![group_id];
+    while let Some(group) = pending.pop() {
+        if groups.insert(group.to_string()) {
+            pending.extend(tree.children(group));
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut edges = HashSet::new();
+    let mut stubs = HashSet::new();
+
+    for event in events {
+        match event {
+            GraphEvent::AddNode { id, .. }
+            | GraphEvent::UpdateNode { id, .. }
+            | GraphEvent::RemoveNode { id }
+                if nodes.contains(id) =>
+            {
+                result.push(event.clone());
+            }
+            GraphEvent::AddEdge { id, from, to, .. } => {
+                let from_inside = nodes.contains(from);
+                let to_inside = nodes.contains(to);
+                if from_inside && to_inside {
+                    edges.insert(id.clone());
+                    result.push(event.clone());
+                } else if stub_boundary && (from_inside || to_inside) {
+                    let outside = if from_inside { to } else { from };
+                    if stubs.insert(outside.clone()) {
+                        result.push(stub_node(outside));
+                    }
+                    edges.insert(id.clone());
+                    result.push(event.clone());
+                }
+            }
+            GraphEvent::UpdateEdge { id, .. } | GraphEvent::RemoveEdge { id }
+                if edges.contains(id) =>
+            {
+                result.push(event.clone());
+            }
+            GraphEvent::AddGroup { id, .. }
+            | GraphEvent::UpdateGroup { id, .. }
+            | GraphEvent::RemoveGroup { id }
+                if groups.contains(id) =>
+            {
+                result.push(event.clone());
+            }
+            GraphEvent::SetLayout { .. }
+            | GraphEvent::SetGraphMeta { .. }
+            | GraphEvent::BatchStart
+            | GraphEvent::BatchEnd => {
+                result.push(event.clone());
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Drop nodes that have no incident edges
+///
+/// `AddNode`, `UpdateNode`, and `RemoveNode` events for orphaned nodes are
+/// removed, and the orphans are taken out of group member lists. All other
+/// events pass through unchanged.
+pub fn prune_orphans(events: &[GraphEvent]) -> Vec<GraphEvent> {
+    prune(events, false)
+}
+
+/// Drop nodes that have no incident edges, unless a group lists them
+///
+/// Like [`prune_orphans`], but a node that is a member of any group is kept
+/// even without edges.
+pub fn prune_orphans_keeping_group_members(events: &[GraphEvent]) -> Vec<GraphEvent> {
+    prune(events, true)
+}
+
+fn prune(events: &[GraphEvent], keep_group_members: bool) -> Vec<GraphEvent> {
+    let mut connected: HashSet<&str> = HashSet::new();
+    for event in events {
+        match event {
+            GraphEvent::AddEdge { from, to, .. } => {
+                connected.insert(from);
+                connected.insert(to);
+            }
+            GraphEvent::AddGroup { members, .. } | GraphEvent::UpdateGroup { members, .. }
+                if keep_group_members =>
+            {
+                connected.extend(members.iter().map(String::as_str));
+            }
+            _ => {}
+        }
+    }
+
+    let nodes: HashSet<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect();
+    let is_orphan = |id: &str| nodes.contains(id) && !connected.contains(id);
+
+    events
+        .iter()
+        .filter_map(|event| match event {
+            GraphEvent::AddNode { id, .. }
+            | GraphEvent::UpdateNode { id, .. }
+            | GraphEvent::RemoveNode { id }
+                if is_orphan(id) =>
+            {
+                None
+            }
+            GraphEvent::AddGroup {
+                id,
+                label,
+                members,
+                group_type,
+                properties,
+            } => Some(GraphEvent::AddGroup {
+                id: id.clone(),
+                label: label.clone(),
+                members: members.iter().filter(|m| !is_orphan(m)).cloned().collect(),
+                group_type: group_type.clone(),
+                properties: properties.clone(),
+            }),
+            GraphEvent::UpdateGroup { id, members } => Some(GraphEvent::UpdateGroup {
+                id: id.clone(),
+                members: members.iter().filter(|m| !is_orphan(m)).cloned().collect(),
+            }),
+            _ => Some(event.clone()),
+        })
+        .collect()
+}
+
+/// Merge parallel edges into one edge per `(from, to, edge_type)`
+///
+/// Only edges that are still present once the whole stream is applied are
+/// bundled; edges that a later `RemoveEdge`, `RemoveNode`, or `Clear` takes
+/// away pass through unchanged. The first surviving edge of each bundle
+/// keeps its id and properties; the labels of the surviving edges are joined
+/// with `", "`, and bundles of more than one edge record their size in
+/// `custom["multiplicity"]`. `UpdateEdge` events for the merged-away edges
+/// are dropped.
+pub fn bundle_edges(events: &[GraphEvent]) -> Vec<GraphEvent> {
+    let alive = surviving_edges(events);
+
+    // Indices into `events` of each bundle's edges, keyed by its first edge
+    let mut bundles: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut by_endpoints: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    for (index, event) in events.iter().enumerate() {
+        let GraphEvent::AddEdge {
+            from,
+            to,
+            edge_type,
+            ..
+        } = event
+        else {
+            continue;
+        };
+        if !alive.contains(&index) {
+            continue;
+        }
+        let firsts = by_endpoints.entry((from, to)).or_default();
+        let first = firsts.iter().copied().find(|&first| {
+            matches!(&events[first], GraphEvent::AddEdge { edge_type: kind, .. } if kind == edge_type)
+        });
+        if let Some(first) = first {
+            bundles.entry(first).or_default().push(index);
+        } else {
+            firsts.push(index);
+            bundles.insert(index, vec![index]);
+        }
+    }
+
+    let mut result: Vec<GraphEvent> = Vec::with_capacity(events.len());
+    let mut merged: HashSet<&str> = HashSet::new();
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            GraphEvent::AddEdge { id, .. } if alive.contains(&index) => {
+                let Some(bundle) = bundles.get(&index) else {
+                    merged.insert(id);
+                    continue;
+                };
+                result.push(bundled_edge(events, bundle));
+            }
+            GraphEvent::AddEdge { id, .. } => {
+                merged.remove(id.as_str());
+                result.push(event.clone());
+            }
+            GraphEvent::UpdateEdge { id, .. } if merged.contains(id.as_str()) => {}
+            GraphEvent::Clear => {
+                merged.clear();
+                result.push(event.clone());
+            }
+            _ => result.push(event.clone()),
+        }
+    }
+    result
+}
+
+/// The first edge of a bundle, carrying the labels and size of the whole
+/// bundle
+fn bundled_edge(events: &[GraphEvent], bundle: &[usize]) -> GraphEvent {
+    let mut edge = events[bundle[0]].clone();
+    if bundle.len() == 1 {
+        return edge;
+    }
+    let labels: Vec<&str> = bundle
+        .iter()
+        .filter_map(|&index| match &events[index] {
+            GraphEvent::AddEdge { label, .. } => label.as_deref(),
+            _ => None,
+        })
+        .collect();
+    if let GraphEvent::AddEdge {
+        label, properties, ..
+    } = &mut edge
+    {
+        if !labels.is_empty() {
+            *label = Some(labels.join(", "));
+        }
+        properties
+            .custom
+            .insert("multiplicity".to_string(), bundle.len().to_string());
+    }
+    edge
+}
+
+/// Indices of the `AddEdge` events whose edges are still present at the end
+/// of the stream
+///
+/// An edge is gone once its id is removed or added again, either endpoint is
+/// removed, or the graph is cleared.
+fn surviving_edges(events: &[GraphEvent]) -> HashSet<usize> {
+    let mut alive = HashSet::new();
+    let mut removed_edges: HashSet<&str> = HashSet::new();
+    let mut removed_nodes: HashSet<&str> = HashSet::new();
+
+    for (index, event) in events.iter().enumerate().rev() {
+        match event {
+            GraphEvent::Clear => break,
+            GraphEvent::RemoveEdge { id } => {
+                removed_edges.insert(id);
+            }
+            GraphEvent::RemoveNode { id } => {
+                removed_nodes.insert(id);
+            }
+            GraphEvent::AddEdge { id, from, to, .. } => {
+                let superseded = !removed_edges.insert(id);
+                let detached =
+                    removed_nodes.contains(from.as_str()) || removed_nodes.contains(to.as_str());
+                if !superseded && !detached {
+                    alive.insert(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    alive
+}
+
+fn stub_node(id: &str) -> GraphEvent {
+    let mut properties = Properties::default();
+    properties
+        .custom
+        .insert("stub".to_string(), "true".to_string());
+    GraphEvent::AddNode {
+        id: id.to_string(),
+        label: Some(id.to_string()),
+        node_type: NodeType::External,
+        properties,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dot;
+
+    const NESTED_ORG_CHART: &str = r#"
+        digraph {
+            subgraph cluster_tenant {
+                label="Tenant: Acme Corp"
+                subgraph cluster_lob {
+                    label="LOB: Contact Center"
+                    subgraph cluster_site {
+                        label="Site: Main Site"
+                        user1 [label="Alice Smith"];
+                    }
+                }
+            }
+        }
+    "#;
+
+    fn node_ids(events: &[GraphEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn edge_ids(events: &[GraphEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_group_from_nested_org_chart() {
+        let events = dot::parse(NESTED_ORG_CHART);
+        let extracted = extract_group(&events, "cluster_lob");
+
+        assert_eq!(
+            node_ids(&extracted),
+            vec!["Contact Center", "Main Site", "Alice Smith"]
+        );
+        assert_eq!(
+            edge_ids(&extracted),
+            vec!["Contact Center->Main Site", "Main Site->Alice Smith"]
+        );
+
+        let group_count = extracted
+            .iter()
+            .filter(|e| matches!(e, GraphEvent::AddGroup { .. }))
+            .count();
+        assert_eq!(group_count, 2);
+        assert!(matches!(extracted.first(), Some(GraphEvent::BatchStart)));
+        assert!(matches!(extracted.last(), Some(GraphEvent::BatchEnd)));
+    }
+
+    #[test]
+    fn test_prune_orphans() {
+        let events = vec![
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("B", "B"),
+            GraphEvent::simple_node("lonely", "lonely"),
+            GraphEvent::simple_node("member", "member"),
+            GraphEvent::simple_edge("A", "B"),
+            GraphEvent::AddGroup {
+                id: "g".to_string(),
+                label: None,
+                members: vec!["A".to_string(), "member".to_string()],
+                group_type: crate::events::GroupType::Cluster,
+                properties: Properties::default(),
+            },
+        ];
+
+        let pruned = prune_orphans(&events);
+        assert_eq!(node_ids(&pruned), vec!["A", "B"]);
+        assert_eq!(edge_ids(&pruned), vec!["A->B"]);
+        assert!(pruned.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddGroup { members, .. } if members == &["A".to_string()]
+        )));
+
+        let kept = prune_orphans_keeping_group_members(&events);
+        assert_eq!(node_ids(&kept), vec!["A", "B", "member"]);
+    }
+
+    #[test]
+    fn test_bundle_edges() {
+        let labelled = |label: &str| match GraphEvent::simple_edge("A", "B") {
+            GraphEvent::AddEdge {
+                id,
+                from,
+                to,
+                edge_type,
+                properties,
+                ..
+            } => GraphEvent::AddEdge {
+                id: format!("{id}-{label}"),
+                from,
+                to,
+                edge_type,
+                label: Some(label.to_string()),
+                properties,
+            },
+            _ => unreachable!(),
+        };
+        let events = vec![
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("B", "B"),
+            labelled("reads"),
+            GraphEvent::simple_edge("B", "A"),
+            labelled("writes"),
+            labelled("deletes"),
+            GraphEvent::RemoveEdge {
+                id: "A->B-writes".to_string(),
+            },
+        ];
+
+        let bundled = bundle_edges(&events);
+        // The removed edge and its removal pass through untouched
+        assert_eq!(
+            edge_ids(&bundled),
+            vec!["A->B-reads", "B->A", "A->B-writes"]
+        );
+        assert_eq!(bundled.len(), 6);
+
+        let (label, multiplicity) = bundled
+            .iter()
+            .find_map(|e| match e {
+                GraphEvent::AddEdge {
+                    id,
+                    label,
+                    properties,
+                    ..
+                } if id == "A->B-reads" => Some((
+                    label.clone(),
+                    properties.custom.get("multiplicity").cloned(),
+                )),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(label.as_deref(), Some("reads, deletes"));
+        assert_eq!(multiplicity.as_deref(), Some("2"));
+
+        // A single edge is left as it was
+        assert!(bundled.contains(&GraphEvent::simple_edge("B", "A")));
+
+        // Removing the first edge moves the bundle onto the next survivor
+        let events = vec![
+            GraphEvent::simple_node("A", "A"),
+            GraphEvent::simple_node("B", "B"),
+            labelled("e1"),
+            labelled("e2"),
+            GraphEvent::RemoveEdge {
+                id: "A->B-e1".to_string(),
+            },
+        ];
+        let edge_count = |events: &[GraphEvent]| {
+            let mut reducer = crate::reducer::EventReducer::new();
+            reducer.apply_all(events);
+            reducer.data().graph.edge_count()
+        };
+        let bundled = bundle_edges(&events);
+        assert_eq!(edge_count(&bundled), 1);
+        assert_eq!(edge_count(&bundled), edge_count(&events));
+        assert!(bundled.contains(&labelled("e2")));
+    }
+
+    #[test]
+    fn test_extract_group_with_stubs() {
+        let events = dot::parse(NESTED_ORG_CHART);
+        let extracted = extract_group_with_stubs(&events, "cluster_lob");
+
+        assert_eq!(
+            node_ids(&extracted),
+            vec!["Contact Center", "Acme Corp", "Main Site", "Alice Smith"]
+        );
+        assert!(edge_ids(&extracted).contains(&"Acme Corp->Contact Center"));
+    }
+}